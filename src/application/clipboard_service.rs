@@ -1,9 +1,19 @@
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::fmt;
+use std::time::SystemTime;
 
-use log::debug;
+use log::{debug, warn};
 
-use crate::domain::image_processor::make_transparent;
-use crate::domain::port::{ClipboardPort, ConfigPort};
+use crate::domain::history::{
+    HistoryEntry, HistoryImage, HistoryRecord, RecordedImage, THUMBNAIL_SIZE, thumbnail,
+};
+use crate::domain::pipeline::{Outcome, remove_background};
+use crate::domain::port::{
+    ClipboardPort, ConfigPort, HistoryPort, ImageCodec, ImageData, NoCodec, NoHistory,
+};
+use crate::domain::representation::Representation;
 
 /// Result of processing a clipboard image.
 #[derive(Debug, PartialEq, Eq)]
@@ -14,6 +24,88 @@ pub enum ProcessResult {
     NoImage,
     /// The clipboard has not changed since the last check (skipped).
     Skipped,
+    /// Automatic detection could not identify the background with enough
+    /// confidence, so the image was left unchanged.
+    Undetected,
+}
+
+/// Errors returned by [`ClipboardService`].
+///
+/// Wraps the error types of the clipboard port (`CE`), config port (`GE`)
+/// and history port (`HE`) so callers can react to the failed step, e.g. by
+/// pausing processing until a broken config file is fixed.
+#[derive(Debug)]
+pub enum ServiceError<CE, GE, HE = Infallible> {
+    /// Failed to read the clipboard change counter.
+    ChangeCount(CE),
+    /// Failed to read the image from the clipboard.
+    Read(CE),
+    /// Failed to write the processed image to the clipboard.
+    Write(CE),
+    /// Failed to load the configuration.
+    ConfigLoad(GE),
+    /// Failed to save the configuration.
+    ConfigSave(GE),
+    /// Failed to read the history.
+    History(HE),
+}
+
+impl<CE, GE, HE> fmt::Display for ServiceError<CE, GE, HE>
+where
+    CE: fmt::Display,
+    GE: fmt::Display,
+    HE: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::ChangeCount(e) => write!(f, "failed to read change count: {e}"),
+            ServiceError::Read(e) => write!(f, "failed to read clipboard: {e}"),
+            ServiceError::Write(e) => write!(f, "failed to write clipboard: {e}"),
+            ServiceError::ConfigLoad(e) => write!(f, "failed to load config: {e}"),
+            ServiceError::ConfigSave(e) => write!(f, "failed to save config: {e}"),
+            ServiceError::History(e) => write!(f, "failed to read history: {e}"),
+        }
+    }
+}
+
+impl<CE, GE, HE> std::error::Error for ServiceError<CE, GE, HE>
+where
+    CE: std::error::Error + 'static,
+    GE: std::error::Error + 'static,
+    HE: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ServiceError::ChangeCount(e) | ServiceError::Read(e) | ServiceError::Write(e) => {
+                Some(e)
+            }
+            ServiceError::ConfigLoad(e) | ServiceError::ConfigSave(e) => Some(e),
+            ServiceError::History(e) => Some(e),
+        }
+    }
+}
+
+/// Result of a [`ClipboardService`] operation, failing with the
+/// [`ServiceError`] of the service's ports.
+pub type ServiceResult<T, C, G, H = NoHistory> = Result<
+    T,
+    ServiceError<<C as ClipboardPort>::Error, <G as ConfigPort>::Error, <H as HistoryPort>::Error>,
+>;
+
+/// Result of reading the clipboard image: the image, if any, and the codec's
+/// metadata if it was read encoded.
+type ImageRead<K, C, G, H> =
+    ServiceResult<Option<(ImageData, Option<<K as ImageCodec>::Metadata>)>, C, G, H>;
+
+/// Number of original images kept for [`ClipboardService::restore_last_original`]
+/// unless set with [`ClipboardService::with_originals_kept`].
+pub const DEFAULT_ORIGINALS_KEPT: usize = 3;
+
+/// An image as read from the clipboard, before processing.
+#[derive(Debug)]
+struct Original<M> {
+    image: ImageData,
+    metadata: Option<M>,
 }
 
 /// Orchestrates the clipboard-to-transparent-image workflow.
@@ -25,17 +117,39 @@ pub enum ProcessResult {
 /// After writing a processed image back, it records the new counter value
 /// so its own write is not re-processed on the next poll.
 ///
+/// With an [`ImageCodec`] (see [`with_codec`](Self::with_codec)), images are
+/// read in the codec's encoded format where the clipboard supports it, and
+/// written back in that format and as HTML alongside the pixels, so metadata
+/// such as DPI survives processing.
+///
+/// The originals of the last few processed images are kept in memory, so
+/// [`restore_last_original`](Self::restore_last_original) can undo the
+/// processing. With a [`HistoryPort`] (see [`with_history`](Self::with_history)),
+/// every processed image is also recorded, and any earlier result or
+/// original can be copied again with
+/// [`copy_from_history`](Self::copy_from_history).
+///
 /// Depends on port traits only — no concrete infrastructure references.
 #[derive(Debug)]
-pub struct ClipboardService<C, G>
+pub struct ClipboardService<C, G, K = NoCodec, H = NoHistory>
 where
     C: ClipboardPort,
     G: ConfigPort,
+    K: ImageCodec,
+    H: HistoryPort,
 {
     clipboard: C,
     config: G,
+    /// Codec for encoded clipboard data; `None` uses raw pixels only.
+    codec: Option<K>,
+    /// History of processed images; `None` records nothing.
+    history: Option<H>,
     /// The clipboard change counter after the last write (or initial check).
     last_change_count: Cell<u64>,
+    /// Originals of the most recently processed images, oldest first.
+    originals: RefCell<VecDeque<Original<K::Metadata>>>,
+    /// Maximum length of `originals`.
+    originals_kept: usize,
 }
 
 impl<C, G> ClipboardService<C, G>
@@ -48,31 +162,95 @@ where
         Self {
             clipboard,
             config,
+            codec: None,
+            history: None,
             last_change_count: Cell::new(0),
+            originals: RefCell::new(VecDeque::new()),
+            originals_kept: DEFAULT_ORIGINALS_KEPT,
+        }
+    }
+}
+
+impl<C, G, K, H> ClipboardService<C, G, K, H>
+where
+    C: ClipboardPort,
+    G: ConfigPort,
+    K: ImageCodec,
+    H: HistoryPort,
+{
+    /// Reads and writes images through `codec` when the clipboard offers its
+    /// format, falling back to raw pixels otherwise.
+    ///
+    /// Originals kept so far are dropped, as their metadata belongs to the
+    /// previous codec.
+    pub fn with_codec<K2: ImageCodec>(self, codec: K2) -> ClipboardService<C, G, K2, H> {
+        ClipboardService {
+            clipboard: self.clipboard,
+            config: self.config,
+            codec: Some(codec),
+            history: self.history,
+            last_change_count: self.last_change_count,
+            originals: RefCell::new(VecDeque::new()),
+            originals_kept: self.originals_kept,
+        }
+    }
+
+    /// Records every processed image in `history`.
+    pub fn with_history<H2: HistoryPort>(self, history: H2) -> ClipboardService<C, G, K, H2> {
+        ClipboardService {
+            clipboard: self.clipboard,
+            config: self.config,
+            codec: self.codec,
+            history: Some(history),
+            last_change_count: self.last_change_count,
+            originals: self.originals,
+            originals_kept: self.originals_kept,
         }
     }
 
+    /// Keeps the originals of the last `count` processed images for
+    /// [`restore_last_original`](Self::restore_last_original) instead of
+    /// [`DEFAULT_ORIGINALS_KEPT`]; `0` disables restoring.
+    ///
+    /// Each original is held in memory uncompressed, at 4 bytes per pixel.
+    pub fn with_originals_kept(mut self, count: usize) -> Self {
+        self.originals_kept = count;
+        self.originals.get_mut().truncate(count);
+        self
+    }
+
     /// Processes the current clipboard image.
     ///
     /// 1. Checks the clipboard change counter (lightweight).
     /// 2. If unchanged, returns `Skipped` without reading the image.
-    /// 3. Reads the image from the clipboard.
-    /// 4. Loads the target color from configuration.
-    /// 5. Makes matching pixels transparent.
-    /// 6. Writes the processed image back to the clipboard.
-    /// 7. Records the new change counter to avoid re-processing.
+    /// 3. Reads the image from the clipboard, in the codec's encoded format
+    ///    if possible.
+    /// 4. Loads the target color and matching settings from configuration,
+    ///    or infers the target from the image border if auto-detection is on.
+    /// 5. Makes pixels near the target color transparent — fully, or faded
+    ///    along the alpha ramp if soft edges are configured.
+    /// 6. Applies the configured post-processing steps.
+    /// 7. Writes the processed image back to the clipboard, together with
+    ///    the codec's encoding (keeping the original metadata) and an HTML
+    ///    fallback.
+    /// 8. Records the new change counter to avoid re-processing, and keeps
+    ///    the unprocessed image for
+    ///    [`restore_last_original`](Self::restore_last_original) and in the
+    ///    history.
     ///
-    /// Returns `ProcessResult::NoImage` if no image is on the clipboard.
+    /// Returns `ProcessResult::NoImage` if no image is on the clipboard, and
+    /// `ProcessResult::Undetected` if auto-detection is not confident enough.
     ///
     /// # Errors
     ///
-    /// Returns an error string if any clipboard or config operation fails.
-    pub fn process_clipboard(&self) -> Result<ProcessResult, String> {
+    /// Returns a [`ServiceError`] naming the clipboard or config operation
+    /// that failed.
+    pub fn process_clipboard(&self) -> ServiceResult<ProcessResult, C, G, H> {
         // Step 1: Lightweight change detection via counter
         let current_count = self
             .clipboard
             .change_count()
-            .map_err(|e| format!("failed to read change count: {e}"))?;
+            .map_err(ServiceError::ChangeCount)?;
 
         if current_count == self.last_change_count.get() {
             return Ok(ProcessResult::Skipped);
@@ -85,11 +263,7 @@ where
         );
 
         // Step 2: Read the image
-        let Some(mut image) = self
-            .clipboard
-            .get_image()
-            .map_err(|e| format!("failed to read clipboard: {e}"))?
-        else {
+        let Some((mut image, metadata)) = self.read_image()? else {
             // No image — remember this counter so we don't re-check
             self.last_change_count.set(current_count);
             return Ok(ProcessResult::NoImage);
@@ -102,61 +276,363 @@ where
             image.pixels.len()
         );
 
-        // Sample corner pixel for diagnostics
-        if image.pixels.len() >= 4 {
-            let (r, g, b, a) = (
-                image.pixels[0],
-                image.pixels[1],
-                image.pixels[2],
-                image.pixels[3],
-            );
-            debug!("sample pixel (0,0): RGBA({r},{g},{b},{a})");
+        let settings = self
+            .config
+            .load_settings()
+            .map_err(ServiceError::ConfigLoad)?;
+
+        debug!("settings loaded: {settings:?}");
+
+        let original = image.clone();
+        let changed = match remove_background(&mut image, &settings) {
+            Outcome::Changed(changed) => {
+                debug!("{changed} pixel(s) matched target color");
+                changed
+            }
+            Outcome::Unchanged => {
+                debug!("no pixels matched — skipping clipboard write");
+                self.last_change_count.set(current_count);
+                return Ok(ProcessResult::Processed);
+            }
+            Outcome::Undetected(estimate) => {
+                if let Some(estimate) = estimate {
+                    debug!(
+                        "border background estimate: {} (confidence {:.2})",
+                        estimate.color, estimate.confidence
+                    );
+                }
+                debug!("background not detected confidently — skipping");
+                self.last_change_count.set(current_count);
+                return Ok(ProcessResult::Undetected);
+            }
+        };
+
+        let encoded = self.write_image(&image, metadata.as_ref())?;
+
+        // Record the counter right AFTER our write so we skip our own change,
+        // without counting anything copied meanwhile as ours
+        let new_count = self.record_own_write()?;
+
+        self.record_history(changed, image, encoded, &original, metadata.as_ref());
+        self.keep_original(Original {
+            image: original,
+            metadata,
+        });
+
+        debug!("transparency applied, image written back to clipboard (count: {new_count})");
+
+        Ok(ProcessResult::Processed)
+    }
+
+    /// Puts the original of the most recently processed image back on the
+    /// clipboard, undoing the background removal.
+    ///
+    /// The restored image is not processed again: like a processed image,
+    /// its write is recorded in the change counter bookkeeping. Each call
+    /// goes one image further back, up to the number of originals kept (see
+    /// [`with_originals_kept`](Self::with_originals_kept)).
+    ///
+    /// Returns `false` if there is no original to restore.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServiceError::Write`] if the image cannot be written; the
+    /// original is kept so restoring can be retried.
+    pub fn restore_last_original(&self) -> ServiceResult<bool, C, G, H> {
+        let Some(original) = self.originals.borrow_mut().pop_back() else {
+            return Ok(false);
+        };
+        if let Err(e) = self.write_image(&original.image, original.metadata.as_ref()) {
+            self.originals.borrow_mut().push_back(original);
+            return Err(e);
         }
 
-        let target_color = self
-            .config
-            .load_target_color()
-            .map_err(|e| format!("failed to load config: {e}"))?;
+        let new_count = self.record_own_write()?;
 
-        debug!(
-            "target color loaded: RGB({}, {}, {})",
-            target_color.r(),
-            target_color.g(),
-            target_color.b()
-        );
+        debug!("original image restored to clipboard (count: {new_count})");
 
-        let changed = make_transparent(&mut image.pixels, &target_color);
+        Ok(true)
+    }
 
-        debug!("{changed} pixel(s) matched target color");
+    /// Returns `true` if [`restore_last_original`](Self::restore_last_original)
+    /// has an image to restore.
+    pub fn has_original(&self) -> bool {
+        !self.originals.borrow().is_empty()
+    }
 
-        if changed == 0 {
-            debug!("no pixels matched — skipping clipboard write");
-            self.last_change_count.set(current_count);
-            return Ok(ProcessResult::Processed);
+    /// Returns the history of processed images, newest first, or nothing
+    /// without a history.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServiceError::History`] if the history cannot be read.
+    pub fn history(&self) -> ServiceResult<Vec<HistoryEntry>, C, G, H> {
+        match &self.history {
+            Some(history) => history.entries().map_err(ServiceError::History),
+            None => Ok(Vec::new()),
         }
+    }
 
-        self.clipboard
-            .set_image(&image)
-            .map_err(|e| format!("failed to write clipboard: {e}"))?;
+    /// Puts the result or the original of the history entry `id` back on
+    /// the clipboard, without processing it again.
+    ///
+    /// With a codec, the image is written with the metadata it was recorded
+    /// with, if the history kept it.
+    ///
+    /// Returns `false` if the entry is no longer in the history.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServiceError::History`] if the image cannot be read from
+    /// the history, or [`ServiceError::Write`] if it cannot be written.
+    pub fn copy_from_history(&self, id: u64, image: HistoryImage) -> ServiceResult<bool, C, G, H> {
+        let Some(history) = &self.history else {
+            return Ok(false);
+        };
+        let Some((data, metadata)) = self.load_from_history(history, id, image)? else {
+            return Ok(false);
+        };
+        self.write_image(&data, metadata.as_ref())?;
+
+        let new_count = self.record_own_write()?;
+
+        debug!("history entry {id} ({image:?}) copied to clipboard (count: {new_count})");
+
+        Ok(true)
+    }
+
+    /// Reads an image of the history entry `id`, preferring the codec's
+    /// encoded format so its metadata can be kept.
+    fn load_from_history(
+        &self,
+        history: &H,
+        id: u64,
+        which: HistoryImage,
+    ) -> ImageRead<K, C, G, H> {
+        if let Some(codec) = &self.codec
+            && let Some(encoded) = history
+                .load_encoded(id, which)
+                .map_err(ServiceError::History)?
+            && encoded.mime_type == codec.mime_type()
+        {
+            match codec.decode(&encoded.data) {
+                Ok((image, metadata)) => return Ok(Some((image, Some(metadata)))),
+                Err(e) => warn!("failed to decode history entry {id}, reading pixels: {e}"),
+            }
+        }
+        let image = history
+            .load_image(id, which)
+            .map_err(ServiceError::History)?;
+        Ok(image.map(|image| (image, None)))
+    }
+
+    /// Adds a processed image to the history, if any.
+    ///
+    /// The result is kept as `encoded` for the clipboard, if it was, and the
+    /// original is encoded with its `metadata` if there is a codec. Each is
+    /// kept in one form only: encoded, or else as pixels.
+    ///
+    /// Failures are logged rather than returned: the image has already been
+    /// processed and written.
+    fn record_history(
+        &self,
+        changed_pixels: usize,
+        result: ImageData,
+        encoded: Option<Representation>,
+        original: &ImageData,
+        metadata: Option<&K::Metadata>,
+    ) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        let profile = self.config.load_active_profile().unwrap_or_else(|e| {
+            warn!("failed to read active profile for history: {e}");
+            None
+        });
+        let original = match self.encode(original, metadata) {
+            Some(encoded) => RecordedImage::Encoded(encoded),
+            None => RecordedImage::Pixels(original.clone()),
+        };
+        let record = HistoryRecord {
+            timestamp: SystemTime::now(),
+            width: result.width,
+            height: result.height,
+            changed_pixels,
+            profile,
+            thumbnail: thumbnail(&result, THUMBNAIL_SIZE),
+            result: match encoded {
+                Some(encoded) => RecordedImage::Encoded(encoded),
+                None => RecordedImage::Pixels(result),
+            },
+            original,
+        };
+        if let Err(e) = history.record(record) {
+            warn!("failed to record history: {e}");
+        }
+    }
 
-        // Record the counter AFTER our write so we skip our own change
-        let new_count = self
+    /// Keeps `original` for restoring, dropping the oldest beyond the limit.
+    fn keep_original(&self, original: Original<K::Metadata>) {
+        if self.originals_kept == 0 {
+            return;
+        }
+        let mut originals = self.originals.borrow_mut();
+        if originals.len() == self.originals_kept {
+            originals.pop_front();
+        }
+        originals.push_back(original);
+    }
+
+    /// Records the change counter after the service's own write, so the
+    /// written image is not processed, and returns it.
+    fn record_own_write(&self) -> ServiceResult<u64, C, G, H> {
+        let count = self
             .clipboard
             .change_count()
-            .map_err(|e| format!("failed to read change count after write: {e}"))?;
-        self.last_change_count.set(new_count);
+            .map_err(ServiceError::ChangeCount)?;
+        self.last_change_count.set(count);
+        Ok(count)
+    }
 
-        debug!("transparency applied, image written back to clipboard (count: {new_count})");
+    /// Reads the clipboard image, preferring the codec's encoded format so
+    /// its metadata can be kept.
+    fn read_image(&self) -> ImageRead<K, C, G, H> {
+        if let Some(codec) = &self.codec
+            && let Some(data) = self
+                .clipboard
+                .get_encoded(codec.mime_type())
+                .map_err(ServiceError::Read)?
+        {
+            match codec.decode(&data) {
+                Ok((image, metadata)) => return Ok(Some((image, Some(metadata)))),
+                Err(e) => warn!(
+                    "failed to decode {}, reading pixels: {e}",
+                    codec.mime_type()
+                ),
+            }
+        }
+        let image = self.clipboard.get_image().map_err(ServiceError::Read)?;
+        Ok(image.map(|image| (image, None)))
+    }
 
-        Ok(ProcessResult::Processed)
+    /// Writes the processed image, also offering it in the codec's format
+    /// (with the original metadata, if any) and as an HTML data-URI image.
+    ///
+    /// Returns the image in the codec's format, if it was offered.
+    fn write_image(
+        &self,
+        image: &ImageData,
+        metadata: Option<&K::Metadata>,
+    ) -> ServiceResult<Option<Representation>, C, G, H> {
+        match self.encode(image, metadata) {
+            Some(encoded) => {
+                let html = encoded.to_html_image();
+                let representations = [encoded, html];
+                self.clipboard
+                    .set_representations(image, &representations)
+                    .map_err(ServiceError::Write)?;
+                let [encoded, _] = representations;
+                Ok(Some(encoded))
+            }
+            None => {
+                self.clipboard
+                    .set_image(image)
+                    .map_err(ServiceError::Write)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Encodes `image` in the codec's format with `metadata` (or the
+    /// default metadata).
+    ///
+    /// Returns `None` without a codec, or if encoding fails.
+    fn encode(&self, image: &ImageData, metadata: Option<&K::Metadata>) -> Option<Representation> {
+        let codec = self.codec.as_ref()?;
+        let encoded = match metadata {
+            Some(metadata) => codec.encode(image, metadata),
+            None => codec.encode(image, &K::Metadata::default()),
+        };
+        match encoded {
+            Ok(data) => Some(Representation::new(codec.mime_type(), data)),
+            Err(e) => {
+                warn!(
+                    "failed to encode {}, using pixels only: {e}",
+                    codec.mime_type()
+                );
+                None
+            }
+        }
+    }
+
+    /// Returns the names of the configured profiles, in sorted order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServiceError::ConfigLoad`] if the config cannot be read.
+    pub fn list_profiles(&self) -> ServiceResult<Vec<String>, C, G, H> {
+        self.config
+            .list_profiles()
+            .map_err(ServiceError::ConfigLoad)
+    }
+
+    /// Returns the name of the active profile, or `None` for the top-level settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServiceError::ConfigLoad`] if the config cannot be read.
+    pub fn active_profile(&self) -> ServiceResult<Option<String>, C, G, H> {
+        self.config
+            .load_active_profile()
+            .map_err(ServiceError::ConfigLoad)
+    }
+
+    /// Switches processing to the named profile, or back to the top-level
+    /// settings with `None`.
+    ///
+    /// Takes effect from the next processed image.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServiceError::ConfigSave`] if the profile is not defined or
+    /// the config cannot be written.
+    pub fn switch_profile(&self, name: Option<&str>) -> ServiceResult<(), C, G, H> {
+        self.config
+            .save_active_profile(name)
+            .map_err(ServiceError::ConfigSave)?;
+        debug!("active profile: {}", name.unwrap_or("(default)"));
+        Ok(())
+    }
+
+    /// Reloads the config if it changed, so the next processed image uses
+    /// the new settings.
+    ///
+    /// Returns `true` if a new config was loaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ServiceError::ConfigLoad`] if the changed config is invalid;
+    /// the previous settings stay in effect.
+    pub fn reload_config(&self) -> ServiceResult<bool, C, G, H> {
+        let reloaded = self.config.reload().map_err(ServiceError::ConfigLoad)?;
+        if reloaded {
+            debug!("config reloaded");
+        }
+        Ok(reloaded)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::background::AutoDetect;
     use crate::domain::color::Color;
+    use crate::domain::distance::{AlphaRamp, DistanceMetric, Tolerance};
+    use crate::domain::flood_fill::{FloodFill, RemovalMode};
+    use crate::domain::image_processor::PostProcessing;
     use crate::domain::port::ImageData;
+    use crate::domain::profile::Profile;
+    use crate::domain::target::{AlphaMatching, TargetColor};
     use std::cell::{Cell as StdCell, RefCell};
 
     // -- Mock ClipboardPort --
@@ -165,6 +641,7 @@ mod tests {
     struct MockClipboard {
         image: RefCell<Option<ImageData>>,
         counter: StdCell<u64>,
+        fail_reads: bool,
     }
 
     #[derive(Debug)]
@@ -186,6 +663,9 @@ mod tests {
         }
 
         fn get_image(&self) -> Result<Option<ImageData>, Self::Error> {
+            if self.fail_reads {
+                return Err(MockClipboardError("clipboard busy".to_string()));
+            }
             Ok(self.image.borrow().clone())
         }
 
@@ -201,7 +681,31 @@ mod tests {
 
     #[derive(Debug)]
     struct MockConfig {
-        color: Color,
+        targets: Vec<TargetColor>,
+        auto: Option<AutoDetect>,
+        tolerance: Tolerance,
+        ramp: Option<AlphaRamp>,
+        post: PostProcessing,
+        mode: RemovalMode,
+        profiles: Vec<String>,
+        active: RefCell<Option<String>>,
+        broken: bool,
+    }
+
+    impl MockConfig {
+        fn with_color(color: Color) -> Self {
+            Self {
+                targets: vec![TargetColor::new(color)],
+                auto: None,
+                tolerance: Tolerance::exact(),
+                ramp: None,
+                post: PostProcessing::default(),
+                mode: RemovalMode::Global,
+                profiles: Vec::new(),
+                active: RefCell::new(None),
+                broken: false,
+            }
+        }
     }
 
     #[derive(Debug)]
@@ -218,11 +722,48 @@ mod tests {
     impl ConfigPort for MockConfig {
         type Error = MockConfigError;
 
-        fn load_target_color(&self) -> Result<Color, Self::Error> {
-            Ok(self.color)
+        fn load_settings(&self) -> Result<Profile, Self::Error> {
+            if self.broken {
+                return Err(MockConfigError("config parse error".to_string()));
+            }
+            Ok(Profile {
+                targets: self.targets.clone(),
+                auto_detect: self.auto,
+                tolerance: self.tolerance,
+                alpha_ramp: self.ramp,
+                alpha_matching: AlphaMatching::Ignore,
+                mode: self.mode.clone(),
+                post_processing: self.post,
+            })
         }
 
-        fn save_target_color(&self, _color: &Color) -> Result<(), Self::Error> {
+        fn save_settings(&self, _settings: &Profile) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn list_profiles(&self) -> Result<Vec<String>, Self::Error> {
+            Ok(self.profiles.clone())
+        }
+
+        fn load_profile(&self, _name: &str) -> Result<Option<Profile>, Self::Error> {
+            Ok(None)
+        }
+
+        fn save_profile(&self, _name: &str, _profile: &Profile) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn load_active_profile(&self) -> Result<Option<String>, Self::Error> {
+            Ok(self.active.borrow().clone())
+        }
+
+        fn save_active_profile(&self, name: Option<&str>) -> Result<(), Self::Error> {
+            if let Some(name) = name
+                && !self.profiles.iter().any(|p| p == name)
+            {
+                return Err(MockConfigError(format!("unknown profile {name}")));
+            }
+            *self.active.borrow_mut() = name.map(str::to_string);
             Ok(())
         }
 
@@ -231,17 +772,70 @@ mod tests {
         }
     }
 
+    // -- Mock HistoryPort --
+
+    #[derive(Debug, Default)]
+    struct MockHistory {
+        records: RefCell<Vec<HistoryRecord>>,
+    }
+
+    impl HistoryPort for MockHistory {
+        type Error = Infallible;
+
+        fn record(&self, record: HistoryRecord) -> Result<HistoryEntry, Self::Error> {
+            let mut records = self.records.borrow_mut();
+            let entry = record.entry(records.len() as u64);
+            records.push(record);
+            Ok(entry)
+        }
+
+        fn entries(&self) -> Result<Vec<HistoryEntry>, Self::Error> {
+            let records = self.records.borrow();
+            Ok((0..records.len())
+                .rev()
+                .map(|i| records[i].entry(i as u64))
+                .collect())
+        }
+
+        fn load_image(
+            &self,
+            id: u64,
+            image: HistoryImage,
+        ) -> Result<Option<ImageData>, Self::Error> {
+            let records = self.records.borrow();
+            let Some(record) = records.get(id as usize) else {
+                return Ok(None);
+            };
+            let recorded = match image {
+                HistoryImage::Result => &record.result,
+                HistoryImage::Original => &record.original,
+            };
+            match recorded {
+                RecordedImage::Pixels(pixels) => Ok(Some(pixels.clone())),
+                RecordedImage::Encoded(_) => Ok(None),
+            }
+        }
+    }
+
     fn make_service(
         image: Option<ImageData>,
         target: Color,
+    ) -> ClipboardService<MockClipboard, MockConfig> {
+        make_service_with_config(image, MockConfig::with_color(target))
+    }
+
+    fn make_service_with_config(
+        image: Option<ImageData>,
+        config: MockConfig,
     ) -> ClipboardService<MockClipboard, MockConfig> {
         ClipboardService::new(
             MockClipboard {
                 image: RefCell::new(image),
                 // Start at 1 so it differs from the initial last_change_count of 0
                 counter: StdCell::new(1),
+                fail_reads: false,
             },
-            MockConfig { color: target },
+            config,
         )
     }
 
@@ -354,4 +948,450 @@ mod tests {
             ProcessResult::Processed
         );
     }
+
+    #[test]
+    fn uses_configured_tolerance() {
+        let image = ImageData {
+            pixels: vec![250, 252, 255, 255, 0, 0, 0, 255],
+            width: 2,
+            height: 1,
+        };
+        let config = MockConfig {
+            tolerance: Tolerance::new(DistanceMetric::ChannelMax, 5.0).unwrap(),
+            ..MockConfig::with_color(Color::new(255, 255, 255))
+        };
+        let service = make_service_with_config(Some(image), config);
+
+        service.process_clipboard().unwrap();
+
+        let written = service.clipboard.image.borrow();
+        let written = written.as_ref().unwrap();
+        assert_eq!(written.pixels, vec![250, 252, 255, 0, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn uses_configured_alpha_ramp() {
+        let image = ImageData {
+            pixels: vec![255, 255, 255, 255, 205, 205, 205, 255, 0, 0, 0, 255],
+            width: 3,
+            height: 1,
+        };
+        let config = MockConfig {
+            ramp: AlphaRamp::new(DistanceMetric::ChannelMax, 0.0, 100.0),
+            ..MockConfig::with_color(Color::new(255, 255, 255))
+        };
+        let service = make_service_with_config(Some(image), config);
+
+        service.process_clipboard().unwrap();
+
+        let written = service.clipboard.image.borrow();
+        let written = written.as_ref().unwrap();
+        assert_eq!(
+            written.pixels,
+            vec![255, 255, 255, 0, 205, 205, 205, 128, 0, 0, 0, 255]
+        );
+    }
+
+    #[test]
+    fn decontaminates_edges_when_enabled() {
+        let image = ImageData {
+            pixels: vec![0, 255, 0, 255, 17, 145, 17, 255],
+            width: 2,
+            height: 1,
+        };
+        let config = MockConfig {
+            ramp: AlphaRamp::new(DistanceMetric::EuclideanRgb, 0.0, 225.0),
+            post: PostProcessing {
+                decontaminate_edges: true,
+                ..PostProcessing::default()
+            },
+            ..MockConfig::with_color(Color::new(0, 255, 0))
+        };
+        let service = make_service_with_config(Some(image), config);
+
+        service.process_clipboard().unwrap();
+
+        let written = service.clipboard.image.borrow();
+        let written = written.as_ref().unwrap();
+        assert_eq!(written.pixels, vec![0, 255, 0, 0, 34, 36, 34, 128]);
+    }
+
+    #[test]
+    fn flood_mode_keeps_enclosed_target_pixels() {
+        // 3x3 checkerboard: the white centre touches other white pixels only diagonally
+        #[rustfmt::skip]
+        let pixels = vec![
+            255, 255, 255, 255,  0, 0, 0, 255,        255, 255, 255, 255,
+            0, 0, 0, 255,        255, 255, 255, 255,  0, 0, 0, 255,
+            255, 255, 255, 255,  0, 0, 0, 255,        255, 255, 255, 255,
+        ];
+        let image = ImageData {
+            pixels,
+            width: 3,
+            height: 3,
+        };
+        let config = MockConfig {
+            mode: RemovalMode::Flood(FloodFill::default()),
+            ..MockConfig::with_color(Color::new(255, 255, 255))
+        };
+        let service = make_service_with_config(Some(image), config);
+
+        service.process_clipboard().unwrap();
+
+        let written = service.clipboard.image.borrow();
+        let written = written.as_ref().unwrap();
+        // Four white corners are on the border -> transparent
+        for corner in [0, 2, 6, 8] {
+            assert_eq!(written.pixels[corner * 4 + 3], 0, "corner {corner}");
+        }
+        // White centre is only diagonally connected -> kept with 4-connectivity
+        assert_eq!(written.pixels[4 * 4 + 3], 255);
+    }
+
+    #[test]
+    fn auto_detect_uses_border_color_instead_of_target() {
+        // 3x3 blue image with a red centre; configured target is white
+        let mut pixels = [0, 0, 255, 255].repeat(9);
+        pixels[16..20].copy_from_slice(&[255, 0, 0, 255]);
+        let image = ImageData {
+            pixels,
+            width: 3,
+            height: 3,
+        };
+        let config = MockConfig {
+            auto: Some(AutoDetect::default()),
+            ..MockConfig::with_color(Color::new(255, 255, 255))
+        };
+        let service = make_service_with_config(Some(image), config);
+
+        assert_eq!(
+            service.process_clipboard().unwrap(),
+            ProcessResult::Processed
+        );
+
+        let written = service.clipboard.image.borrow();
+        let written = written.as_ref().unwrap();
+        let alphas: Vec<u8> = written.pixels.chunks(4).map(|p| p[3]).collect();
+        assert_eq!(alphas, vec![0, 0, 0, 0, 255, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn auto_detect_skips_when_confidence_is_low() {
+        // 2x2 split evenly between two colors
+        let original = ImageData {
+            pixels: vec![
+                255, 0, 0, 255, 255, 0, 0, 255, //
+                0, 0, 255, 255, 0, 0, 255, 255,
+            ],
+            width: 2,
+            height: 2,
+        };
+        let config = MockConfig {
+            auto: Some(AutoDetect::default()),
+            ..MockConfig::with_color(Color::new(255, 0, 0))
+        };
+        let service = make_service_with_config(Some(original.clone()), config);
+
+        assert_eq!(
+            service.process_clipboard().unwrap(),
+            ProcessResult::Undetected
+        );
+        assert_eq!(service.clipboard.image.borrow().as_ref(), Some(&original));
+        // Not retried until the clipboard changes again
+        assert_eq!(service.process_clipboard().unwrap(), ProcessResult::Skipped);
+    }
+
+    #[test]
+    fn removes_every_configured_target_color() {
+        let image = ImageData {
+            pixels: vec![
+                255, 255, 255, 255, // white -> transparent
+                250, 250, 250, 255, // off-white -> transparent
+                248, 248, 248, 255, // within off-white's own tolerance -> transparent
+                245, 245, 245, 255, // outside every tolerance -> unchanged
+            ],
+            width: 4,
+            height: 1,
+        };
+        let config = MockConfig {
+            targets: vec![
+                TargetColor::new(Color::new(255, 255, 255)),
+                TargetColor::with_tolerance(
+                    Color::new(250, 250, 250),
+                    Tolerance::new(DistanceMetric::ChannelMax, 2.0).unwrap(),
+                ),
+            ],
+            ..MockConfig::with_color(Color::new(255, 255, 255))
+        };
+        let service = make_service_with_config(Some(image), config);
+
+        service.process_clipboard().unwrap();
+
+        let written = service.clipboard.image.borrow();
+        let alphas: Vec<u8> = written
+            .as_ref()
+            .unwrap()
+            .pixels
+            .chunks(4)
+            .map(|p| p[3])
+            .collect();
+        assert_eq!(alphas, vec![0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn switch_profile_updates_active_profile() {
+        let config = MockConfig {
+            profiles: vec!["green".to_string(), "white".to_string()],
+            ..MockConfig::with_color(Color::new(255, 255, 255))
+        };
+        let service = make_service_with_config(None, config);
+
+        assert_eq!(service.list_profiles().unwrap(), vec!["green", "white"]);
+        assert_eq!(service.active_profile().unwrap(), None);
+
+        service.switch_profile(Some("green")).unwrap();
+        assert_eq!(service.active_profile().unwrap().as_deref(), Some("green"));
+
+        service.switch_profile(None).unwrap();
+        assert_eq!(service.active_profile().unwrap(), None);
+    }
+
+    #[test]
+    fn switch_to_unknown_profile_fails() {
+        let service = make_service(None, Color::new(255, 255, 255));
+        let err = service.switch_profile(Some("missing")).unwrap_err();
+        assert!(matches!(err, ServiceError::ConfigSave(_)));
+    }
+
+    #[test]
+    fn config_load_failure_is_config_load_error() {
+        let image = ImageData {
+            pixels: vec![255, 255, 255, 255],
+            width: 1,
+            height: 1,
+        };
+        let config = MockConfig {
+            broken: true,
+            ..MockConfig::with_color(Color::new(255, 255, 255))
+        };
+        let service = make_service_with_config(Some(image), config);
+
+        let err = service.process_clipboard().unwrap_err();
+        assert!(matches!(err, ServiceError::ConfigLoad(_)));
+        assert_eq!(err.to_string(), "failed to load config: config parse error");
+    }
+
+    #[test]
+    fn clipboard_read_failure_is_read_error() {
+        let service = ClipboardService::new(
+            MockClipboard {
+                image: RefCell::new(None),
+                counter: StdCell::new(1),
+                fail_reads: true,
+            },
+            MockConfig::with_color(Color::default()),
+        );
+
+        let err = service.process_clipboard().unwrap_err();
+        assert!(matches!(err, ServiceError::Read(_)));
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    fn pixel(value: u8) -> ImageData {
+        ImageData {
+            pixels: vec![value, value, value, 255],
+            width: 1,
+            height: 1,
+        }
+    }
+
+    /// Simulates another application copying `image`.
+    fn copy(clipboard: &MockClipboard, image: ImageData) {
+        *clipboard.image.borrow_mut() = Some(image);
+        clipboard.counter.set(clipboard.counter.get() + 1);
+    }
+
+    #[test]
+    fn restore_puts_original_back_without_reprocessing() {
+        let service = make_service(Some(pixel(255)), Color::new(255, 255, 255));
+        assert!(!service.has_original());
+
+        service.process_clipboard().unwrap();
+        assert!(service.has_original());
+
+        assert!(service.restore_last_original().unwrap());
+        assert_eq!(*service.clipboard.image.borrow(), Some(pixel(255)));
+        assert!(!service.has_original());
+        assert_eq!(service.process_clipboard().unwrap(), ProcessResult::Skipped);
+        assert_eq!(*service.clipboard.image.borrow(), Some(pixel(255)));
+    }
+
+    #[test]
+    fn restore_without_original_does_nothing() {
+        let service = make_service(Some(pixel(0)), Color::new(255, 255, 255));
+
+        // Nothing matched, so nothing was changed that could be restored
+        service.process_clipboard().unwrap();
+
+        assert!(!service.restore_last_original().unwrap());
+        assert_eq!(service.clipboard.counter.get(), 1);
+    }
+
+    #[test]
+    fn restore_walks_back_through_kept_originals() {
+        let mut config = MockConfig::with_color(Color::default());
+        config.tolerance = Tolerance::new(DistanceMetric::ChannelMax, 255.0).unwrap();
+        let service = make_service_with_config(None, config).with_originals_kept(2);
+
+        for value in [1, 2, 3] {
+            copy(&service.clipboard, pixel(value));
+            service.process_clipboard().unwrap();
+        }
+
+        assert!(service.restore_last_original().unwrap());
+        assert_eq!(*service.clipboard.image.borrow(), Some(pixel(3)));
+        assert!(service.restore_last_original().unwrap());
+        assert_eq!(*service.clipboard.image.borrow(), Some(pixel(2)));
+        assert!(!service.restore_last_original().unwrap());
+    }
+
+    #[test]
+    fn keeping_no_originals_disables_restore() {
+        let service =
+            make_service(Some(pixel(255)), Color::new(255, 255, 255)).with_originals_kept(0);
+
+        service.process_clipboard().unwrap();
+
+        assert!(!service.has_original());
+        assert!(!service.restore_last_original().unwrap());
+    }
+
+    #[test]
+    fn records_processed_images_in_history() {
+        let config = MockConfig {
+            profiles: vec!["logos".to_string()],
+            active: RefCell::new(Some("logos".to_string())),
+            ..MockConfig::with_color(Color::new(255, 255, 255))
+        };
+        let image = ImageData {
+            pixels: vec![255, 255, 255, 255, 0, 0, 0, 255],
+            width: 2,
+            height: 1,
+        };
+        let service = make_service_with_config(Some(image.clone()), config)
+            .with_history(MockHistory::default());
+
+        service.process_clipboard().unwrap();
+
+        let entries = service.history().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].changed_pixels, 1);
+        assert_eq!((entries[0].width, entries[0].height), (2, 1));
+        assert_eq!(entries[0].profile.as_deref(), Some("logos"));
+        let records = service.history.as_ref().unwrap().records.borrow();
+        assert_eq!(records[0].original, RecordedImage::Pixels(image));
+        assert_eq!(
+            records[0].result,
+            RecordedImage::Pixels(ImageData {
+                pixels: vec![255, 255, 255, 0, 0, 0, 0, 255],
+                width: 2,
+                height: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn unchanged_images_are_not_recorded() {
+        let service = make_service(Some(pixel(0)), Color::new(255, 255, 255))
+            .with_history(MockHistory::default());
+
+        service.process_clipboard().unwrap();
+
+        assert!(service.history().unwrap().is_empty());
+    }
+
+    #[test]
+    fn copy_from_history_is_not_reprocessed() {
+        let service =
+            make_service(None, Color::new(255, 255, 255)).with_history(MockHistory::default());
+        copy(&service.clipboard, pixel(255));
+        service.process_clipboard().unwrap();
+        copy(&service.clipboard, pixel(0));
+        service.process_clipboard().unwrap();
+
+        assert!(
+            service
+                .copy_from_history(0, HistoryImage::Original)
+                .unwrap()
+        );
+        assert_eq!(*service.clipboard.image.borrow(), Some(pixel(255)));
+        assert_eq!(service.process_clipboard().unwrap(), ProcessResult::Skipped);
+
+        assert!(service.copy_from_history(0, HistoryImage::Result).unwrap());
+        assert_eq!(
+            service.clipboard.image.borrow().as_ref().unwrap().pixels[3],
+            0
+        );
+        assert!(!service.copy_from_history(5, HistoryImage::Result).unwrap());
+    }
+
+    /// A history during whose recording another application copies an image.
+    struct CopyingHistory<'a> {
+        clipboard: &'a MockClipboard,
+    }
+
+    impl HistoryPort for CopyingHistory<'_> {
+        type Error = Infallible;
+
+        fn record(&self, record: HistoryRecord) -> Result<HistoryEntry, Self::Error> {
+            copy(self.clipboard, pixel(255));
+            Ok(record.entry(0))
+        }
+
+        fn entries(&self) -> Result<Vec<HistoryEntry>, Self::Error> {
+            Ok(Vec::new())
+        }
+
+        fn load_image(
+            &self,
+            _id: u64,
+            _image: HistoryImage,
+        ) -> Result<Option<ImageData>, Self::Error> {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn copies_while_recording_history_are_processed() {
+        let clipboard = MockClipboard {
+            image: RefCell::new(Some(pixel(255))),
+            counter: StdCell::new(1),
+            fail_reads: false,
+        };
+        let service = ClipboardService::new(
+            &clipboard,
+            MockConfig::with_color(Color::new(255, 255, 255)),
+        )
+        .with_history(CopyingHistory {
+            clipboard: &clipboard,
+        });
+
+        service.process_clipboard().unwrap();
+
+        // Not mistaken for the service's own write
+        assert_eq!(
+            service.process_clipboard().unwrap(),
+            ProcessResult::Processed
+        );
+    }
+
+    #[test]
+    fn without_history_nothing_is_recorded() {
+        let service = make_service(Some(pixel(255)), Color::new(255, 255, 255));
+
+        service.process_clipboard().unwrap();
+
+        assert!(service.history().unwrap().is_empty());
+        assert!(!service.copy_from_history(0, HistoryImage::Result).unwrap());
+    }
 }
@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use crate::domain::color::Color;
+use crate::domain::distance::DistanceMetric;
+
+/// Largest per-channel difference at which a border pixel still counts as
+/// agreeing with the dominant color (absorbs JPEG noise and dithering).
+const AGREEMENT_SPREAD: f64 = 8.0;
+
+/// Settings for inferring the background color from the image itself.
+///
+/// Used instead of a fixed target color. Images whose background cannot be
+/// identified with at least `min_confidence` are left unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::background::AutoDetect;
+///
+/// let auto = AutoDetect::new(0.9).unwrap();
+/// assert_eq!(auto.min_confidence(), 0.9);
+/// assert!(AutoDetect::new(1.5).is_none());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoDetect {
+    min_confidence: f64,
+}
+
+impl AutoDetect {
+    /// Default confidence required before an image is processed.
+    pub const DEFAULT_MIN_CONFIDENCE: f64 = 0.8;
+
+    /// Creates auto-detection settings.
+    ///
+    /// Returns `None` unless `min_confidence` is between 0 and 1 inclusive.
+    pub fn new(min_confidence: f64) -> Option<Self> {
+        (0.0..=1.0)
+            .contains(&min_confidence)
+            .then_some(Self { min_confidence })
+    }
+
+    /// Returns the confidence required before an image is processed.
+    pub fn min_confidence(&self) -> f64 {
+        self.min_confidence
+    }
+
+    /// Returns whether `estimate` is confident enough to act on.
+    pub fn accepts(&self, estimate: &BackgroundEstimate) -> bool {
+        estimate.confidence >= self.min_confidence
+    }
+}
+
+impl Default for AutoDetect {
+    fn default() -> Self {
+        Self {
+            min_confidence: Self::DEFAULT_MIN_CONFIDENCE,
+        }
+    }
+}
+
+/// A background color inferred from an image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackgroundEstimate {
+    /// The most common opaque color along the image border.
+    pub color: Color,
+    /// Share of border pixels that agree with `color`, from 0 to 1.
+    pub confidence: f64,
+}
+
+/// Infers the background color from the pixels along the image border.
+///
+/// The dominant fully opaque border color is taken as the background. The
+/// confidence is the share of all border pixels within a small per-channel
+/// spread of it, so a busy or already transparent border scores low.
+///
+/// Returns `None` for an empty image or one whose border has no opaque pixels.
+///
+/// # Panics
+///
+/// Panics if `pixels.len()` is not `width * height * 4`.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::background::estimate_background;
+/// use bgclipper::domain::color::Color;
+///
+/// // 3x3 white image with a black centre
+/// let mut pixels = [255u8; 36];
+/// pixels[16..19].copy_from_slice(&[0, 0, 0]);
+///
+/// let estimate = estimate_background(&pixels, 3, 3).unwrap();
+/// assert_eq!(estimate.color, Color::new(255, 255, 255));
+/// assert_eq!(estimate.confidence, 1.0);
+/// ```
+pub fn estimate_background(pixels: &[u8], width: u32, height: u32) -> Option<BackgroundEstimate> {
+    let (w, h) = (width as usize, height as usize);
+    assert_eq!(
+        pixels.len(),
+        w * h * 4,
+        "pixel buffer length must be width * height * 4"
+    );
+
+    let border: Vec<&[u8]> = border_indices(w, h)
+        .map(|index| &pixels[index * 4..index * 4 + 4])
+        .collect();
+
+    let mut counts: HashMap<Color, usize> = HashMap::new();
+    for p in border.iter().filter(|p| p[3] == 255) {
+        *counts.entry(Color::new(p[0], p[1], p[2])).or_default() += 1;
+    }
+    // Tie-break on the color itself so the result does not depend on hash order
+    let color = counts
+        .into_iter()
+        .max_by_key(|&(color, count)| (count, color.r(), color.g(), color.b()))
+        .map(|(color, _)| color)?;
+
+    let agreeing = border
+        .iter()
+        .filter(|p| {
+            p[3] == 255
+                && DistanceMetric::ChannelMax.distance(&color, &Color::new(p[0], p[1], p[2]))
+                    <= AGREEMENT_SPREAD
+        })
+        .count();
+
+    Some(BackgroundEstimate {
+        color,
+        confidence: agreeing as f64 / border.len() as f64,
+    })
+}
+
+/// Returns the index of every border pixel exactly once, in no particular order.
+fn border_indices(w: usize, h: usize) -> Box<dyn Iterator<Item = usize>> {
+    if w == 0 || h == 0 {
+        return Box::new(std::iter::empty());
+    }
+    let top = 0..w;
+    let bottom = (h > 1).then(|| (h - 1) * w..h * w).into_iter().flatten();
+    let sides = (1..h.saturating_sub(1)).flat_map(move |y| {
+        let left = y * w;
+        let right = y * w + w - 1;
+        std::iter::once(left).chain((right != left).then_some(right))
+    });
+    Box::new(top.chain(bottom).chain(sides))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, rgba: [u8; 4]) -> Vec<u8> {
+        rgba.repeat((width * height) as usize)
+    }
+
+    fn set(pixels: &mut [u8], width: u32, x: u32, y: u32, rgba: [u8; 4]) {
+        let i = ((y * width + x) * 4) as usize;
+        pixels[i..i + 4].copy_from_slice(&rgba);
+    }
+
+    #[test]
+    fn auto_detect_rejects_out_of_range_confidence() {
+        assert!(AutoDetect::new(-0.1).is_none());
+        assert!(AutoDetect::new(1.01).is_none());
+        assert!(AutoDetect::new(f64::NAN).is_none());
+        assert!(AutoDetect::new(0.0).is_some());
+        assert!(AutoDetect::new(1.0).is_some());
+    }
+
+    #[test]
+    fn auto_detect_default_confidence() {
+        assert_eq!(
+            AutoDetect::default().min_confidence(),
+            AutoDetect::DEFAULT_MIN_CONFIDENCE
+        );
+    }
+
+    #[test]
+    fn uniform_border_has_full_confidence() {
+        let mut pixels = solid(4, 4, [10, 200, 30, 255]);
+        set(&mut pixels, 4, 1, 1, [0, 0, 0, 255]);
+        set(&mut pixels, 4, 2, 2, [0, 0, 0, 255]);
+
+        let estimate = estimate_background(&pixels, 4, 4).unwrap();
+        assert_eq!(estimate.color, Color::new(10, 200, 30));
+        assert_eq!(estimate.confidence, 1.0);
+    }
+
+    #[test]
+    fn interior_pixels_do_not_vote() {
+        // 7x7: 24 white border pixels around 25 black interior pixels
+        let mut pixels = solid(7, 7, [0, 0, 0, 255]);
+        for i in 0..7 {
+            set(&mut pixels, 7, i, 0, [255, 255, 255, 255]);
+            set(&mut pixels, 7, i, 6, [255, 255, 255, 255]);
+            set(&mut pixels, 7, 0, i, [255, 255, 255, 255]);
+            set(&mut pixels, 7, 6, i, [255, 255, 255, 255]);
+        }
+
+        let estimate = estimate_background(&pixels, 7, 7).unwrap();
+        assert_eq!(estimate.color, Color::new(255, 255, 255));
+    }
+
+    #[test]
+    fn near_colors_count_towards_confidence() {
+        let mut pixels = solid(3, 3, [200, 200, 200, 255]);
+        set(&mut pixels, 3, 0, 0, [205, 197, 200, 255]);
+
+        let estimate = estimate_background(&pixels, 3, 3).unwrap();
+        assert_eq!(estimate.color, Color::new(200, 200, 200));
+        assert_eq!(estimate.confidence, 1.0);
+    }
+
+    #[test]
+    fn mixed_border_has_low_confidence() {
+        // 2x2: every pixel is on the border, two colors split evenly
+        let pixels = [
+            255, 0, 0, 255, 255, 0, 0, 255, //
+            0, 0, 255, 255, 0, 0, 255, 255,
+        ];
+
+        let estimate = estimate_background(&pixels, 2, 2).unwrap();
+        assert_eq!(estimate.confidence, 0.5);
+        assert!(!AutoDetect::default().accepts(&estimate));
+    }
+
+    #[test]
+    fn transparent_border_pixels_lower_confidence() {
+        let mut pixels = solid(2, 2, [255, 255, 255, 255]);
+        set(&mut pixels, 2, 0, 0, [255, 255, 255, 0]);
+
+        let estimate = estimate_background(&pixels, 2, 2).unwrap();
+        assert_eq!(estimate.color, Color::new(255, 255, 255));
+        assert_eq!(estimate.confidence, 0.75);
+    }
+
+    #[test]
+    fn fully_transparent_border_has_no_estimate() {
+        let pixels = solid(3, 3, [255, 255, 255, 0]);
+        assert!(estimate_background(&pixels, 3, 3).is_none());
+    }
+
+    #[test]
+    fn empty_image_has_no_estimate() {
+        assert!(estimate_background(&[], 0, 0).is_none());
+    }
+
+    #[test]
+    fn border_indices_cover_each_pixel_once() {
+        for (w, h, expected) in [(1, 1, 1), (1, 3, 3), (3, 1, 3), (2, 2, 4), (4, 3, 10)] {
+            let mut indices: Vec<usize> = border_indices(w, h).collect();
+            assert_eq!(indices.len(), expected, "{w}x{h}");
+            indices.sort_unstable();
+            indices.dedup();
+            assert_eq!(indices.len(), expected, "{w}x{h} has duplicates");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "pixel buffer length must be width * height * 4")]
+    fn mismatched_dimensions_panic() {
+        estimate_background(&[255, 255, 255, 255], 2, 2);
+    }
+}
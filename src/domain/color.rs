@@ -1,4 +1,7 @@
 use std::fmt;
+use std::str::FromStr;
+
+use crate::domain::named_colors;
 
 /// Represents an RGB color value.
 ///
@@ -14,6 +17,10 @@ use std::fmt;
 /// assert_eq!(white.r(), 255);
 /// assert_eq!(white.g(), 255);
 /// assert_eq!(white.b(), 255);
+///
+/// // Colors can also be parsed from CSS-style strings
+/// assert_eq!("#fff".parse(), Ok(white));
+/// assert_eq!("hsl(0, 0%, 100%)".parse(), Ok(white));
 /// ```
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Color {
@@ -62,6 +69,311 @@ impl fmt::Display for Color {
     }
 }
 
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    /// Parses a color in one of the CSS notations:
+    ///
+    /// - `#rgb` or `#rrggbb` hex
+    /// - `rgb(r, g, b)` with channels 0–255 (the [`Display`](fmt::Display) format)
+    /// - `hsl(h, s%, l%)` with the hue in degrees
+    /// - a CSS named color such as `white` or `rebeccapurple`
+    ///
+    /// Function and color names are case-insensitive, and surrounding
+    /// whitespace is ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            return match parse_hex(hex) {
+                Some((color, None)) => Ok(color),
+                _ => Err(ParseColorError::InvalidHex(s.to_string())),
+            };
+        }
+
+        let lower = s.to_ascii_lowercase();
+        if let Some(call) = function_call(&lower, "rgb") {
+            return parse_rgb(call).ok_or_else(|| ParseColorError::InvalidRgb(s.to_string()));
+        }
+        if let Some(call) = function_call(&lower, "hsl") {
+            return parse_hsl(call).ok_or_else(|| ParseColorError::InvalidHsl(s.to_string()));
+        }
+        named_colors::lookup(&lower).ok_or_else(|| ParseColorError::UnknownName(s.to_string()))
+    }
+}
+
+/// An RGB color with an alpha channel.
+///
+/// Used to match pixels that are already (partially) transparent, such as
+/// images copied from a browser. An alpha of 255 is fully opaque.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::color::{Color, Rgba};
+///
+/// let half_white = Rgba::new(255, 255, 255, 128);
+/// assert_eq!(half_white.rgb(), Color::new(255, 255, 255));
+/// assert!(!half_white.is_opaque());
+/// assert_eq!(half_white.premultiplied(), Color::new(128, 128, 128));
+/// assert_eq!("#ffffff80".parse(), Ok(half_white));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rgba {
+    rgb: Color,
+    alpha: u8,
+}
+
+impl Rgba {
+    /// Creates a new `Rgba` with the given channel values.
+    pub fn new(r: u8, g: u8, b: u8, alpha: u8) -> Self {
+        Self {
+            rgb: Color::new(r, g, b),
+            alpha,
+        }
+    }
+
+    /// Reads one pixel from an RGBA buffer chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk` is shorter than 4 bytes.
+    pub fn from_pixel(chunk: &[u8]) -> Self {
+        Self::new(chunk[0], chunk[1], chunk[2], chunk[3])
+    }
+
+    /// Returns the color channels, ignoring alpha.
+    pub fn rgb(&self) -> Color {
+        self.rgb
+    }
+
+    /// Returns the alpha channel value.
+    pub fn alpha(&self) -> u8 {
+        self.alpha
+    }
+
+    /// Returns `true` if the alpha channel is 255.
+    pub fn is_opaque(&self) -> bool {
+        self.alpha == u8::MAX
+    }
+
+    /// Returns the color channels multiplied by alpha, i.e. the color this
+    /// pixel contributes when composited over black.
+    pub fn premultiplied(&self) -> Color {
+        let scale = |channel: u8| {
+            ((u16::from(channel) * u16::from(self.alpha) + 127) / u16::from(u8::MAX)) as u8
+        };
+        Color::new(scale(self.rgb.r), scale(self.rgb.g), scale(self.rgb.b))
+    }
+
+    /// Parses a color in any notation [`FromStr`] accepts, or as bare
+    /// `rrggbb` or `rrggbbaa` hex, for input where `#` is awkward to type,
+    /// such as command-line flags and environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of [`FromStr`] if `s` is not a color.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bgclipper::domain::color::Rgba;
+    ///
+    /// assert_eq!(Rgba::parse_with_bare_hex("ffffff80"), "#ffffff80".parse());
+    /// assert_eq!(Rgba::parse_with_bare_hex("navy"), "navy".parse());
+    /// ```
+    pub fn parse_with_bare_hex(s: &str) -> Result<Self, ParseColorError> {
+        let s = s.trim();
+        s.parse().or_else(|e| {
+            if matches!(s.len(), 6 | 8) && s.bytes().all(|b| b.is_ascii_hexdigit()) {
+                format!("#{s}").parse()
+            } else {
+                Err(e)
+            }
+        })
+    }
+}
+
+impl From<Color> for Rgba {
+    /// Returns the color as a fully opaque `Rgba`.
+    fn from(rgb: Color) -> Self {
+        Self {
+            rgb,
+            alpha: u8::MAX,
+        }
+    }
+}
+
+impl fmt::Display for Rgba {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_opaque() {
+            return self.rgb.fmt(f);
+        }
+        // Three decimals are enough to round-trip every 8-bit alpha
+        let alpha = (f64::from(self.alpha) / 255.0 * 1000.0).round() / 1000.0;
+        let Color { r, g, b } = self.rgb;
+        write!(f, "rgba({r}, {g}, {b}, {alpha})")
+    }
+}
+
+impl FromStr for Rgba {
+    type Err = ParseColorError;
+
+    /// Parses any notation [`Color`] accepts as an opaque color, plus:
+    ///
+    /// - `#rgba` or `#rrggbbaa` hex
+    /// - `rgba(r, g, b, a)` with the alpha from 0 to 1 or as a percentage
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            let (rgb, alpha) =
+                parse_hex(hex).ok_or_else(|| ParseColorError::InvalidHex(s.to_string()))?;
+            return Ok(Self {
+                rgb,
+                alpha: alpha.unwrap_or(u8::MAX),
+            });
+        }
+        if let Some(call) = function_call(&s.to_ascii_lowercase(), "rgba") {
+            return parse_rgba(call).ok_or_else(|| ParseColorError::InvalidRgba(s.to_string()));
+        }
+        s.parse::<Color>().map(Self::from)
+    }
+}
+
+/// Error returned when a string cannot be parsed as a [`Color`] or [`Rgba`].
+///
+/// Each variant holds the rejected input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseColorError {
+    /// `#` was not followed by 3 or 6 hex digits (or 4 or 8 for [`Rgba`]).
+    InvalidHex(String),
+    /// Malformed `rgb(...)`, or a channel outside 0–255.
+    InvalidRgb(String),
+    /// Malformed `rgba(...)`, or an alpha outside 0–1.
+    InvalidRgba(String),
+    /// Malformed `hsl(...)`, or a saturation or lightness outside 0–100%.
+    InvalidHsl(String),
+    /// Not a known CSS color name.
+    UnknownName(String),
+}
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseColorError::InvalidHex(s) => write!(
+                f,
+                "invalid hex color {s:?}: expected #rgb or #rrggbb (#rgba or #rrggbbaa with alpha)"
+            ),
+            ParseColorError::InvalidRgb(s) => {
+                write!(
+                    f,
+                    "invalid rgb color {s:?}: expected rgb(r, g, b) with 0-255 channels"
+                )
+            }
+            ParseColorError::InvalidRgba(s) => write!(
+                f,
+                "invalid rgba color {s:?}: expected rgba(r, g, b, a) with alpha from 0 to 1"
+            ),
+            ParseColorError::InvalidHsl(s) => {
+                write!(f, "invalid hsl color {s:?}: expected hsl(h, s%, l%)")
+            }
+            ParseColorError::UnknownName(s) => write!(f, "unknown color name {s:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+/// Parses the digits of `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`, returning
+/// the color and the alpha if one was given.
+fn parse_hex(hex: &str) -> Option<(Color, Option<u8>)> {
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    // Each short digit is repeated: #abc is #aabbcc
+    let channels: Vec<u8> = match hex.len() {
+        3 | 4 => hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8 * 17))
+            .collect::<Option<_>>()?,
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+    let color = Color::new(channels[0], channels[1], channels[2]);
+    Some((color, channels.get(3).copied()))
+}
+
+/// Returns the text after `name` if `s` is a call of the CSS function
+/// `name`, i.e. starts with `name(`.
+fn function_call<'a>(s: &'a str, name: &str) -> Option<&'a str> {
+    let call = s.strip_prefix(name)?.trim_start();
+    call.starts_with('(').then_some(call)
+}
+
+/// Returns the `N` comma-separated arguments of a `(a, b, ...)` call.
+fn call_args<const N: usize>(call: &str) -> Option<[&str; N]> {
+    let inner = call.strip_prefix('(')?.strip_suffix(')')?;
+    let args: Vec<&str> = inner.split(',').map(str::trim).collect();
+    args.try_into().ok()
+}
+
+fn parse_rgb(call: &str) -> Option<Color> {
+    let [r, g, b] = call_args(call)?;
+    Some(Color::new(
+        r.parse().ok()?,
+        g.parse().ok()?,
+        b.parse().ok()?,
+    ))
+}
+
+fn parse_rgba(call: &str) -> Option<Rgba> {
+    let [r, g, b, a] = call_args(call)?;
+    let alpha: f64 = match a.strip_suffix('%') {
+        Some(percent) => percent.trim_end().parse::<f64>().ok()? / 100.0,
+        None => a.parse().ok()?,
+    };
+    if !(0.0..=1.0).contains(&alpha) {
+        return None;
+    }
+    let alpha = (alpha * 255.0).round() as u8;
+    Some(Rgba::new(
+        r.parse().ok()?,
+        g.parse().ok()?,
+        b.parse().ok()?,
+        alpha,
+    ))
+}
+
+fn parse_hsl(call: &str) -> Option<Color> {
+    let [h, s, l] = call_args(call)?;
+    let hue: f64 = h.strip_suffix("deg").unwrap_or(h).trim_end().parse().ok()?;
+    let percent = |v: &str| {
+        let v: f64 = v.strip_suffix('%')?.trim_end().parse().ok()?;
+        (0.0..=100.0).contains(&v).then_some(v / 100.0)
+    };
+    let (saturation, lightness) = (percent(s)?, percent(l)?);
+    if !hue.is_finite() {
+        return None;
+    }
+
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |v: f64| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    Some(Color::new(channel(r), channel(g), channel(b)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -145,6 +457,149 @@ mod tests {
         assert_eq!(max.b(), 255);
     }
 
+    #[test]
+    fn parse_accepts_hex() {
+        assert_eq!("#e7feb6".parse(), Ok(Color::new(0xe7, 0xfe, 0xb6)));
+        assert_eq!("#FFF".parse(), Ok(Color::new(255, 255, 255)));
+        assert_eq!("#1a2".parse(), Ok(Color::new(0x11, 0xaa, 0x22)));
+    }
+
+    #[test]
+    fn parse_invalid_hex_is_error() {
+        for input in ["#", "#ffff", "#fffffff", "#ggg", "#+1ffff"] {
+            assert_eq!(
+                input.parse::<Color>(),
+                Err(ParseColorError::InvalidHex(input.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn parse_accepts_rgb() {
+        assert_eq!("rgb(255,255,255)".parse(), Ok(Color::new(255, 255, 255)));
+        assert_eq!(" RGB( 1 , 2, 3 ) ".parse(), Ok(Color::new(1, 2, 3)));
+    }
+
+    #[test]
+    fn parse_roundtrips_display() {
+        let color = Color::new(255, 128, 0);
+        assert_eq!(color.to_string().parse(), Ok(color));
+    }
+
+    #[test]
+    fn parse_invalid_rgb_is_error() {
+        for input in [
+            "rgb(256, 0, 0)",
+            "rgb(1, 2)",
+            "rgb(1, 2, 3, 4)",
+            "rgb(1, 2, 3",
+        ] {
+            assert!(matches!(
+                input.parse::<Color>(),
+                Err(ParseColorError::InvalidRgb(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn parse_accepts_hsl() {
+        assert_eq!("hsl(0,0%,100%)".parse(), Ok(Color::new(255, 255, 255)));
+        assert_eq!("hsl(0, 100%, 50%)".parse(), Ok(Color::new(255, 0, 0)));
+        assert_eq!("hsl(120deg, 100%, 25%)".parse(), Ok(Color::new(0, 128, 0)));
+        assert_eq!("hsl(-120, 100%, 50%)".parse(), Ok(Color::new(0, 0, 255)));
+        assert_eq!("hsl(270, 50%, 40%)".parse(), Ok(Color::new(102, 51, 153)));
+    }
+
+    #[test]
+    fn parse_invalid_hsl_is_error() {
+        for input in ["hsl(0, 0, 100%)", "hsl(0, 0%, 101%)", "hsl(inf, 0%, 0%)"] {
+            assert!(matches!(
+                input.parse::<Color>(),
+                Err(ParseColorError::InvalidHsl(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn parse_accepts_named_color() {
+        assert_eq!("white".parse(), Ok(Color::new(255, 255, 255)));
+        assert_eq!("CornflowerBlue".parse(), Ok(Color::new(100, 149, 237)));
+        assert_eq!(
+            "ffffff".parse::<Color>(),
+            Err(ParseColorError::UnknownName("ffffff".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_error_display_names_input() {
+        let err = "#12".parse::<Color>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid hex color \"#12\": expected #rgb or #rrggbb (#rgba or #rrggbbaa with alpha)"
+        );
+    }
+
+    #[test]
+    fn color_parse_rejects_alpha() {
+        assert!(matches!(
+            "#ffff".parse::<Color>(),
+            Err(ParseColorError::InvalidHex(_))
+        ));
+        assert!(matches!(
+            "rgba(1, 2, 3, 0.5)".parse::<Color>(),
+            Err(ParseColorError::UnknownName(_))
+        ));
+    }
+
+    #[test]
+    fn rgba_parse_accepts_alpha_notations() {
+        assert_eq!("#ffffff80".parse(), Ok(Rgba::new(255, 255, 255, 128)));
+        assert_eq!("#0008".parse(), Ok(Rgba::new(0, 0, 0, 136)));
+        assert_eq!("rgba(1, 2, 3, 0.5)".parse(), Ok(Rgba::new(1, 2, 3, 128)));
+        assert_eq!("RGBA(1,2,3,100%)".parse(), Ok(Rgba::new(1, 2, 3, 255)));
+    }
+
+    #[test]
+    fn rgba_parse_accepts_opaque_notations() {
+        assert_eq!("white".parse(), Ok(Rgba::new(255, 255, 255, 255)));
+        assert_eq!("#fff".parse(), Ok(Rgba::new(255, 255, 255, 255)));
+    }
+
+    #[test]
+    fn rgba_parse_rejects_invalid_alpha() {
+        for input in ["rgba(1, 2, 3, 1.5)", "rgba(1, 2, 3)", "rgba(1, 2, 3, x)"] {
+            assert_eq!(
+                input.parse::<Rgba>(),
+                Err(ParseColorError::InvalidRgba(input.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn rgba_display_roundtrips() {
+        for alpha in 0..=255 {
+            let rgba = Rgba::new(1, 2, 3, alpha);
+            assert_eq!(rgba.to_string().parse(), Ok(rgba));
+        }
+        assert_eq!(Rgba::new(1, 2, 3, 255).to_string(), "rgb(1, 2, 3)");
+    }
+
+    #[test]
+    fn premultiplied_scales_channels_by_alpha() {
+        assert_eq!(
+            Rgba::new(255, 100, 0, 255).premultiplied(),
+            Color::new(255, 100, 0)
+        );
+        assert_eq!(
+            Rgba::new(255, 100, 0, 51).premultiplied(),
+            Color::new(51, 20, 0)
+        );
+        assert_eq!(
+            Rgba::new(255, 255, 255, 0).premultiplied(),
+            Color::new(0, 0, 0)
+        );
+    }
+
     #[test]
     fn hash_is_consistent_for_equal_colors() {
         use std::collections::HashSet;
@@ -156,4 +611,33 @@ mod tests {
         set.insert(a);
         assert!(set.contains(&b));
     }
+
+    #[test]
+    fn parse_with_bare_hex_accepts_hex_without_hash() {
+        assert_eq!(
+            Rgba::parse_with_bare_hex("e7feb6"),
+            Ok(Rgba::new(0xe7, 0xfe, 0xb6, 255))
+        );
+        assert_eq!(
+            Rgba::parse_with_bare_hex(" ffffff80 "),
+            Ok(Rgba::new(255, 255, 255, 0x80))
+        );
+        assert_eq!(
+            Rgba::parse_with_bare_hex("#FFF"),
+            Ok(Rgba::new(255, 255, 255, 255))
+        );
+        assert_eq!(
+            Rgba::parse_with_bare_hex("navy"),
+            Ok(Rgba::new(0, 0, 128, 255))
+        );
+        // Short hex would be ambiguous with color names, e.g. "add"
+        assert_eq!(
+            Rgba::parse_with_bare_hex("fff"),
+            Err(ParseColorError::UnknownName("fff".to_string()))
+        );
+        assert_eq!(
+            Rgba::parse_with_bare_hex("gggggg"),
+            Err(ParseColorError::UnknownName("gggggg".to_string()))
+        );
+    }
 }
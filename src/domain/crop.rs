@@ -0,0 +1,214 @@
+use crate::domain::port::ImageData;
+
+/// Settings for cropping an image to its visible content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Crop {
+    /// Transparent margin, in pixels, kept around the content on each side.
+    ///
+    /// The margin never extends past the original image.
+    pub padding: u32,
+}
+
+/// A rectangle of pixels: `x..x + width` by `y..y + height`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    /// Leftmost column.
+    pub x: u32,
+    /// Top row.
+    pub y: u32,
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+}
+
+impl Crop {
+    /// Crops `image` to the bounding box of its non-transparent pixels,
+    /// widened by `padding`, updating its width and height.
+    ///
+    /// A fully transparent image has no content and is left unchanged.
+    /// Returns `true` if the image was cropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `image.pixels.len()` is not `width * height * 4`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bgclipper::domain::crop::Crop;
+    /// use bgclipper::domain::port::ImageData;
+    ///
+    /// // 3x1: transparent, red, transparent
+    /// let mut image = ImageData {
+    ///     pixels: vec![0, 0, 0, 0, 255, 0, 0, 255, 0, 0, 0, 0],
+    ///     width: 3,
+    ///     height: 1,
+    /// };
+    /// assert!(Crop { padding: 0 }.apply(&mut image));
+    /// assert_eq!((image.width, image.height), (1, 1));
+    /// assert_eq!(image.pixels, vec![255, 0, 0, 255]);
+    /// ```
+    pub fn apply(&self, image: &mut ImageData) -> bool {
+        let Some(content) = content_bounds(image) else {
+            return false;
+        };
+        let x = content.x.saturating_sub(self.padding);
+        let y = content.y.saturating_sub(self.padding);
+        let right = (content.x + content.width)
+            .saturating_add(self.padding)
+            .min(image.width);
+        let bottom = (content.y + content.height)
+            .saturating_add(self.padding)
+            .min(image.height);
+        let bounds = Bounds {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        };
+        if (bounds.width, bounds.height) == (image.width, image.height) {
+            return false;
+        }
+
+        let stride = image.width as usize * 4;
+        let row_start = bounds.x as usize * 4;
+        let row_len = bounds.width as usize * 4;
+        let mut pixels = Vec::with_capacity(row_len * bounds.height as usize);
+        for row in image
+            .pixels
+            .chunks_exact(stride)
+            .skip(bounds.y as usize)
+            .take(bounds.height as usize)
+        {
+            pixels.extend_from_slice(&row[row_start..row_start + row_len]);
+        }
+
+        *image = ImageData {
+            pixels,
+            width: bounds.width,
+            height: bounds.height,
+        };
+        true
+    }
+}
+
+/// Returns the bounding box of the pixels with non-zero alpha, or `None` if
+/// the image is fully transparent.
+///
+/// # Panics
+///
+/// Panics if `image.pixels.len()` is not `width * height * 4`.
+pub fn content_bounds(image: &ImageData) -> Option<Bounds> {
+    let (w, h) = (image.width as usize, image.height as usize);
+    assert_eq!(
+        image.pixels.len(),
+        w * h * 4,
+        "pixel buffer length must be width * height * 4"
+    );
+    if w == 0 {
+        return None;
+    }
+
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for (y, row) in image.pixels.chunks_exact(w * 4).enumerate() {
+        let mut opaque = row
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(_, pixel)| pixel[3] != 0)
+            .map(|(x, _)| x);
+        let Some(first) = opaque.next() else {
+            continue;
+        };
+        let last = opaque.next_back().unwrap_or(first);
+        bounds = Some(match bounds {
+            Some((left, top, right, _)) => (left.min(first), top, right.max(last), y),
+            None => (first, y, last, y),
+        });
+    }
+
+    bounds.map(|(left, top, right, bottom)| Bounds {
+        x: left as u32,
+        y: top as u32,
+        width: (right - left + 1) as u32,
+        height: (bottom - top + 1) as u32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+    const INK: [u8; 4] = [10, 20, 30, 255];
+
+    /// An image of `width` x `height` clear pixels with ink at `points`.
+    fn canvas(width: u32, height: u32, points: &[(u32, u32)]) -> ImageData {
+        let mut pixels = CLEAR.repeat((width * height) as usize);
+        for &(x, y) in points {
+            let i = ((y * width + x) * 4) as usize;
+            pixels[i..i + 4].copy_from_slice(&INK);
+        }
+        ImageData {
+            pixels,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn bounds_cover_all_visible_pixels() {
+        let image = canvas(6, 5, &[(2, 1), (4, 3), (1, 2)]);
+        assert_eq!(
+            content_bounds(&image),
+            Some(Bounds {
+                x: 1,
+                y: 1,
+                width: 4,
+                height: 3
+            })
+        );
+    }
+
+    #[test]
+    fn partially_transparent_pixels_are_content() {
+        let mut image = canvas(3, 1, &[]);
+        image.pixels[7] = 1;
+        assert_eq!(content_bounds(&image).map(|b| (b.x, b.width)), Some((1, 1)));
+    }
+
+    #[test]
+    fn fully_transparent_image_is_left_unchanged() {
+        let mut image = canvas(4, 4, &[]);
+        assert_eq!(content_bounds(&image), None);
+        assert!(!Crop::default().apply(&mut image));
+        assert_eq!((image.width, image.height), (4, 4));
+    }
+
+    #[test]
+    fn crops_to_content() {
+        let mut image = canvas(5, 4, &[(1, 1), (2, 2)]);
+
+        assert!(Crop::default().apply(&mut image));
+
+        assert_eq!(image, canvas(2, 2, &[(0, 0), (1, 1)]));
+    }
+
+    #[test]
+    fn padding_is_clamped_to_image() {
+        let mut image = canvas(10, 10, &[(1, 5), (3, 6)]);
+
+        assert!(Crop { padding: 2 }.apply(&mut image));
+
+        // Left margin is limited to the one column before the content
+        assert_eq!((image.width, image.height), (6, 6));
+        assert_eq!(image.pixels.len(), 6 * 6 * 4);
+        assert_eq!(&image.pixels[(2 * 6 + 1) * 4..(2 * 6 + 2) * 4], INK);
+    }
+
+    #[test]
+    fn content_filling_the_image_is_not_cropped() {
+        let mut image = canvas(2, 1, &[(0, 0), (1, 0)]);
+        assert!(!Crop { padding: 3 }.apply(&mut image));
+    }
+}
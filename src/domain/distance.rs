@@ -0,0 +1,492 @@
+use std::fmt;
+
+use crate::domain::color::Color;
+
+/// Metric used to measure how far a pixel color is from the target color.
+///
+/// Thresholds are expressed in the units of the chosen metric:
+///
+/// | Metric | Range | Notes |
+/// |---|---|---|
+/// | `EuclideanRgb` | 0–441.7 | Straight-line distance in RGB space |
+/// | `Cie76` | 0–~150 | ΔE*ab in CIELAB; ~2.3 is a "just noticeable difference" |
+/// | `Ciede2000` | 0–~100 | ΔE00; perceptually uniform refinement of CIE76 |
+/// | `ChannelMax` | 0–255 | Largest absolute difference of a single channel |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DistanceMetric {
+    /// Euclidean distance between the RGB channel values.
+    #[default]
+    EuclideanRgb,
+    /// CIE 1976 color difference (Euclidean distance in CIELAB).
+    Cie76,
+    /// CIE 2000 color difference.
+    Ciede2000,
+    /// Maximum absolute per-channel difference (Chebyshev distance).
+    ChannelMax,
+}
+
+impl DistanceMetric {
+    /// Returns the distance between two colors under this metric.
+    ///
+    /// The result is `0.0` for identical colors and grows with the difference.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bgclipper::domain::color::Color;
+    /// use bgclipper::domain::distance::DistanceMetric;
+    ///
+    /// let a = Color::new(255, 255, 255);
+    /// let b = Color::new(250, 252, 255);
+    /// assert_eq!(DistanceMetric::ChannelMax.distance(&a, &b), 5.0);
+    /// ```
+    pub fn distance(&self, a: &Color, b: &Color) -> f64 {
+        match self {
+            DistanceMetric::EuclideanRgb => {
+                let dr = f64::from(a.r()) - f64::from(b.r());
+                let dg = f64::from(a.g()) - f64::from(b.g());
+                let db = f64::from(a.b()) - f64::from(b.b());
+                (dr * dr + dg * dg + db * db).sqrt()
+            }
+            DistanceMetric::Cie76 => Lab::from(a).cie76(&Lab::from(b)),
+            DistanceMetric::Ciede2000 => Lab::from(a).ciede2000(&Lab::from(b)),
+            DistanceMetric::ChannelMax => {
+                let dr = a.r().abs_diff(b.r());
+                let dg = a.g().abs_diff(b.g());
+                let db = a.b().abs_diff(b.b());
+                f64::from(dr.max(dg).max(db))
+            }
+        }
+    }
+
+    /// Returns the lowercase name used in configuration files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DistanceMetric::EuclideanRgb => "euclidean",
+            DistanceMetric::Cie76 => "cie76",
+            DistanceMetric::Ciede2000 => "ciede2000",
+            DistanceMetric::ChannelMax => "channel_max",
+        }
+    }
+}
+
+impl fmt::Display for DistanceMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How close a pixel must be to the target color to count as a match.
+///
+/// A pixel matches when its distance from the target, measured with
+/// `metric`, is less than or equal to `threshold`. A threshold of `0.0`
+/// is equivalent to an exact RGB match.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::color::Color;
+/// use bgclipper::domain::distance::{DistanceMetric, Tolerance};
+///
+/// let tolerance = Tolerance::new(DistanceMetric::ChannelMax, 3.0).unwrap();
+/// let white = Color::new(255, 255, 255);
+/// assert!(tolerance.matches(&white, &Color::new(253, 254, 255)));
+/// assert!(!tolerance.matches(&white, &Color::new(250, 255, 255)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Tolerance {
+    metric: DistanceMetric,
+    threshold: f64,
+}
+
+impl Tolerance {
+    /// Creates a tolerance with the given metric and threshold.
+    ///
+    /// Returns `None` if `threshold` is negative, NaN, or infinite.
+    pub fn new(metric: DistanceMetric, threshold: f64) -> Option<Self> {
+        (threshold.is_finite() && threshold >= 0.0).then_some(Self { metric, threshold })
+    }
+
+    /// Returns a tolerance that only matches identical colors.
+    pub fn exact() -> Self {
+        Self::default()
+    }
+
+    /// Returns the distance metric.
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// Returns the maximum distance that still counts as a match.
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Returns `true` if this tolerance only matches identical colors.
+    pub fn is_exact(&self) -> bool {
+        self.threshold == 0.0
+    }
+
+    /// Returns `true` if `color` is within this tolerance of `target`.
+    pub fn matches(&self, target: &Color, color: &Color) -> bool {
+        if self.is_exact() {
+            return target.matches(color);
+        }
+        self.metric.distance(target, color) <= self.threshold
+    }
+}
+
+/// Distance band over which pixels fade from transparent to opaque.
+///
+/// Pixels whose distance from the target is at most `inner` become fully
+/// transparent, pixels at `outer` or beyond stay opaque, and pixels in
+/// between get an alpha proportional to their position in the band. This
+/// gives anti-aliased edges a smooth falloff instead of a hard cut.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::color::Color;
+/// use bgclipper::domain::distance::{AlphaRamp, DistanceMetric};
+///
+/// let ramp = AlphaRamp::new(DistanceMetric::ChannelMax, 10.0, 30.0).unwrap();
+/// let white = Color::new(255, 255, 255);
+/// assert_eq!(ramp.opacity(&white, &Color::new(250, 250, 250)), 0.0);
+/// assert_eq!(ramp.opacity(&white, &Color::new(235, 235, 235)), 0.5);
+/// assert_eq!(ramp.opacity(&white, &Color::new(200, 200, 200)), 1.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlphaRamp {
+    metric: DistanceMetric,
+    inner: f64,
+    outer: f64,
+}
+
+impl AlphaRamp {
+    /// Creates a ramp between the `inner` and `outer` distances.
+    ///
+    /// Returns `None` unless `0 <= inner <= outer` and both are finite.
+    pub fn new(metric: DistanceMetric, inner: f64, outer: f64) -> Option<Self> {
+        let valid = inner.is_finite() && outer.is_finite() && 0.0 <= inner && inner <= outer;
+        valid.then_some(Self {
+            metric,
+            inner,
+            outer,
+        })
+    }
+
+    /// Returns the distance metric.
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// Returns the distance up to which pixels become fully transparent.
+    pub fn inner(&self) -> f64 {
+        self.inner
+    }
+
+    /// Returns the distance from which pixels stay fully opaque.
+    pub fn outer(&self) -> f64 {
+        self.outer
+    }
+
+    /// Returns the opacity factor for `color`, from `0.0` (transparent) to `1.0` (opaque).
+    pub fn opacity(&self, target: &Color, color: &Color) -> f64 {
+        let distance = self.metric.distance(target, color);
+        if distance <= self.inner {
+            0.0
+        } else if distance >= self.outer {
+            1.0
+        } else {
+            (distance - self.inner) / (self.outer - self.inner)
+        }
+    }
+}
+
+/// CIE reference white (D65) in XYZ, matching the sRGB color space.
+const WHITE_D65: [f64; 3] = [0.950_47, 1.0, 1.088_83];
+
+/// A color in the CIELAB color space (D65 white point).
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Lab {
+    l: f64,
+    a: f64,
+    b: f64,
+}
+
+impl From<&Color> for Lab {
+    /// Converts an sRGB color to CIELAB via linear RGB and CIE XYZ.
+    fn from(color: &Color) -> Self {
+        let r = srgb_to_linear(color.r());
+        let g = srgb_to_linear(color.g());
+        let b = srgb_to_linear(color.b());
+
+        let x = 0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b;
+        let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175_0 * b;
+        let z = 0.019_333_9 * r + 0.119_192_0 * g + 0.950_304_1 * b;
+
+        let fx = lab_f(x / WHITE_D65[0]);
+        let fy = lab_f(y / WHITE_D65[1]);
+        let fz = lab_f(z / WHITE_D65[2]);
+
+        Self {
+            l: 116.0 * fy - 16.0,
+            a: 500.0 * (fx - fy),
+            b: 200.0 * (fy - fz),
+        }
+    }
+}
+
+impl Lab {
+    /// CIE 1976 color difference (ΔE*ab).
+    fn cie76(&self, other: &Lab) -> f64 {
+        let dl = self.l - other.l;
+        let da = self.a - other.a;
+        let db = self.b - other.b;
+        (dl * dl + da * da + db * db).sqrt()
+    }
+
+    /// CIE 2000 color difference (ΔE00) with unit weighting factors.
+    ///
+    /// Follows Sharma, Wu & Dalal, "The CIEDE2000 Color-Difference Formula:
+    /// Implementation Notes, Supplementary Test Data, and Mathematical
+    /// Observations" (2005).
+    fn ciede2000(&self, other: &Lab) -> f64 {
+        const POW25_7: f64 = 6_103_515_625.0; // 25^7
+
+        let c1 = self.a.hypot(self.b);
+        let c2 = other.a.hypot(other.b);
+        let c_bar7 = ((c1 + c2) / 2.0).powi(7);
+        let g = 0.5 * (1.0 - (c_bar7 / (c_bar7 + POW25_7)).sqrt());
+
+        let a1 = (1.0 + g) * self.a;
+        let a2 = (1.0 + g) * other.a;
+        let c1 = a1.hypot(self.b);
+        let c2 = a2.hypot(other.b);
+        let h1 = hue_degrees(self.b, a1);
+        let h2 = hue_degrees(other.b, a2);
+
+        let dl = other.l - self.l;
+        let dc = c2 - c1;
+        let dh = if c1 * c2 == 0.0 {
+            0.0
+        } else if (h2 - h1).abs() <= 180.0 {
+            h2 - h1
+        } else if h2 - h1 > 180.0 {
+            h2 - h1 - 360.0
+        } else {
+            h2 - h1 + 360.0
+        };
+        let dh_big = 2.0 * (c1 * c2).sqrt() * (dh.to_radians() / 2.0).sin();
+
+        let l_bar = (self.l + other.l) / 2.0;
+        let c_bar = (c1 + c2) / 2.0;
+        let h_bar = if c1 * c2 == 0.0 {
+            h1 + h2
+        } else if (h1 - h2).abs() <= 180.0 {
+            (h1 + h2) / 2.0
+        } else if h1 + h2 < 360.0 {
+            (h1 + h2 + 360.0) / 2.0
+        } else {
+            (h1 + h2 - 360.0) / 2.0
+        };
+
+        let t = 1.0 - 0.17 * (h_bar - 30.0).to_radians().cos()
+            + 0.24 * (2.0 * h_bar).to_radians().cos()
+            + 0.32 * (3.0 * h_bar + 6.0).to_radians().cos()
+            - 0.20 * (4.0 * h_bar - 63.0).to_radians().cos();
+        let l_bar_50 = (l_bar - 50.0).powi(2);
+        let s_l = 1.0 + 0.015 * l_bar_50 / (20.0 + l_bar_50).sqrt();
+        let s_c = 1.0 + 0.045 * c_bar;
+        let s_h = 1.0 + 0.015 * c_bar * t;
+
+        let d_theta = 30.0 * (-((h_bar - 275.0) / 25.0).powi(2)).exp();
+        let c_bar7 = c_bar.powi(7);
+        let r_c = 2.0 * (c_bar7 / (c_bar7 + POW25_7)).sqrt();
+        let r_t = -(2.0 * d_theta).to_radians().sin() * r_c;
+
+        let tl = dl / s_l;
+        let tc = dc / s_c;
+        let th = dh_big / s_h;
+        (tl * tl + tc * tc + th * th + r_t * tc * th).sqrt()
+    }
+}
+
+/// Converts an 8-bit sRGB channel to linear light in `0.0..=1.0`.
+fn srgb_to_linear(channel: u8) -> f64 {
+    let c = f64::from(channel) / 255.0;
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// CIELAB companding function.
+fn lab_f(t: f64) -> f64 {
+    const DELTA: f64 = 6.0 / 29.0;
+    if t > DELTA * DELTA * DELTA {
+        t.cbrt()
+    } else {
+        t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+    }
+}
+
+/// Returns the hue angle in degrees (`0.0..360.0`) for the given `b`/`a` pair.
+fn hue_degrees(b: f64, a: f64) -> f64 {
+    if a == 0.0 && b == 0.0 {
+        return 0.0;
+    }
+    let h = b.atan2(a).to_degrees();
+    if h < 0.0 { h + 360.0 } else { h }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-3;
+
+    fn lab(l: f64, a: f64, b: f64) -> Lab {
+        Lab { l, a, b }
+    }
+
+    #[test]
+    fn identical_colors_have_zero_distance_for_all_metrics() {
+        let c = Color::new(12, 200, 99);
+        for metric in [
+            DistanceMetric::EuclideanRgb,
+            DistanceMetric::Cie76,
+            DistanceMetric::Ciede2000,
+            DistanceMetric::ChannelMax,
+        ] {
+            assert_eq!(metric.distance(&c, &c), 0.0, "{metric}");
+        }
+    }
+
+    #[test]
+    fn euclidean_rgb_distance() {
+        let a = Color::new(0, 0, 0);
+        let b = Color::new(3, 4, 0);
+        assert_eq!(DistanceMetric::EuclideanRgb.distance(&a, &b), 5.0);
+    }
+
+    #[test]
+    fn channel_max_distance_uses_largest_channel_difference() {
+        let a = Color::new(10, 20, 30);
+        let b = Color::new(12, 10, 31);
+        assert_eq!(DistanceMetric::ChannelMax.distance(&a, &b), 10.0);
+    }
+
+    #[test]
+    fn white_converts_to_lab_100() {
+        let white = Lab::from(&Color::new(255, 255, 255));
+        assert!((white.l - 100.0).abs() < EPSILON, "{white:?}");
+        assert!(white.a.abs() < EPSILON, "{white:?}");
+        assert!(white.b.abs() < EPSILON, "{white:?}");
+    }
+
+    #[test]
+    fn black_converts_to_lab_0() {
+        let black = Lab::from(&Color::new(0, 0, 0));
+        assert!(black.cie76(&lab(0.0, 0.0, 0.0)) < EPSILON, "{black:?}");
+    }
+
+    #[test]
+    fn cie76_between_black_and_white_is_100() {
+        let d = DistanceMetric::Cie76.distance(&Color::new(0, 0, 0), &Color::new(255, 255, 255));
+        assert!((d - 100.0).abs() < EPSILON, "{d}");
+    }
+
+    #[test]
+    fn ciede2000_matches_sharma_reference_data() {
+        // Pairs 1, 7, 13 and 25 from Sharma et al. (2005), Table 1.
+        let cases = [
+            (
+                lab(50.0, 2.6772, -79.7751),
+                lab(50.0, 0.0, -82.7485),
+                2.0425,
+            ),
+            (lab(50.0, 0.0, 0.0), lab(50.0, -1.0, 2.0), 2.3669),
+            (
+                lab(50.0, 2.4900, -0.0010),
+                lab(50.0, -2.4900, 0.0009),
+                7.1792,
+            ),
+            (
+                lab(60.2574, -34.0099, 36.2677),
+                lab(60.4626, -34.1751, 39.4387),
+                1.2644,
+            ),
+        ];
+        for (a, b, expected) in cases {
+            let d = a.ciede2000(&b);
+            assert!(
+                (d - expected).abs() < 1e-4,
+                "{a:?} vs {b:?}: {d} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn ciede2000_is_symmetric() {
+        let a = Lab::from(&Color::new(231, 254, 182));
+        let b = Lab::from(&Color::new(200, 10, 40));
+        assert!((a.ciede2000(&b) - b.ciede2000(&a)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tolerance_rejects_invalid_thresholds() {
+        assert!(Tolerance::new(DistanceMetric::Cie76, -1.0).is_none());
+        assert!(Tolerance::new(DistanceMetric::Cie76, f64::NAN).is_none());
+        assert!(Tolerance::new(DistanceMetric::Cie76, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn exact_tolerance_matches_only_identical_colors() {
+        let tolerance = Tolerance::exact();
+        let white = Color::new(255, 255, 255);
+        assert!(tolerance.is_exact());
+        assert!(tolerance.matches(&white, &white));
+        assert!(!tolerance.matches(&white, &Color::new(255, 255, 254)));
+    }
+
+    #[test]
+    fn tolerance_matches_on_threshold_boundary() {
+        let tolerance = Tolerance::new(DistanceMetric::ChannelMax, 2.0).unwrap();
+        let target = Color::new(100, 100, 100);
+        assert!(tolerance.matches(&target, &Color::new(102, 98, 100)));
+        assert!(!tolerance.matches(&target, &Color::new(103, 100, 100)));
+    }
+
+    #[test]
+    fn alpha_ramp_rejects_invalid_bounds() {
+        assert!(AlphaRamp::new(DistanceMetric::Cie76, -1.0, 5.0).is_none());
+        assert!(AlphaRamp::new(DistanceMetric::Cie76, 5.0, 1.0).is_none());
+        assert!(AlphaRamp::new(DistanceMetric::Cie76, 0.0, f64::NAN).is_none());
+    }
+
+    #[test]
+    fn alpha_ramp_with_equal_bounds_is_binary() {
+        let ramp = AlphaRamp::new(DistanceMetric::ChannelMax, 4.0, 4.0).unwrap();
+        let target = Color::new(0, 0, 0);
+        assert_eq!(ramp.opacity(&target, &Color::new(4, 0, 0)), 0.0);
+        assert_eq!(ramp.opacity(&target, &Color::new(5, 0, 0)), 1.0);
+    }
+
+    #[test]
+    fn alpha_ramp_is_linear_between_bounds() {
+        let ramp = AlphaRamp::new(DistanceMetric::ChannelMax, 0.0, 100.0).unwrap();
+        let target = Color::new(0, 0, 0);
+        assert_eq!(ramp.opacity(&target, &Color::new(25, 0, 0)), 0.25);
+        assert_eq!(ramp.opacity(&target, &Color::new(0, 75, 0)), 0.75);
+    }
+
+    #[test]
+    fn metric_names() {
+        assert_eq!(DistanceMetric::EuclideanRgb.to_string(), "euclidean");
+        assert_eq!(DistanceMetric::Cie76.to_string(), "cie76");
+        assert_eq!(DistanceMetric::Ciede2000.to_string(), "ciede2000");
+        assert_eq!(DistanceMetric::ChannelMax.to_string(), "channel_max");
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use crate::domain::color::Rgba;
+
+/// Which neighbors count as connected when growing a region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Connectivity {
+    /// Horizontal and vertical neighbors only.
+    #[default]
+    Four,
+    /// Horizontal, vertical and diagonal neighbors.
+    Eight,
+}
+
+/// Where a flood fill starts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum FloodSeeds {
+    /// Every pixel on the image border.
+    #[default]
+    Border,
+    /// Explicit `(x, y)` pixel coordinates. Points outside the image are ignored.
+    Points(Vec<(u32, u32)>),
+}
+
+/// Settings for connected-region background removal.
+///
+/// Only background-colored pixels reachable from a seed through other
+/// background-colored pixels are removed, so enclosed areas of the same
+/// color (e.g. white text inside a white button) are kept.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct FloodFill {
+    /// Where the fill starts.
+    pub seeds: FloodSeeds,
+    /// Which neighbors are connected.
+    pub connectivity: Connectivity,
+}
+
+/// Which pixels of the image background removal applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum RemovalMode {
+    /// Every matching pixel anywhere in the image.
+    #[default]
+    Global,
+    /// Only matching pixels connected to the flood-fill seeds.
+    Flood(FloodFill),
+}
+
+impl FloodFill {
+    /// Returns a mask of the pixels connected to the seeds.
+    ///
+    /// Starting from each seed that `is_background` accepts, the region
+    /// grows through neighboring pixels (per `connectivity`) that are also
+    /// accepted. The result has one entry per pixel, in row-major order.
+    ///
+    /// `is_background` is called at most once per distinct RGBA value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixels.len()` is not `width * height * 4`.
+    pub fn region_mask(
+        &self,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        mut is_background: impl FnMut(&Rgba) -> bool,
+    ) -> Vec<bool> {
+        let (w, h) = (width as usize, height as usize);
+        assert_eq!(
+            pixels.len(),
+            w * h * 4,
+            "pixel buffer length must be width * height * 4"
+        );
+
+        let mut verdicts: HashMap<Rgba, bool> = HashMap::new();
+        let mut accepts = |index: usize| {
+            let pixel = Rgba::from_pixel(&pixels[index * 4..index * 4 + 4]);
+            *verdicts
+                .entry(pixel)
+                .or_insert_with(|| is_background(&pixel))
+        };
+
+        let mut mask = vec![false; w * h];
+        let mut stack: Vec<usize> = self.seed_indices(w, h).collect();
+
+        // Iterative depth-first fill; recursion would overflow on large regions.
+        while let Some(index) = stack.pop() {
+            if mask[index] || !accepts(index) {
+                continue;
+            }
+            mask[index] = true;
+
+            let (x, y) = (index % w, index / w);
+            for (dx, dy) in self.connectivity.offsets() {
+                let (Some(nx), Some(ny)) = (x.checked_add_signed(*dx), y.checked_add_signed(*dy))
+                else {
+                    continue;
+                };
+                if nx < w && ny < h && !mask[ny * w + nx] {
+                    stack.push(ny * w + nx);
+                }
+            }
+        }
+        mask
+    }
+
+    /// Returns the pixel indices the fill starts from.
+    fn seed_indices(&self, w: usize, h: usize) -> Box<dyn Iterator<Item = usize> + '_> {
+        if w == 0 || h == 0 {
+            return Box::new(std::iter::empty());
+        }
+        match &self.seeds {
+            FloodSeeds::Border => {
+                let rows = (0..w).flat_map(move |x| [x, (h - 1) * w + x]);
+                let cols = (0..h).flat_map(move |y| [y * w, y * w + w - 1]);
+                Box::new(rows.chain(cols))
+            }
+            FloodSeeds::Points(points) => Box::new(
+                points
+                    .iter()
+                    .map(|&(x, y)| (x as usize, y as usize))
+                    .filter(move |&(x, y)| x < w && y < h)
+                    .map(move |(x, y)| y * w + x),
+            ),
+        }
+    }
+}
+
+impl Connectivity {
+    /// Returns the `(dx, dy)` offsets of connected neighbors.
+    fn offsets(&self) -> &'static [(isize, isize)] {
+        const FOUR: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+        const EIGHT: [(isize, isize); 8] = [
+            (1, 0),
+            (-1, 0),
+            (0, 1),
+            (0, -1),
+            (1, 1),
+            (1, -1),
+            (-1, 1),
+            (-1, -1),
+        ];
+        match self {
+            Connectivity::Four => &FOUR,
+            Connectivity::Eight => &EIGHT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::color::Color;
+
+    const W: [u8; 4] = [255, 255, 255, 255];
+    const K: [u8; 4] = [0, 0, 0, 255];
+
+    fn image(rows: &[&[[u8; 4]]]) -> (Vec<u8>, u32, u32) {
+        let height = rows.len() as u32;
+        let width = rows.first().map_or(0, |r| r.len()) as u32;
+        let pixels = rows
+            .iter()
+            .flat_map(|r| r.iter().flatten().copied())
+            .collect();
+        (pixels, width, height)
+    }
+
+    fn is_white(pixel: &Rgba) -> bool {
+        pixel.rgb() == Color::new(255, 255, 255)
+    }
+
+    #[test]
+    fn border_fill_keeps_enclosed_region() {
+        #[rustfmt::skip]
+        let (pixels, w, h) = image(&[
+            &[W, W, W, W, W],
+            &[W, K, K, K, W],
+            &[W, K, W, K, W],
+            &[W, K, K, K, W],
+            &[W, W, W, W, W],
+        ]);
+        let mask = FloodFill::default().region_mask(&pixels, w, h, is_white);
+
+        // Centre white pixel is enclosed by black and must not be filled
+        assert!(!mask[2 * 5 + 2]);
+        assert_eq!(mask.iter().filter(|&&m| m).count(), 16);
+    }
+
+    #[test]
+    fn four_connectivity_does_not_cross_diagonals() {
+        #[rustfmt::skip]
+        let (pixels, w, h) = image(&[
+            &[W, K, K],
+            &[K, W, K],
+            &[K, K, K],
+        ]);
+        let fill = FloodFill {
+            seeds: FloodSeeds::Points(vec![(0, 0)]),
+            connectivity: Connectivity::Four,
+        };
+        let mask = fill.region_mask(&pixels, w, h, is_white);
+        assert_eq!(mask.iter().filter(|&&m| m).count(), 1);
+    }
+
+    #[test]
+    fn eight_connectivity_crosses_diagonals() {
+        #[rustfmt::skip]
+        let (pixels, w, h) = image(&[
+            &[W, K, K],
+            &[K, W, K],
+            &[K, K, K],
+        ]);
+        let fill = FloodFill {
+            seeds: FloodSeeds::Points(vec![(0, 0)]),
+            connectivity: Connectivity::Eight,
+        };
+        let mask = fill.region_mask(&pixels, w, h, is_white);
+        assert!(mask[0]);
+        assert!(mask[4]);
+    }
+
+    #[test]
+    fn seed_on_non_background_pixel_fills_nothing() {
+        let (pixels, w, h) = image(&[&[K, W, W]]);
+        let fill = FloodFill {
+            seeds: FloodSeeds::Points(vec![(0, 0)]),
+            ..FloodFill::default()
+        };
+        let mask = fill.region_mask(&pixels, w, h, is_white);
+        assert!(mask.iter().all(|&m| !m));
+    }
+
+    #[test]
+    fn out_of_bounds_seeds_are_ignored() {
+        let (pixels, w, h) = image(&[&[W, W]]);
+        let fill = FloodFill {
+            seeds: FloodSeeds::Points(vec![(5, 0), (0, 9)]),
+            ..FloodFill::default()
+        };
+        let mask = fill.region_mask(&pixels, w, h, is_white);
+        assert!(mask.iter().all(|&m| !m));
+    }
+
+    #[test]
+    fn empty_image_produces_empty_mask() {
+        let mask = FloodFill::default().region_mask(&[], 0, 0, is_white);
+        assert!(mask.is_empty());
+    }
+
+    #[test]
+    fn classifier_called_once_per_color() {
+        let (pixels, w, h) = image(&[&[W, W, W], &[W, K, W], &[W, W, W]]);
+        let mut calls = 0;
+        FloodFill::default().region_mask(&pixels, w, h, |c| {
+            calls += 1;
+            is_white(c)
+        });
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "pixel buffer length must be width * height * 4")]
+    fn mismatched_dimensions_panic() {
+        FloodFill::default().region_mask(&[255, 255, 255, 255], 2, 2, is_white);
+    }
+}
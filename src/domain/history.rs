@@ -0,0 +1,207 @@
+use std::time::SystemTime;
+
+use crate::domain::port::ImageData;
+use crate::domain::representation::Representation;
+
+/// Longest side of history thumbnails, in pixels.
+pub const THUMBNAIL_SIZE: u32 = 32;
+
+/// A processed image to add to the history, with the image as it was copied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryRecord {
+    /// When the image was processed.
+    pub timestamp: SystemTime,
+    /// Result width in pixels.
+    pub width: u32,
+    /// Result height in pixels.
+    pub height: u32,
+    /// Number of pixels whose alpha was changed.
+    pub changed_pixels: usize,
+    /// Name of the profile used, or `None` for the top-level settings.
+    pub profile: Option<String>,
+    /// The result scaled down to at most [`THUMBNAIL_SIZE`] on each side.
+    pub thumbnail: ImageData,
+    /// The processed image, as written to the clipboard.
+    pub result: RecordedImage,
+    /// The image before processing.
+    pub original: RecordedImage,
+}
+
+impl HistoryRecord {
+    /// Summarizes this record as the history entry `id`.
+    pub fn entry(&self, id: u64) -> HistoryEntry {
+        HistoryEntry {
+            id,
+            timestamp: self.timestamp,
+            width: self.width,
+            height: self.height,
+            changed_pixels: self.changed_pixels,
+            profile: self.profile.clone(),
+            thumbnail: self.thumbnail.clone(),
+        }
+    }
+}
+
+/// An image of a [`HistoryRecord`], kept in a single form.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordedImage {
+    /// Raw pixels, for the history to encode as it stores them.
+    Pixels(ImageData),
+    /// Encoded with the metadata it was copied with, e.g. PNG with its DPI.
+    Encoded(Representation),
+}
+
+/// Summary of a processed image in the history, without the full images.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// Identifier, unique and increasing within a history.
+    pub id: u64,
+    /// When the image was processed.
+    pub timestamp: SystemTime,
+    /// Image width in pixels.
+    pub width: u32,
+    /// Image height in pixels.
+    pub height: u32,
+    /// Number of pixels whose alpha was changed.
+    pub changed_pixels: usize,
+    /// Name of the profile used, or `None` for the top-level settings.
+    pub profile: Option<String>,
+    /// The result scaled down to at most [`THUMBNAIL_SIZE`] on each side.
+    pub thumbnail: ImageData,
+}
+
+/// Which image of a history entry to retrieve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryImage {
+    /// The processed image.
+    Result,
+    /// The image before processing.
+    Original,
+}
+
+/// Scales `image` down so neither side exceeds `max_side`, keeping its
+/// aspect ratio. Smaller images are returned unchanged.
+///
+/// Each thumbnail pixel averages the block of pixels it covers, weighting
+/// colors by alpha so transparent pixels do not darken the edges.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::history::thumbnail;
+/// use bgclipper::domain::port::ImageData;
+///
+/// let image = ImageData { pixels: vec![255; 64 * 32 * 4], width: 64, height: 32 };
+/// let small = thumbnail(&image, 16);
+/// assert_eq!((small.width, small.height), (16, 8));
+/// ```
+pub fn thumbnail(image: &ImageData, max_side: u32) -> ImageData {
+    let longest = image.width.max(image.height);
+    if longest <= max_side || max_side == 0 {
+        return image.clone();
+    }
+    let scale = |side: u32| (u64::from(side) * u64::from(max_side) / u64::from(longest)).max(1);
+    let (width, height) = (scale(image.width), scale(image.height));
+    let (src_width, src_height) = (u64::from(image.width), u64::from(image.height));
+
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    // Scaling down, so every block covers at least one source pixel
+    for y in 0..height {
+        let rows = y * src_height / height..(y + 1) * src_height / height;
+        for x in 0..width {
+            let cols = x * src_width / width..(x + 1) * src_width / width;
+            let mut sum = [0u64; 4];
+            let mut count = 0u64;
+            for sy in rows.clone() {
+                for sx in cols.clone() {
+                    let i = ((sy * src_width + sx) * 4) as usize;
+                    let alpha = u64::from(image.pixels[i + 3]);
+                    for (c, total) in sum.iter_mut().take(3).enumerate() {
+                        *total += u64::from(image.pixels[i + c]) * alpha;
+                    }
+                    sum[3] += alpha;
+                    count += 1;
+                }
+            }
+            for total in &sum[..3] {
+                pixels.push(total.checked_div(sum[3]).unwrap_or(0) as u8);
+            }
+            pixels.push((sum[3] / count) as u8);
+        }
+    }
+
+    ImageData {
+        pixels,
+        width: width as u32,
+        height: height as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, rgba: [u8; 4]) -> ImageData {
+        ImageData {
+            pixels: rgba.repeat((width * height) as usize),
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn small_images_are_unchanged() {
+        let image = solid(8, 4, [1, 2, 3, 255]);
+        assert_eq!(thumbnail(&image, 8), image);
+    }
+
+    #[test]
+    fn keeps_aspect_ratio_and_minimum_size() {
+        let wide = thumbnail(&solid(100, 10, [0, 0, 0, 255]), 20);
+        assert_eq!((wide.width, wide.height), (20, 2));
+
+        let line = thumbnail(&solid(1, 1000, [0, 0, 0, 255]), 10);
+        assert_eq!((line.width, line.height), (1, 10));
+        assert_eq!(line.pixels.len(), 10 * 4);
+    }
+
+    #[test]
+    fn averages_blocks_weighted_by_alpha() {
+        // 2x1 → 1x1: a red pixel next to a fully transparent black one
+        let image = ImageData {
+            pixels: vec![255, 0, 0, 255, 0, 0, 0, 0],
+            width: 2,
+            height: 1,
+        };
+        assert_eq!(thumbnail(&image, 1).pixels, vec![255, 0, 0, 127]);
+
+        let clear = solid(4, 4, [9, 9, 9, 0]);
+        assert_eq!(thumbnail(&clear, 2), solid(2, 2, [0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn entry_summarizes_record() {
+        let result = solid(64, 64, [0, 0, 0, 0]);
+        let record = HistoryRecord {
+            timestamp: SystemTime::UNIX_EPOCH,
+            width: 64,
+            height: 64,
+            changed_pixels: 12,
+            profile: Some("logos".to_string()),
+            thumbnail: thumbnail(&result, THUMBNAIL_SIZE),
+            result: RecordedImage::Pixels(result),
+            original: RecordedImage::Encoded(Representation::new("image/png", b"PNG".to_vec())),
+        };
+
+        let entry = record.entry(7);
+
+        assert_eq!(entry.id, 7);
+        assert_eq!((entry.width, entry.height), (64, 64));
+        assert_eq!(entry.changed_pixels, 12);
+        assert_eq!(entry.profile.as_deref(), Some("logos"));
+        assert_eq!(
+            (entry.thumbnail.width, entry.thumbnail.height),
+            (THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        );
+    }
+}
@@ -1,4 +1,9 @@
-use crate::domain::color::Color;
+use std::collections::HashMap;
+
+use crate::domain::color::{Color, Rgba};
+use crate::domain::crop::Crop;
+use crate::domain::distance::{AlphaRamp, Tolerance};
+use crate::domain::flood_fill::FloodFill;
 
 /// Replaces pixels matching the target color with full transparency.
 ///
@@ -47,6 +52,367 @@ pub fn make_transparent(pixels: &mut [u8], target: &Color) -> usize {
     count
 }
 
+/// Replaces pixels within `tolerance` of the target color with full transparency.
+///
+/// Like [`make_transparent`], but a pixel matches when its RGB distance from
+/// `target` is within the tolerance threshold. This clears the near-background
+/// noise left by JPEG compression or dithering. An exact tolerance behaves
+/// identically to [`make_transparent`].
+///
+/// Returns the number of pixels that were made transparent.
+///
+/// # Panics
+///
+/// Panics if `pixels.len()` is not a multiple of 4.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::color::Color;
+/// use bgclipper::domain::distance::{DistanceMetric, Tolerance};
+/// use bgclipper::domain::image_processor::make_transparent_with_tolerance;
+///
+/// let mut pixels = vec![254, 255, 253, 255, 0, 0, 0, 255];
+/// let white = Color::new(255, 255, 255);
+/// let tolerance = Tolerance::new(DistanceMetric::ChannelMax, 2.0).unwrap();
+/// let count = make_transparent_with_tolerance(&mut pixels, &white, &tolerance);
+/// assert_eq!(count, 1);
+/// assert_eq!(pixels, vec![254, 255, 253, 0, 0, 0, 0, 255]);
+/// ```
+pub fn make_transparent_with_tolerance(
+    pixels: &mut [u8],
+    target: &Color,
+    tolerance: &Tolerance,
+) -> usize {
+    if tolerance.is_exact() {
+        return make_transparent(pixels, target);
+    }
+
+    let mut count = 0;
+    for_each_pixel_by_color(
+        pixels,
+        |pixel| tolerance.matches(target, &pixel.rgb()),
+        |chunk, matched| {
+            if matched {
+                chunk[3] = 0;
+                count += 1;
+            }
+        },
+    );
+    count
+}
+
+/// Fades pixels near the target color to transparency along an alpha ramp.
+///
+/// Pixels within `ramp.inner()` of `target` become fully transparent, pixels
+/// at `ramp.outer()` or beyond are left unchanged, and pixels in between
+/// have their alpha scaled by their relative distance across the ramp. This
+/// keeps anti-aliased edges smooth instead of cutting them off.
+///
+/// Returns the number of pixels whose alpha was reduced.
+///
+/// # Panics
+///
+/// Panics if `pixels.len()` is not a multiple of 4.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::color::Color;
+/// use bgclipper::domain::distance::{AlphaRamp, DistanceMetric};
+/// use bgclipper::domain::image_processor::make_transparent_soft;
+///
+/// let mut pixels = vec![255, 255, 255, 255, 205, 205, 205, 255, 0, 0, 0, 255];
+/// let white = Color::new(255, 255, 255);
+/// let ramp = AlphaRamp::new(DistanceMetric::ChannelMax, 0.0, 100.0).unwrap();
+/// let count = make_transparent_soft(&mut pixels, &white, &ramp);
+/// assert_eq!(count, 2);
+/// assert_eq!(pixels, vec![255, 255, 255, 0, 205, 205, 205, 128, 0, 0, 0, 255]);
+/// ```
+pub fn make_transparent_soft(pixels: &mut [u8], target: &Color, ramp: &AlphaRamp) -> usize {
+    make_transparent_by_opacity(pixels, |pixel| ramp.opacity(target, &pixel.rgb()))
+}
+
+/// Scales each pixel's alpha by the opacity `opacity` assigns to it.
+///
+/// This is the general form of [`make_transparent_soft`]: `opacity` returns
+/// how much of a pixel to keep, from 0 (fully transparent) to 1 (unchanged).
+/// It sees the pixel's current alpha, so already transparent pixels can be
+/// handled deliberately. It is called once per run of identical pixels, not
+/// once per pixel.
+///
+/// Returns the number of pixels whose alpha was reduced.
+///
+/// # Panics
+///
+/// Panics if `pixels.len()` is not a multiple of 4.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::color::Color;
+/// use bgclipper::domain::image_processor::make_transparent_by_opacity;
+///
+/// let mut pixels = vec![255, 255, 255, 255, 250, 250, 250, 255, 0, 0, 0, 255];
+/// let backgrounds = [Color::new(255, 255, 255), Color::new(250, 250, 250)];
+/// let count = make_transparent_by_opacity(&mut pixels, |pixel| {
+///     if backgrounds.contains(&pixel.rgb()) { 0.0 } else { 1.0 }
+/// });
+/// assert_eq!(count, 2);
+/// assert_eq!(pixels, vec![255, 255, 255, 0, 250, 250, 250, 0, 0, 0, 0, 255]);
+/// ```
+pub fn make_transparent_by_opacity(pixels: &mut [u8], opacity: impl FnMut(&Rgba) -> f64) -> usize {
+    let mut count = 0;
+    for_each_pixel_by_color(pixels, opacity, |chunk, opacity| {
+        if scale_alpha(chunk, opacity) {
+            count += 1;
+        }
+    });
+    count
+}
+
+/// Makes background pixels connected to the flood-fill seeds transparent.
+///
+/// Unlike [`make_transparent_with_tolerance`], which clears every matching
+/// pixel, this only clears matching pixels reachable from `flood.seeds`
+/// through other matching pixels. Enclosed areas that happen to share the
+/// background color, such as white text on a white button, are kept.
+///
+/// Returns the number of pixels that were made transparent.
+///
+/// # Panics
+///
+/// Panics if `pixels.len()` is not `width * height * 4`.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::color::Color;
+/// use bgclipper::domain::distance::Tolerance;
+/// use bgclipper::domain::flood_fill::FloodFill;
+/// use bgclipper::domain::image_processor::make_transparent_flood;
+///
+/// // A white pixel surrounded by black is not connected to the border.
+/// #[rustfmt::skip]
+/// let mut pixels = vec![
+///     0, 0, 0, 255,  0, 0, 0, 255,        0, 0, 0, 255,
+///     0, 0, 0, 255,  255, 255, 255, 255,  0, 0, 0, 255,
+///     0, 0, 0, 255,  0, 0, 0, 255,        0, 0, 0, 255,
+/// ];
+/// let white = Color::new(255, 255, 255);
+/// let count = make_transparent_flood(
+///     &mut pixels, 3, 3, &white, &Tolerance::exact(), &FloodFill::default(),
+/// );
+/// assert_eq!(count, 0);
+/// assert_eq!(pixels[19], 255);
+/// ```
+pub fn make_transparent_flood(
+    pixels: &mut [u8],
+    width: u32,
+    height: u32,
+    target: &Color,
+    tolerance: &Tolerance,
+    flood: &FloodFill,
+) -> usize {
+    let mask = flood.region_mask(pixels, width, height, |pixel| {
+        tolerance.matches(target, &pixel.rgb())
+    });
+
+    let mut count = 0;
+    for (chunk, _) in pixels
+        .chunks_exact_mut(4)
+        .zip(mask)
+        .filter(|(_, in_region)| *in_region)
+    {
+        chunk[3] = 0;
+        count += 1;
+    }
+    count
+}
+
+/// Fades background pixels connected to the flood-fill seeds along an alpha ramp.
+///
+/// The flood-fill counterpart of [`make_transparent_soft`]: the region grows
+/// through every pixel the ramp would make at least partially transparent,
+/// and only pixels in that region are faded.
+///
+/// Returns the number of pixels whose alpha was reduced.
+///
+/// # Panics
+///
+/// Panics if `pixels.len()` is not `width * height * 4`.
+pub fn make_transparent_soft_flood(
+    pixels: &mut [u8],
+    width: u32,
+    height: u32,
+    target: &Color,
+    ramp: &AlphaRamp,
+    flood: &FloodFill,
+) -> usize {
+    make_transparent_flood_by_opacity(
+        pixels,
+        width,
+        height,
+        |pixel| ramp.opacity(target, &pixel.rgb()),
+        flood,
+    )
+}
+
+/// Scales the alpha of pixels connected to the flood-fill seeds by the
+/// opacity `opacity` assigns to them.
+///
+/// The flood-fill counterpart of [`make_transparent_by_opacity`]: a pixel is
+/// part of the background region when its opacity is below 1 and it is
+/// connected to a seed through other such pixels. `opacity` is called at
+/// most once per distinct RGBA value.
+///
+/// Returns the number of pixels whose alpha was reduced.
+///
+/// # Panics
+///
+/// Panics if `pixels.len()` is not `width * height * 4`.
+pub fn make_transparent_flood_by_opacity(
+    pixels: &mut [u8],
+    width: u32,
+    height: u32,
+    mut opacity: impl FnMut(&Rgba) -> f64,
+    flood: &FloodFill,
+) -> usize {
+    let mut opacities: HashMap<Rgba, f64> = HashMap::new();
+    let mask = flood.region_mask(pixels, width, height, |pixel| {
+        *opacities.entry(*pixel).or_insert_with(|| opacity(pixel)) < 1.0
+    });
+
+    let mut count = 0;
+    for (chunk, _) in pixels
+        .chunks_exact_mut(4)
+        .zip(mask)
+        .filter(|(_, in_region)| *in_region)
+    {
+        let pixel = Rgba::from_pixel(chunk);
+        // Every pixel in the region was classified while growing it
+        if scale_alpha(chunk, opacities[&pixel]) {
+            count += 1;
+        }
+    }
+    count
+}
+
+/// Optional steps applied after transparency conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PostProcessing {
+    /// Remove the background tint from partially transparent pixels
+    /// (see [`decontaminate_edges`]).
+    pub decontaminate_edges: bool,
+    /// Crop the image to its non-transparent content (see [`Crop::apply`]).
+    pub crop: Option<Crop>,
+}
+
+/// Removes the background color from partially transparent edge pixels.
+///
+/// Anti-aliased edges are a blend of foreground and background:
+/// `observed = alpha * foreground + (1 - alpha) * background`. After an alpha
+/// ramp has assigned each edge pixel its coverage, this solves for the
+/// foreground color so the old background no longer shows as a fringe when
+/// the image is pasted onto a different background.
+///
+/// Only pixels with `0 < alpha < 255` are touched; the alpha channel itself is
+/// left unchanged. The input is assumed to have been opaque before
+/// transparency was applied, so that alpha equals foreground coverage.
+///
+/// Returns the number of pixels whose color was changed.
+///
+/// # Panics
+///
+/// Panics if `pixels.len()` is not a multiple of 4.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::color::Color;
+/// use bgclipper::domain::image_processor::decontaminate_edges;
+///
+/// // 50% black over white, marked half transparent
+/// let mut pixels = vec![128, 128, 128, 128];
+/// let count = decontaminate_edges(&mut pixels, &Color::new(255, 255, 255));
+/// assert_eq!(count, 1);
+/// assert_eq!(pixels, vec![2, 2, 2, 128]);
+/// ```
+pub fn decontaminate_edges(pixels: &mut [u8], background: &Color) -> usize {
+    assert!(
+        pixels.len().is_multiple_of(4),
+        "pixel buffer length must be a multiple of 4, got {}",
+        pixels.len()
+    );
+
+    let background = [background.r(), background.g(), background.b()];
+    let mut count = 0;
+    for chunk in pixels.chunks_exact_mut(4) {
+        let alpha = chunk[3];
+        if alpha == 0 || alpha == u8::MAX {
+            continue;
+        }
+
+        let coverage = f64::from(alpha) / f64::from(u8::MAX);
+        let mut changed = false;
+        for (channel, bg) in chunk[..3].iter_mut().zip(background) {
+            let foreground = (f64::from(*channel) - (1.0 - coverage) * f64::from(bg)) / coverage;
+            let foreground = foreground.round().clamp(0.0, f64::from(u8::MAX)) as u8;
+            changed |= foreground != *channel;
+            *channel = foreground;
+        }
+        if changed {
+            count += 1;
+        }
+    }
+    count
+}
+
+/// Multiplies the alpha of one RGBA pixel by `opacity`, returning whether it dropped.
+fn scale_alpha(chunk: &mut [u8], opacity: f64) -> bool {
+    let alpha = (f64::from(chunk[3]) * opacity).round() as u8;
+    let reduced = alpha < chunk[3];
+    if reduced {
+        chunk[3] = alpha;
+    }
+    reduced
+}
+
+/// Classifies each pixel by its RGBA value and applies the verdict to it.
+///
+/// Screenshots contain long runs of identical colors, so the verdict for
+/// the previous pixel is reused when the pixel repeats. This skips most of
+/// the (comparatively expensive) distance computations.
+///
+/// # Panics
+///
+/// Panics if `pixels.len()` is not a multiple of 4.
+fn for_each_pixel_by_color<T: Copy>(
+    pixels: &mut [u8],
+    mut classify: impl FnMut(&Rgba) -> T,
+    mut apply: impl FnMut(&mut [u8], T),
+) {
+    assert!(
+        pixels.len().is_multiple_of(4),
+        "pixel buffer length must be a multiple of 4, got {}",
+        pixels.len()
+    );
+
+    let mut last: Option<(Rgba, T)> = None;
+    for chunk in pixels.chunks_exact_mut(4) {
+        let pixel = Rgba::from_pixel(chunk);
+        let verdict = match last {
+            Some((previous, verdict)) if previous == pixel => verdict,
+            _ => {
+                let verdict = classify(&pixel);
+                last = Some((pixel, verdict));
+                verdict
+            }
+        };
+        apply(chunk, verdict);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -129,4 +495,303 @@ mod tests {
         make_transparent(&mut pixels, &target);
         assert_eq!(pixels, vec![255, 255, 0, 255]);
     }
+
+    // -- make_transparent_with_tolerance --
+
+    use crate::domain::distance::DistanceMetric;
+
+    #[test]
+    fn tolerance_clears_near_target_pixels() {
+        let mut pixels = vec![
+            255, 255, 255, 255, // exact white -> transparent
+            252, 254, 255, 255, // near white -> transparent
+            240, 240, 240, 255, // light gray -> unchanged
+        ];
+        let target = Color::new(255, 255, 255);
+        let tolerance = Tolerance::new(DistanceMetric::EuclideanRgb, 5.0).unwrap();
+        let count = make_transparent_with_tolerance(&mut pixels, &target, &tolerance);
+        assert_eq!(count, 2);
+        assert_eq!(
+            pixels,
+            vec![255, 255, 255, 0, 252, 254, 255, 0, 240, 240, 240, 255]
+        );
+    }
+
+    #[test]
+    fn exact_tolerance_behaves_like_make_transparent() {
+        let original = vec![255, 255, 255, 255, 254, 255, 255, 255, 0, 0, 0, 255];
+        let target = Color::new(255, 255, 255);
+
+        let mut exact = original.clone();
+        make_transparent(&mut exact, &target);
+
+        let mut tolerant = original;
+        make_transparent_with_tolerance(&mut tolerant, &target, &Tolerance::exact());
+
+        assert_eq!(tolerant, exact);
+    }
+
+    #[test]
+    fn ciede2000_tolerance_clears_jpeg_noise() {
+        // #e7feb6 with +-2 noise on each channel
+        let mut pixels = vec![229, 255, 184, 255, 233, 252, 180, 255, 40, 90, 200, 255];
+        let target = Color::new(231, 254, 182);
+        let tolerance = Tolerance::new(DistanceMetric::Ciede2000, 2.3).unwrap();
+        let count = make_transparent_with_tolerance(&mut pixels, &target, &tolerance);
+        assert_eq!(count, 2);
+        assert_eq!(pixels[11], 255);
+    }
+
+    #[test]
+    fn repeated_colors_use_cached_verdict_consistently() {
+        let mut pixels = [250, 250, 250, 255].repeat(4);
+        pixels.extend_from_slice(&[0, 0, 0, 255]);
+        let target = Color::new(255, 255, 255);
+        let tolerance = Tolerance::new(DistanceMetric::ChannelMax, 5.0).unwrap();
+        let count = make_transparent_with_tolerance(&mut pixels, &target, &tolerance);
+        assert_eq!(count, 4);
+        assert_eq!(pixels[19], 255);
+    }
+
+    #[test]
+    #[should_panic(expected = "pixel buffer length must be a multiple of 4")]
+    fn tolerance_invalid_buffer_length_panics() {
+        let mut pixels = vec![255, 255, 255];
+        let target = Color::new(255, 255, 255);
+        let tolerance = Tolerance::new(DistanceMetric::Cie76, 1.0).unwrap();
+        make_transparent_with_tolerance(&mut pixels, &target, &tolerance);
+    }
+
+    // -- make_transparent_soft --
+
+    #[test]
+    fn soft_ramp_fades_alpha_between_bounds() {
+        let mut pixels = vec![
+            255, 255, 255, 255, // distance 0 -> transparent
+            250, 250, 250, 255, // distance 5 (inner) -> transparent
+            230, 230, 230, 255, // distance 25 -> half alpha
+            205, 205, 205, 255, // distance 50 (outer) -> unchanged
+        ];
+        let target = Color::new(255, 255, 255);
+        let ramp = AlphaRamp::new(DistanceMetric::ChannelMax, 5.0, 45.0).unwrap();
+        let count = make_transparent_soft(&mut pixels, &target, &ramp);
+        assert_eq!(count, 3);
+        assert_eq!(pixels[3], 0);
+        assert_eq!(pixels[7], 0);
+        assert_eq!(pixels[11], 128);
+        assert_eq!(pixels[15], 255);
+    }
+
+    #[test]
+    fn soft_ramp_scales_existing_alpha() {
+        let mut pixels = vec![230, 230, 230, 100];
+        let target = Color::new(255, 255, 255);
+        let ramp = AlphaRamp::new(DistanceMetric::ChannelMax, 0.0, 50.0).unwrap();
+        make_transparent_soft(&mut pixels, &target, &ramp);
+        assert_eq!(pixels, vec![230, 230, 230, 50]);
+    }
+
+    #[test]
+    fn soft_ramp_does_not_count_already_transparent_pixels() {
+        let mut pixels = vec![255, 255, 255, 0];
+        let target = Color::new(255, 255, 255);
+        let ramp = AlphaRamp::new(DistanceMetric::EuclideanRgb, 1.0, 10.0).unwrap();
+        let count = make_transparent_soft(&mut pixels, &target, &ramp);
+        assert_eq!(count, 0);
+        assert_eq!(pixels, vec![255, 255, 255, 0]);
+    }
+
+    #[test]
+    fn soft_ramp_leaves_rgb_unchanged() {
+        let mut pixels = vec![240, 10, 90, 255];
+        let target = Color::new(255, 0, 100);
+        let ramp = AlphaRamp::new(DistanceMetric::EuclideanRgb, 0.0, 100.0).unwrap();
+        make_transparent_soft(&mut pixels, &target, &ramp);
+        assert_eq!(&pixels[..3], &[240, 10, 90]);
+    }
+
+    // -- decontaminate_edges --
+
+    #[test]
+    fn decontaminate_recovers_blended_foreground() {
+        // 25% coverage of RGB(40, 80, 120) over white
+        let mut pixels = vec![201, 211, 221, 64];
+        let count = decontaminate_edges(&mut pixels, &Color::new(255, 255, 255));
+        assert_eq!(count, 1);
+        assert_eq!(pixels, vec![40, 80, 120, 64]);
+    }
+
+    #[test]
+    fn decontaminate_skips_opaque_and_transparent_pixels() {
+        let mut pixels = vec![200, 200, 200, 255, 200, 200, 200, 0];
+        let count = decontaminate_edges(&mut pixels, &Color::new(255, 255, 255));
+        assert_eq!(count, 0);
+        assert_eq!(pixels, vec![200, 200, 200, 255, 200, 200, 200, 0]);
+    }
+
+    #[test]
+    fn decontaminate_clamps_out_of_gamut_results() {
+        // Brighter than the background at low coverage: solution exceeds 255
+        let mut pixels = vec![250, 0, 10, 26];
+        decontaminate_edges(&mut pixels, &Color::new(200, 200, 200));
+        assert_eq!(pixels, vec![255, 0, 0, 26]);
+    }
+
+    #[test]
+    fn decontaminate_after_soft_ramp_removes_background_tint() {
+        // 50% blend of dark gray RGB(34, 35, 34) over green
+        let mut pixels = vec![17, 145, 17, 255];
+        let green = Color::new(0, 255, 0);
+        let ramp = AlphaRamp::new(DistanceMetric::EuclideanRgb, 0.0, 225.0).unwrap();
+        make_transparent_soft(&mut pixels, &green, &ramp);
+        decontaminate_edges(&mut pixels, &green);
+        assert_eq!(pixels, vec![34, 36, 34, 128]);
+    }
+
+    // -- make_transparent_flood / make_transparent_soft_flood --
+
+    use crate::domain::flood_fill::{Connectivity, FloodSeeds};
+
+    #[test]
+    fn flood_keeps_enclosed_background_colored_pixels() {
+        let w = [255, 255, 255, 255];
+        let k = [0, 0, 0, 255];
+        #[rustfmt::skip]
+        let rows = [
+            [w, w, w, w, w],
+            [w, k, k, k, w],
+            [w, k, w, k, w],
+            [w, k, k, k, w],
+            [w, w, w, w, w],
+        ];
+        let mut pixels: Vec<u8> = rows.iter().flatten().flatten().copied().collect();
+        let target = Color::new(255, 255, 255);
+        let count = make_transparent_flood(
+            &mut pixels,
+            5,
+            5,
+            &target,
+            &Tolerance::exact(),
+            &FloodFill::default(),
+        );
+        assert_eq!(count, 16);
+        // Enclosed white centre pixel stays opaque
+        assert_eq!(pixels[(2 * 5 + 2) * 4 + 3], 255);
+    }
+
+    #[test]
+    fn flood_from_seed_point_clears_connected_region_only() {
+        // white, white, black, white
+        let mut pixels = vec![
+            255, 255, 255, 255, 255, 255, 255, 255, 0, 0, 0, 255, 255, 255, 255, 255,
+        ];
+        let target = Color::new(255, 255, 255);
+        let flood = FloodFill {
+            seeds: FloodSeeds::Points(vec![(0, 0)]),
+            connectivity: Connectivity::Four,
+        };
+        let count = make_transparent_flood(&mut pixels, 4, 1, &target, &Tolerance::exact(), &flood);
+        assert_eq!(count, 2);
+        assert_eq!(
+            pixels,
+            vec![
+                255, 255, 255, 0, 255, 255, 255, 0, 0, 0, 0, 255, 255, 255, 255, 255
+            ]
+        );
+    }
+
+    #[test]
+    fn soft_flood_fades_connected_pixels_only() {
+        let w = [255, 255, 255, 255];
+        let g = [205, 205, 205, 255];
+        let k = [0, 0, 0, 255];
+        #[rustfmt::skip]
+        let rows = [
+            [g, w, w],
+            [k, k, k],
+            [k, g, k],
+            [k, k, k],
+        ];
+        let mut pixels: Vec<u8> = rows.iter().flatten().flatten().copied().collect();
+        let target = Color::new(255, 255, 255);
+        let ramp = AlphaRamp::new(DistanceMetric::ChannelMax, 0.0, 100.0).unwrap();
+        let flood = FloodFill {
+            seeds: FloodSeeds::Points(vec![(1, 0)]),
+            connectivity: Connectivity::Four,
+        };
+        let count = make_transparent_soft_flood(&mut pixels, 3, 4, &target, &ramp, &flood);
+
+        assert_eq!(count, 3);
+        assert_eq!(pixels[3], 128); // connected gray -> half alpha
+        assert_eq!(pixels[7], 0); // white -> transparent
+        assert_eq!(pixels[(2 * 3 + 1) * 4 + 3], 255); // enclosed gray unchanged
+    }
+
+    #[test]
+    fn by_opacity_removes_several_colors() {
+        let mut pixels = vec![
+            255, 255, 255, 255, // white -> transparent
+            250, 250, 250, 200, // off-white -> half of 200
+            0, 0, 0, 255, // black -> unchanged
+        ];
+        let count = make_transparent_by_opacity(&mut pixels, |pixel| match pixel.rgb().r() {
+            255 => 0.0,
+            250 => 0.5,
+            _ => 1.0,
+        });
+        assert_eq!(count, 2);
+        assert_eq!(
+            pixels,
+            vec![255, 255, 255, 0, 250, 250, 250, 100, 0, 0, 0, 255]
+        );
+    }
+
+    #[test]
+    fn by_opacity_sees_existing_alpha() {
+        let mut pixels = vec![255, 255, 255, 255, 255, 255, 255, 128];
+        let count =
+            make_transparent_by_opacity(
+                &mut pixels,
+                |pixel| {
+                    if pixel.is_opaque() { 0.0 } else { 1.0 }
+                },
+            );
+        assert_eq!(count, 1);
+        assert_eq!(pixels, vec![255, 255, 255, 0, 255, 255, 255, 128]);
+    }
+
+    #[test]
+    fn by_opacity_does_not_count_already_transparent_pixels() {
+        let mut pixels = vec![255, 255, 255, 0];
+        assert_eq!(make_transparent_by_opacity(&mut pixels, |_| 0.0), 0);
+    }
+
+    #[test]
+    fn flood_by_opacity_grows_across_different_background_colors() {
+        // Border-connected white and off-white next to each other; the
+        // enclosed off-white centre of the black ring is kept.
+        let w = [255, 255, 255, 255];
+        let o = [250, 250, 250, 255];
+        let k = [0, 0, 0, 255];
+        #[rustfmt::skip]
+        let rows: [[[u8; 4]; 5]; 5] = [
+            [w, o, o, o, w],
+            [w, k, k, k, o],
+            [o, k, o, k, o],
+            [w, k, k, k, w],
+            [w, w, o, w, w],
+        ];
+        let mut pixels: Vec<u8> = rows.iter().flatten().flatten().copied().collect();
+
+        let count = make_transparent_flood_by_opacity(
+            &mut pixels,
+            5,
+            5,
+            |pixel| if pixel.rgb().r() >= 250 { 0.0 } else { 1.0 },
+            &FloodFill::default(),
+        );
+
+        assert_eq!(count, 16);
+        assert_eq!(pixels[(2 * 5 + 2) * 4 + 3], 255);
+    }
 }
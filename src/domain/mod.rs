@@ -1,3 +1,13 @@
+pub mod background;
 pub mod color;
+pub mod crop;
+pub mod distance;
+pub mod flood_fill;
+pub mod history;
 pub mod image_processor;
+mod named_colors;
+pub mod pipeline;
 pub mod port;
+pub mod profile;
+pub mod representation;
+pub mod target;
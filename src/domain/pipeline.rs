@@ -0,0 +1,230 @@
+use crate::domain::background::{BackgroundEstimate, estimate_background};
+use crate::domain::color::Rgba;
+use crate::domain::flood_fill::RemovalMode;
+use crate::domain::image_processor::{
+    decontaminate_edges, make_transparent_by_opacity, make_transparent_flood_by_opacity,
+};
+use crate::domain::port::ImageData;
+use crate::domain::profile::Profile;
+use crate::domain::target::{TargetColor, pixel_opacity};
+
+/// Result of running the background removal pipeline on one image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// The given number of pixels were made (partly) transparent.
+    Changed(usize),
+    /// No pixel matched, so the image was left unchanged.
+    Unchanged,
+    /// Automatic detection could not identify the background with enough
+    /// confidence, so the image was left unchanged.
+    Undetected(Option<BackgroundEstimate>),
+}
+
+/// Removes the background from `image` according to `profile`.
+///
+/// This is the processing shared by the clipboard service and the CLI:
+///
+/// 1. Picks the target colors — the profile's, or the background inferred
+///    from the image border if auto-detection is on.
+/// 2. Makes matching pixels transparent per the tolerance, alpha ramp,
+///    alpha matching rule and removal mode.
+/// 3. Applies the post-processing steps if anything changed: edge
+///    decontamination, then cropping to the remaining content, which
+///    updates the image's width and height.
+///
+/// # Panics
+///
+/// Panics if `image.pixels.len()` is not `width * height * 4`.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::pipeline::{Outcome, remove_background};
+/// use bgclipper::domain::port::ImageData;
+/// use bgclipper::domain::profile::Profile;
+///
+/// let mut image = ImageData {
+///     pixels: vec![255, 255, 255, 255, 0, 0, 0, 255],
+///     width: 2,
+///     height: 1,
+/// };
+/// let outcome = remove_background(&mut image, &Profile::default());
+/// assert_eq!(outcome, Outcome::Changed(1));
+/// assert_eq!(image.pixels, vec![255, 255, 255, 0, 0, 0, 0, 255]);
+/// ```
+pub fn remove_background(image: &mut ImageData, profile: &Profile) -> Outcome {
+    let detected;
+    let targets: &[TargetColor] = match &profile.auto_detect {
+        Some(auto) => {
+            let estimate = estimate_background(&image.pixels, image.width, image.height);
+            match estimate.filter(|estimate| auto.accepts(estimate)) {
+                Some(estimate) => {
+                    detected = [TargetColor::new(estimate.color)];
+                    &detected
+                }
+                None => return Outcome::Undetected(estimate),
+            }
+        }
+        None => &profile.targets,
+    };
+
+    let opacity = |pixel: &Rgba| {
+        pixel_opacity(
+            targets,
+            pixel,
+            profile.alpha_matching,
+            &profile.tolerance,
+            profile.alpha_ramp.as_ref(),
+        )
+    };
+    let changed = match &profile.mode {
+        RemovalMode::Global => make_transparent_by_opacity(&mut image.pixels, opacity),
+        RemovalMode::Flood(flood) => make_transparent_flood_by_opacity(
+            &mut image.pixels,
+            image.width,
+            image.height,
+            opacity,
+            flood,
+        ),
+    };
+    if changed == 0 {
+        return Outcome::Unchanged;
+    }
+
+    // Edges can only be unmixed against one background; use the primary target
+    if let (true, Some(primary)) = (profile.post_processing.decontaminate_edges, targets.first()) {
+        decontaminate_edges(&mut image.pixels, &primary.color);
+    }
+    if let Some(crop) = &profile.post_processing.crop {
+        crop.apply(image);
+    }
+
+    Outcome::Changed(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::background::AutoDetect;
+    use crate::domain::color::Color;
+    use crate::domain::crop::Crop;
+    use crate::domain::distance::{AlphaRamp, DistanceMetric};
+    use crate::domain::flood_fill::FloodFill;
+    use crate::domain::image_processor::PostProcessing;
+    use crate::domain::target::AlphaMatching;
+
+    fn image(pixels: Vec<u8>, width: u32, height: u32) -> ImageData {
+        ImageData {
+            pixels,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn unchanged_when_nothing_matches() {
+        let mut img = image(vec![0, 0, 0, 255], 1, 1);
+        assert_eq!(
+            remove_background(&mut img, &Profile::default()),
+            Outcome::Unchanged
+        );
+        assert_eq!(img.pixels, vec![0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn flood_mode_keeps_enclosed_pixels() {
+        #[rustfmt::skip]
+        let mut img = image(vec![
+            255, 255, 255, 255,  0, 0, 0, 255,        255, 255, 255, 255,
+            0, 0, 0, 255,        255, 255, 255, 255,  0, 0, 0, 255,
+            255, 255, 255, 255,  0, 0, 0, 255,        255, 255, 255, 255,
+        ], 3, 3);
+        let profile = Profile {
+            mode: RemovalMode::Flood(FloodFill::default()),
+            ..Profile::default()
+        };
+
+        assert_eq!(remove_background(&mut img, &profile), Outcome::Changed(4));
+        assert_eq!(img.pixels[4 * 4 + 3], 255);
+    }
+
+    #[test]
+    fn auto_detect_uses_border_color() {
+        let mut pixels = [0, 0, 255, 255].repeat(9);
+        pixels[16..20].copy_from_slice(&[255, 0, 0, 255]);
+        let mut img = image(pixels, 3, 3);
+        let profile = Profile {
+            auto_detect: Some(AutoDetect::default()),
+            ..Profile::default()
+        };
+
+        assert_eq!(remove_background(&mut img, &profile), Outcome::Changed(8));
+    }
+
+    #[test]
+    fn auto_detect_reports_low_confidence() {
+        let original = vec![
+            255, 0, 0, 255, 255, 0, 0, 255, //
+            0, 0, 255, 255, 0, 0, 255, 255,
+        ];
+        let mut img = image(original.clone(), 2, 2);
+        let profile = Profile {
+            auto_detect: Some(AutoDetect::default()),
+            ..Profile::default()
+        };
+
+        let outcome = remove_background(&mut img, &profile);
+        assert!(matches!(outcome, Outcome::Undetected(Some(e)) if e.confidence == 0.5));
+        assert_eq!(img.pixels, original);
+    }
+
+    #[test]
+    fn opaque_only_keeps_translucent_background() {
+        let mut img = image(vec![255, 255, 255, 255, 255, 255, 255, 128], 2, 1);
+        let profile = Profile {
+            alpha_matching: AlphaMatching::OpaqueOnly,
+            ..Profile::default()
+        };
+
+        assert_eq!(remove_background(&mut img, &profile), Outcome::Changed(1));
+        assert_eq!(img.pixels, vec![255, 255, 255, 0, 255, 255, 255, 128]);
+    }
+
+    #[test]
+    fn decontaminates_against_primary_target() {
+        let mut img = image(vec![0, 255, 0, 255, 17, 145, 17, 255], 2, 1);
+        let profile = Profile {
+            targets: vec![TargetColor::new(Color::new(0, 255, 0))],
+            alpha_ramp: AlphaRamp::new(DistanceMetric::EuclideanRgb, 0.0, 220.0),
+            post_processing: PostProcessing {
+                decontaminate_edges: true,
+                ..PostProcessing::default()
+            },
+            ..Profile::default()
+        };
+
+        assert_eq!(remove_background(&mut img, &profile), Outcome::Changed(2));
+        assert_eq!(img.pixels[3], 0);
+        // Green tint removed from the partially transparent pixel
+        assert!(img.pixels[5] < 145);
+    }
+
+    #[test]
+    fn crops_to_remaining_content() {
+        #[rustfmt::skip]
+        let mut img = image(vec![
+            255, 255, 255, 255,  255, 255, 255, 255,  255, 255, 255, 255,
+            255, 255, 255, 255,  0, 0, 0, 255,        255, 255, 255, 255,
+        ], 3, 2);
+        let profile = Profile {
+            post_processing: PostProcessing {
+                crop: Some(Crop { padding: 0 }),
+                ..PostProcessing::default()
+            },
+            ..Profile::default()
+        };
+
+        assert_eq!(remove_background(&mut img, &profile), Outcome::Changed(5));
+        assert_eq!(img, image(vec![0, 0, 0, 255], 1, 1));
+    }
+}
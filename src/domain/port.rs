@@ -1,4 +1,12 @@
+use crate::domain::background::AutoDetect;
 use crate::domain::color::Color;
+use crate::domain::distance::{AlphaRamp, Tolerance};
+use crate::domain::flood_fill::RemovalMode;
+use crate::domain::history::{HistoryEntry, HistoryImage, HistoryRecord};
+use crate::domain::image_processor::PostProcessing;
+use crate::domain::profile::Profile;
+use crate::domain::representation::Representation;
+use crate::domain::target::{AlphaMatching, TargetColor};
 
 /// RGBA image data with dimensions.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -34,27 +42,412 @@ pub trait ClipboardPort {
 
     /// Writes RGBA pixel data to the clipboard as an image.
     fn set_image(&self, image: &ImageData) -> Result<(), Self::Error>;
+
+    /// Reads the clipboard content as encoded data of `mime_type`, such as
+    /// [`PNG_MIME_TYPE`], without converting it to pixels first.
+    ///
+    /// Encoded data keeps metadata that raw pixels lose (DPI, color profile).
+    /// Returns `Ok(None)` if the content is not available in that format or
+    /// the clipboard does not support encoded access, which is the default.
+    fn get_encoded(&self, mime_type: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        let _ = mime_type;
+        Ok(None)
+    }
+
+    /// Writes `image` to the clipboard together with `representations` of
+    /// it, so each pasting application can pick the format it prefers.
+    ///
+    /// Representations are listed in order of preference. The default
+    /// ignores them and writes only the pixels with [`set_image`](Self::set_image).
+    fn set_representations(
+        &self,
+        image: &ImageData,
+        representations: &[Representation],
+    ) -> Result<(), Self::Error> {
+        let _ = representations;
+        self.set_image(image)
+    }
+}
+
+/// MIME type of PNG data, for [`ClipboardPort::get_encoded`] and
+/// [`Representation`]s.
+pub const PNG_MIME_TYPE: &str = "image/png";
+
+/// Lets a caller keep access to a clipboard it lends to a service, e.g. to
+/// inspect a fake clipboard after processing.
+impl<T: ClipboardPort + ?Sized> ClipboardPort for &T {
+    type Error = T::Error;
+
+    fn change_count(&self) -> Result<u64, Self::Error> {
+        (**self).change_count()
+    }
+
+    fn get_image(&self) -> Result<Option<ImageData>, Self::Error> {
+        (**self).get_image()
+    }
+
+    fn set_image(&self, image: &ImageData) -> Result<(), Self::Error> {
+        (**self).set_image(image)
+    }
+
+    fn get_encoded(&self, mime_type: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        (**self).get_encoded(mime_type)
+    }
+
+    fn set_representations(
+        &self,
+        image: &ImageData,
+        representations: &[Representation],
+    ) -> Result<(), Self::Error> {
+        (**self).set_representations(image, representations)
+    }
+}
+
+/// Port for converting between an encoded image format and pixel data.
+///
+/// Lets the clipboard service read and write encoded clipboard data while
+/// carrying the format's metadata, which the domain never inspects, from the
+/// decoded image to the re-encoded one.
+pub trait ImageCodec {
+    /// The error type returned when decoding or encoding fails.
+    type Error: std::error::Error;
+
+    /// Metadata kept from decoding to re-encoding (e.g. DPI, color profile).
+    ///
+    /// The default is used when encoding an image that was not decoded.
+    type Metadata: Default;
+
+    /// Returns the MIME type this codec reads and writes.
+    fn mime_type(&self) -> &str;
+
+    /// Decodes `data` into RGBA pixel data and its metadata.
+    fn decode(&self, data: &[u8]) -> Result<(ImageData, Self::Metadata), Self::Error>;
+
+    /// Encodes RGBA pixel data, attaching `metadata`.
+    fn encode(&self, image: &ImageData, metadata: &Self::Metadata) -> Result<Vec<u8>, Self::Error>;
+}
+
+/// An [`ImageCodec`] that cannot be constructed, for services that only use
+/// raw pixel data.
+#[derive(Debug, Clone, Copy)]
+pub enum NoCodec {}
+
+impl ImageCodec for NoCodec {
+    type Error = std::convert::Infallible;
+    type Metadata = ();
+
+    fn mime_type(&self) -> &str {
+        match *self {}
+    }
+
+    fn decode(&self, _data: &[u8]) -> Result<(ImageData, ()), Self::Error> {
+        match *self {}
+    }
+
+    fn encode(&self, _image: &ImageData, _metadata: &()) -> Result<Vec<u8>, Self::Error> {
+        match *self {}
+    }
+}
+
+/// Port for the history of processed images.
+///
+/// Keeps the most recent entries up to an implementation-defined limit,
+/// dropping the oldest. Entries are listed without their full images, which
+/// are retrieved one at a time with [`load_image`](Self::load_image).
+pub trait HistoryPort {
+    /// The error type returned by history operations.
+    type Error: std::error::Error;
+
+    /// Adds `record` as the newest entry and returns its summary.
+    fn record(&self, record: HistoryRecord) -> Result<HistoryEntry, Self::Error>;
+
+    /// Returns the kept entries, newest first.
+    fn entries(&self) -> Result<Vec<HistoryEntry>, Self::Error>;
+
+    /// Returns the processed or original image of the entry `id`.
+    ///
+    /// Returns `None` if the entry is no longer kept.
+    fn load_image(&self, id: u64, image: HistoryImage) -> Result<Option<ImageData>, Self::Error>;
+
+    /// Returns the processed or original image of the entry `id` in encoded
+    /// form, keeping metadata such as DPI that raw pixels lose.
+    ///
+    /// Returns `None` if the entry is no longer kept or the history does not
+    /// keep encoded images, which is the default.
+    fn load_encoded(
+        &self,
+        id: u64,
+        image: HistoryImage,
+    ) -> Result<Option<Representation>, Self::Error> {
+        let _ = (id, image);
+        Ok(None)
+    }
+}
+
+impl<T: HistoryPort + ?Sized> HistoryPort for &T {
+    type Error = T::Error;
+
+    fn record(&self, record: HistoryRecord) -> Result<HistoryEntry, Self::Error> {
+        (**self).record(record)
+    }
+
+    fn entries(&self) -> Result<Vec<HistoryEntry>, Self::Error> {
+        (**self).entries()
+    }
+
+    fn load_image(&self, id: u64, image: HistoryImage) -> Result<Option<ImageData>, Self::Error> {
+        (**self).load_image(id, image)
+    }
+
+    fn load_encoded(
+        &self,
+        id: u64,
+        image: HistoryImage,
+    ) -> Result<Option<Representation>, Self::Error> {
+        (**self).load_encoded(id, image)
+    }
+}
+
+/// A [`HistoryPort`] that cannot be constructed, for services without a
+/// history.
+#[derive(Debug, Clone, Copy)]
+pub enum NoHistory {}
+
+impl HistoryPort for NoHistory {
+    type Error = std::convert::Infallible;
+
+    fn record(&self, _record: HistoryRecord) -> Result<HistoryEntry, Self::Error> {
+        match *self {}
+    }
+
+    fn entries(&self) -> Result<Vec<HistoryEntry>, Self::Error> {
+        match *self {}
+    }
+
+    fn load_image(&self, _id: u64, _image: HistoryImage) -> Result<Option<ImageData>, Self::Error> {
+        match *self {}
+    }
+}
+
+/// Port for being notified when the clipboard may have changed.
+///
+/// Lets the caller react to clipboard changes as they happen instead of
+/// checking [`ClipboardPort::change_count`] on a timer. Notifications are
+/// hints: a spurious one is harmless because the change counter still
+/// decides whether there is anything new to process.
+pub trait ClipboardWatcher {
+    /// The error type returned when watching cannot start.
+    type Error: std::error::Error;
+
+    /// Starts watching in the background and calls `on_change` (from another
+    /// thread) after each clipboard change.
+    ///
+    /// A watcher can only be started once.
+    fn watch<F>(&self, on_change: F) -> Result<(), Self::Error>
+    where
+        F: FnMut() + Send + 'static;
 }
 
 /// Port for reading and writing application configuration.
 ///
 /// Implementations handle config file I/O (e.g., TOML parsing).
 /// The domain layer depends only on this trait.
+///
+/// The processing settings (target colors, tolerance, mode, ...) are read
+/// from and written to the active profile, or the top-level settings if no
+/// profile is active.
+///
+/// Only the whole settings and the profiles must be implemented; the
+/// accessors for single settings default to reading or rewriting the whole
+/// settings, and can be overridden by implementations that store them
+/// individually.
 pub trait ConfigPort {
     /// The error type returned by config operations.
     type Error: std::error::Error;
 
+    /// Loads all processing settings of the active profile (or the top-level
+    /// settings).
+    ///
+    /// Returns the defaults for settings that are not configured.
+    fn load_settings(&self) -> Result<Profile, Self::Error>;
+
+    /// Saves all processing settings to the active profile (or the top-level
+    /// settings).
+    fn save_settings(&self, settings: &Profile) -> Result<(), Self::Error>;
+
     /// Loads the target color from the configuration.
     ///
     /// Returns the configured target color, or a default if no config exists.
-    fn load_target_color(&self) -> Result<Color, Self::Error>;
+    fn load_target_color(&self) -> Result<Color, Self::Error> {
+        Ok(self
+            .load_target_colors()?
+            .first()
+            .map_or_else(Color::default, |target| target.color))
+    }
 
     /// Saves the target color to the configuration.
-    fn save_target_color(&self, color: &Color) -> Result<(), Self::Error>;
+    ///
+    /// Replaces any list of target colors with this single color.
+    fn save_target_color(&self, color: &Color) -> Result<(), Self::Error> {
+        self.save_target_colors(&[TargetColor::new(*color)])
+    }
+
+    /// Loads every target color, each with its optional own tolerance.
+    ///
+    /// Falls back to the single target color if no list is configured, so the
+    /// result always has at least one entry.
+    ///
+    fn load_target_colors(&self) -> Result<Vec<TargetColor>, Self::Error> {
+        Ok(self.load_settings()?.targets)
+    }
+
+    /// Saves the list of target colors to the configuration.
+    fn save_target_colors(&self, targets: &[TargetColor]) -> Result<(), Self::Error> {
+        update_settings(self, |settings| settings.targets = targets.to_vec())
+    }
+
+    /// Loads the automatic background detection settings.
+    ///
+    /// Returns `None` if auto-detection is off, in which case the configured
+    /// target color is used.
+    fn load_auto_detect(&self) -> Result<Option<AutoDetect>, Self::Error> {
+        Ok(self.load_settings()?.auto_detect)
+    }
+
+    /// Saves the automatic background detection settings.
+    ///
+    /// Passing `None` switches back to the configured target color.
+    fn save_auto_detect(&self, auto: Option<&AutoDetect>) -> Result<(), Self::Error> {
+        update_settings(self, |settings| settings.auto_detect = auto.cloned())
+    }
+
+    /// Loads the color-matching tolerance from the configuration.
+    ///
+    /// Returns an exact-match tolerance if none is configured.
+    fn load_tolerance(&self) -> Result<Tolerance, Self::Error> {
+        Ok(self.load_settings()?.tolerance)
+    }
+
+    /// Saves the color-matching tolerance to the configuration.
+    fn save_tolerance(&self, tolerance: &Tolerance) -> Result<(), Self::Error> {
+        update_settings(self, |settings| settings.tolerance = *tolerance)
+    }
+
+    /// Loads the soft-edge alpha ramp from the configuration.
+    ///
+    /// Returns `None` if soft edges are not configured, in which case
+    /// matching pixels are made fully transparent.
+    fn load_alpha_ramp(&self) -> Result<Option<AlphaRamp>, Self::Error> {
+        Ok(self.load_settings()?.alpha_ramp)
+    }
+
+    /// Saves the soft-edge alpha ramp to the configuration.
+    ///
+    /// Passing `None` switches back to binary transparency.
+    fn save_alpha_ramp(&self, ramp: Option<&AlphaRamp>) -> Result<(), Self::Error> {
+        update_settings(self, |settings| settings.alpha_ramp = ramp.copied())
+    }
+
+    /// Loads the post-processing steps from the configuration.
+    ///
+    /// Returns the defaults (all steps disabled) if none are configured.
+    fn load_post_processing(&self) -> Result<PostProcessing, Self::Error> {
+        Ok(self.load_settings()?.post_processing)
+    }
+
+    /// Saves the post-processing steps to the configuration.
+    fn save_post_processing(&self, post: &PostProcessing) -> Result<(), Self::Error> {
+        update_settings(self, |settings| settings.post_processing = *post)
+    }
+
+    /// Loads which pixels background removal applies to.
+    ///
+    /// Returns [`RemovalMode::Global`] if no mode is configured.
+    fn load_removal_mode(&self) -> Result<RemovalMode, Self::Error> {
+        Ok(self.load_settings()?.mode)
+    }
+
+    /// Saves which pixels background removal applies to.
+    fn save_removal_mode(&self, mode: &RemovalMode) -> Result<(), Self::Error> {
+        update_settings(self, |settings| settings.mode = mode.clone())
+    }
+
+    /// Loads how a pixel's existing alpha affects matching.
+    ///
+    /// Returns [`AlphaMatching::Ignore`] if no rule is configured.
+    fn load_alpha_matching(&self) -> Result<AlphaMatching, Self::Error> {
+        Ok(self.load_settings()?.alpha_matching)
+    }
+
+    /// Saves how a pixel's existing alpha affects matching.
+    fn save_alpha_matching(&self, matching: AlphaMatching) -> Result<(), Self::Error> {
+        update_settings(self, |settings| settings.alpha_matching = matching)
+    }
+
+    /// Returns the names of all defined profiles, in sorted order.
+    fn list_profiles(&self) -> Result<Vec<String>, Self::Error>;
+
+    /// Loads the profile with the given name.
+    ///
+    /// Returns `None` if no such profile is defined.
+    fn load_profile(&self, name: &str) -> Result<Option<Profile>, Self::Error>;
+
+    /// Creates or replaces the profile with the given name.
+    fn save_profile(&self, name: &str, profile: &Profile) -> Result<(), Self::Error>;
+
+    /// Returns the name of the active profile.
+    ///
+    /// Returns `None` if the top-level settings are in use.
+    fn load_active_profile(&self) -> Result<Option<String>, Self::Error>;
+
+    /// Switches to the named profile, or back to the top-level settings with `None`.
+    ///
+    /// Fails if the named profile is not defined.
+    fn save_active_profile(&self, name: Option<&str>) -> Result<(), Self::Error>;
 
     /// Ensures the config file exists.
     ///
     /// If the config file does not exist, creates it with default settings.
     /// If the file already exists, does nothing.
     fn ensure_config_exists(&self) -> Result<(), Self::Error>;
+
+    /// Reloads the configuration if its source changed since it was last
+    /// loaded.
+    ///
+    /// Returns `true` if a new configuration was loaded. If the changed
+    /// source is invalid, the error is returned and the previously loaded
+    /// configuration stays in effect.
+    ///
+    /// The default does nothing, for implementations that read their source
+    /// on every call.
+    fn reload(&self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+}
+
+/// Loads the settings of `config`, changes them with `update` and saves them
+/// whole.
+fn update_settings<G: ConfigPort + ?Sized>(
+    config: &G,
+    update: impl FnOnce(&mut Profile),
+) -> Result<(), G::Error> {
+    let mut settings = config.load_settings()?;
+    update(&mut settings);
+    config.save_settings(&settings)
+}
+
+/// Port for being notified when the configuration source may have changed.
+///
+/// Pairs with [`ConfigPort::reload`], which decides whether anything changed.
+pub trait ConfigWatcher {
+    /// The error type returned when watching cannot start.
+    type Error: std::error::Error;
+
+    /// Starts watching in the background and calls `on_change` (from another
+    /// thread) after each change of the configuration source.
+    ///
+    /// A watcher can only be started once.
+    fn watch<F>(&self, on_change: F) -> Result<(), Self::Error>
+    where
+        F: FnMut() + Send + 'static;
 }
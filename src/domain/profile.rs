@@ -0,0 +1,58 @@
+use crate::domain::background::AutoDetect;
+use crate::domain::distance::{AlphaRamp, Tolerance};
+use crate::domain::flood_fill::RemovalMode;
+use crate::domain::image_processor::PostProcessing;
+use crate::domain::target::{AlphaMatching, TargetColor};
+
+/// A named bundle of processing settings, such as "remove white" or
+/// "remove green screen".
+///
+/// Exactly one profile (or the top-level settings) is active at a time;
+/// [`ConfigPort::load_settings`](crate::domain::port::ConfigPort::load_settings)
+/// reads it.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::color::Color;
+/// use bgclipper::domain::profile::Profile;
+/// use bgclipper::domain::target::TargetColor;
+///
+/// let green_screen = Profile {
+///     targets: vec![TargetColor::new(Color::new(0, 255, 0))],
+///     ..Profile::default()
+/// };
+/// assert!(green_screen.tolerance.is_exact());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    /// Background colors to remove. Ignored while `auto_detect` is set.
+    pub targets: Vec<TargetColor>,
+    /// Infer the background from each image instead of using `targets`.
+    pub auto_detect: Option<AutoDetect>,
+    /// Shared tolerance for targets without their own.
+    pub tolerance: Tolerance,
+    /// Soft-edge alpha ramp; takes precedence over `tolerance` when set.
+    pub alpha_ramp: Option<AlphaRamp>,
+    /// How pixels that are already partially transparent are matched.
+    pub alpha_matching: AlphaMatching,
+    /// Which pixels removal applies to.
+    pub mode: RemovalMode,
+    /// Steps applied after removal.
+    pub post_processing: PostProcessing,
+}
+
+impl Default for Profile {
+    /// Returns the built-in defaults: exact white removal across the whole image.
+    fn default() -> Self {
+        Self {
+            targets: vec![TargetColor::default()],
+            auto_detect: None,
+            tolerance: Tolerance::exact(),
+            alpha_ramp: None,
+            alpha_matching: AlphaMatching::default(),
+            mode: RemovalMode::default(),
+            post_processing: PostProcessing::default(),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+/// MIME type of HTML fragments.
+pub const HTML_MIME_TYPE: &str = "text/html";
+
+/// One encoded form of the clipboard content, such as PNG data or an HTML
+/// fragment.
+///
+/// Clipboards can offer the same content in several forms at once; each
+/// pasting application picks the one it understands best.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Representation {
+    /// MIME type of `data`, e.g. `image/png`.
+    pub mime_type: String,
+    /// The encoded content.
+    pub data: Vec<u8>,
+}
+
+impl Representation {
+    /// Creates a representation of `data` in the format `mime_type`.
+    pub fn new(mime_type: impl Into<String>, data: Vec<u8>) -> Self {
+        Self {
+            mime_type: mime_type.into(),
+            data,
+        }
+    }
+
+    /// Returns an HTML fragment embedding this image as a data URI, for
+    /// applications that paste HTML but not images.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bgclipper::domain::representation::Representation;
+    ///
+    /// let png = Representation::new("image/png", b"PNG".to_vec());
+    /// let html = png.to_html_image();
+    /// assert_eq!(html.mime_type, "text/html");
+    /// assert_eq!(html.data, br#"<img src="data:image/png;base64,UE5H">"#);
+    /// ```
+    pub fn to_html_image(&self) -> Representation {
+        let html = format!(
+            r#"<img src="data:{};base64,{}">"#,
+            self.mime_type,
+            base64(&self.data)
+        );
+        Representation::new(HTML_MIME_TYPE, html.into_bytes())
+    }
+}
+
+/// Encodes `data` as standard, padded base64 (RFC 4648).
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3F;
+                out.push(ALPHABET[index as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_matches_rfc_4648_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (input, expected) in vectors {
+            assert_eq!(base64(input.as_bytes()), expected, "input {input:?}");
+        }
+    }
+
+    #[test]
+    fn base64_uses_plus_and_slash() {
+        assert_eq!(base64(&[0xFB, 0xFF, 0xBF, 0xFB, 0xFF, 0xBF]), "+/+/+/+/");
+    }
+
+    #[test]
+    fn html_image_embeds_data_uri() {
+        let png = Representation::new("image/png", vec![0x89, b'P', b'N', b'G']);
+        assert_eq!(
+            png.to_html_image(),
+            Representation::new(
+                HTML_MIME_TYPE,
+                br#"<img src="data:image/png;base64,iVBORw==">"#.to_vec()
+            )
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use crate::domain::color::{Color, Rgba};
+use crate::domain::distance::{AlphaRamp, Tolerance};
+
+/// A background color to remove, with an optional tolerance of its own.
+///
+/// Targets without a tolerance use the shared matching settings (the global
+/// tolerance, or the alpha ramp if soft edges are configured).
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::color::Color;
+/// use bgclipper::domain::distance::{DistanceMetric, Tolerance};
+/// use bgclipper::domain::target::TargetColor;
+///
+/// let white = TargetColor::new(Color::new(255, 255, 255));
+/// let off_white = TargetColor::with_tolerance(
+///     Color::new(250, 250, 250),
+///     Tolerance::new(DistanceMetric::ChannelMax, 2.0).unwrap(),
+/// );
+/// assert!(white.tolerance.is_none());
+/// assert!(off_white.tolerance.is_some());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetColor {
+    /// The color to remove.
+    pub color: Color,
+    /// Per-color tolerance overriding the shared matching settings.
+    pub tolerance: Option<Tolerance>,
+    /// Alpha the background is expected to have. Only
+    /// [`AlphaMatching::Premultiplied`] takes it into account.
+    pub alpha: u8,
+}
+
+impl TargetColor {
+    /// Creates a target that uses the shared matching settings.
+    pub fn new(color: Color) -> Self {
+        Self {
+            color,
+            tolerance: None,
+            alpha: u8::MAX,
+        }
+    }
+
+    /// Creates a target with its own tolerance.
+    pub fn with_tolerance(color: Color, tolerance: Tolerance) -> Self {
+        Self {
+            color,
+            tolerance: Some(tolerance),
+            alpha: u8::MAX,
+        }
+    }
+
+    /// Returns the target color and alpha as one value.
+    pub fn rgba(&self) -> Rgba {
+        Rgba::new(self.color.r(), self.color.g(), self.color.b(), self.alpha)
+    }
+
+    /// Returns how much of `color` to keep when removing this target.
+    ///
+    /// A per-color tolerance matches all-or-nothing. Otherwise `ramp` is used
+    /// if given, falling back to `tolerance`. The result ranges from 0 (fully
+    /// transparent) to 1 (unchanged).
+    pub fn opacity(&self, color: &Color, tolerance: &Tolerance, ramp: Option<&AlphaRamp>) -> f64 {
+        match (&self.tolerance, ramp) {
+            (Some(own), _) => binary_opacity(own.matches(&self.color, color)),
+            (None, Some(ramp)) => ramp.opacity(&self.color, color),
+            (None, None) => binary_opacity(tolerance.matches(&self.color, color)),
+        }
+    }
+}
+
+impl Default for TargetColor {
+    /// Returns the default color (white) with the shared matching settings.
+    fn default() -> Self {
+        Self::new(Color::default())
+    }
+}
+
+impl From<Color> for TargetColor {
+    fn from(color: Color) -> Self {
+        Self::new(color)
+    }
+}
+
+impl From<Rgba> for TargetColor {
+    fn from(rgba: Rgba) -> Self {
+        Self {
+            alpha: rgba.alpha(),
+            ..Self::new(rgba.rgb())
+        }
+    }
+}
+
+/// How a pixel's existing alpha affects matching.
+///
+/// Images copied from browsers are often already partially transparent;
+/// this decides whether such pixels are background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AlphaMatching {
+    /// Match on color alone; a match scales down whatever alpha the pixel has.
+    #[default]
+    Ignore,
+    /// Only fully opaque pixels can match; partially transparent pixels are
+    /// kept as they are.
+    OpaqueOnly,
+    /// Compare colors premultiplied by alpha, so a partially transparent
+    /// pixel matches a target of the same color and alpha.
+    Premultiplied,
+}
+
+/// Returns how much of `color` to keep when removing all of `targets`.
+///
+/// This is the lowest opacity any single target assigns, so a pixel is as
+/// transparent as its closest target makes it.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::color::Color;
+/// use bgclipper::domain::distance::Tolerance;
+/// use bgclipper::domain::target::{TargetColor, combined_opacity};
+///
+/// let targets = [
+///     TargetColor::new(Color::new(255, 255, 255)),
+///     TargetColor::new(Color::new(250, 250, 250)),
+/// ];
+/// let exact = Tolerance::exact();
+/// assert_eq!(combined_opacity(&targets, &Color::new(250, 250, 250), &exact, None), 0.0);
+/// assert_eq!(combined_opacity(&targets, &Color::new(0, 0, 0), &exact, None), 1.0);
+/// ```
+pub fn combined_opacity(
+    targets: &[TargetColor],
+    color: &Color,
+    tolerance: &Tolerance,
+    ramp: Option<&AlphaRamp>,
+) -> f64 {
+    targets
+        .iter()
+        .map(|target| target.opacity(color, tolerance, ramp))
+        .fold(1.0, f64::min)
+}
+
+/// Returns how much of `pixel` to keep when removing all of `targets`,
+/// taking the pixel's alpha into account per `matching`.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::color::{Color, Rgba};
+/// use bgclipper::domain::distance::Tolerance;
+/// use bgclipper::domain::target::{AlphaMatching, TargetColor, pixel_opacity};
+///
+/// let targets = [TargetColor::new(Color::new(255, 255, 255))];
+/// let translucent_white = Rgba::new(255, 255, 255, 128);
+/// let opacity = |matching| {
+///     pixel_opacity(&targets, &translucent_white, matching, &Tolerance::exact(), None)
+/// };
+/// assert_eq!(opacity(AlphaMatching::Ignore), 0.0);
+/// assert_eq!(opacity(AlphaMatching::OpaqueOnly), 1.0);
+/// ```
+pub fn pixel_opacity(
+    targets: &[TargetColor],
+    pixel: &Rgba,
+    matching: AlphaMatching,
+    tolerance: &Tolerance,
+    ramp: Option<&AlphaRamp>,
+) -> f64 {
+    match matching {
+        AlphaMatching::Ignore => combined_opacity(targets, &pixel.rgb(), tolerance, ramp),
+        AlphaMatching::OpaqueOnly if !pixel.is_opaque() => 1.0,
+        AlphaMatching::OpaqueOnly => combined_opacity(targets, &pixel.rgb(), tolerance, ramp),
+        AlphaMatching::Premultiplied => {
+            let color = pixel.premultiplied();
+            targets
+                .iter()
+                .map(|target| {
+                    let premultiplied = TargetColor {
+                        color: target.rgba().premultiplied(),
+                        ..*target
+                    };
+                    premultiplied.opacity(&color, tolerance, ramp)
+                })
+                .fold(1.0, f64::min)
+        }
+    }
+}
+
+fn binary_opacity(matched: bool) -> f64 {
+    if matched { 0.0 } else { 1.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::distance::DistanceMetric;
+
+    fn white() -> Color {
+        Color::new(255, 255, 255)
+    }
+
+    fn off_white() -> Color {
+        Color::new(250, 250, 250)
+    }
+
+    #[test]
+    fn own_tolerance_overrides_shared_settings() {
+        let target = TargetColor::with_tolerance(
+            white(),
+            Tolerance::new(DistanceMetric::ChannelMax, 5.0).unwrap(),
+        );
+        let ramp = AlphaRamp::new(DistanceMetric::ChannelMax, 0.0, 100.0).unwrap();
+
+        assert_eq!(
+            target.opacity(&off_white(), &Tolerance::exact(), Some(&ramp)),
+            0.0
+        );
+        assert_eq!(
+            target.opacity(&Color::new(240, 240, 240), &Tolerance::exact(), Some(&ramp)),
+            1.0
+        );
+    }
+
+    #[test]
+    fn shared_ramp_used_without_own_tolerance() {
+        let target = TargetColor::new(white());
+        let ramp = AlphaRamp::new(DistanceMetric::ChannelMax, 0.0, 10.0).unwrap();
+
+        assert_eq!(
+            target.opacity(&off_white(), &Tolerance::exact(), Some(&ramp)),
+            0.5
+        );
+    }
+
+    #[test]
+    fn shared_tolerance_used_without_ramp() {
+        let target = TargetColor::new(white());
+        let tolerance = Tolerance::new(DistanceMetric::ChannelMax, 5.0).unwrap();
+
+        assert_eq!(target.opacity(&off_white(), &tolerance, None), 0.0);
+        assert_eq!(target.opacity(&off_white(), &Tolerance::exact(), None), 1.0);
+    }
+
+    #[test]
+    fn combined_opacity_takes_closest_target() {
+        let ramp = AlphaRamp::new(DistanceMetric::ChannelMax, 0.0, 10.0).unwrap();
+        let targets = [TargetColor::new(white()), TargetColor::new(off_white())];
+
+        // 2 away from off-white, 7 away from white
+        let color = Color::new(248, 248, 248);
+        let opacity = combined_opacity(&targets, &color, &Tolerance::exact(), Some(&ramp));
+        assert!((opacity - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn opaque_only_skips_translucent_pixels() {
+        let targets = [TargetColor::new(white())];
+        let exact = Tolerance::exact();
+        let opacity =
+            |pixel: Rgba| pixel_opacity(&targets, &pixel, AlphaMatching::OpaqueOnly, &exact, None);
+
+        assert_eq!(opacity(Rgba::new(255, 255, 255, 255)), 0.0);
+        assert_eq!(opacity(Rgba::new(255, 255, 255, 254)), 1.0);
+    }
+
+    #[test]
+    fn premultiplied_matches_target_alpha() {
+        let translucent = TargetColor::from(Rgba::new(255, 255, 255, 128));
+        let opaque = TargetColor::new(white());
+        let exact = Tolerance::exact();
+        let opacity = |target: TargetColor, pixel: Rgba| {
+            pixel_opacity(
+                &[target],
+                &pixel,
+                AlphaMatching::Premultiplied,
+                &exact,
+                None,
+            )
+        };
+
+        assert_eq!(opacity(translucent, Rgba::new(255, 255, 255, 128)), 0.0);
+        assert_eq!(opacity(translucent, Rgba::new(255, 255, 255, 255)), 1.0);
+        assert_eq!(opacity(opaque, Rgba::new(255, 255, 255, 128)), 1.0);
+        assert_eq!(opacity(opaque, Rgba::new(255, 255, 255, 255)), 0.0);
+    }
+
+    #[test]
+    fn premultiplied_keeps_per_color_tolerance() {
+        let target = TargetColor::with_tolerance(
+            white(),
+            Tolerance::new(DistanceMetric::ChannelMax, 4.0).unwrap(),
+        );
+        let opacity = pixel_opacity(
+            &[target],
+            &Rgba::new(255, 255, 255, 252),
+            AlphaMatching::Premultiplied,
+            &Tolerance::exact(),
+            None,
+        );
+        assert_eq!(opacity, 0.0);
+    }
+
+    #[test]
+    fn combined_opacity_of_no_targets_keeps_everything() {
+        assert_eq!(
+            combined_opacity(&[], &white(), &Tolerance::exact(), None),
+            1.0
+        );
+    }
+}
@@ -1,8 +1,34 @@
 use std::borrow::Cow;
+#[cfg(target_os = "linux")]
+use std::sync::{Arc, Mutex};
+#[cfg(target_os = "linux")]
+use std::time::Duration;
 
 use arboard::Clipboard;
+#[cfg(target_os = "linux")]
+use log::warn;
 
+#[cfg(target_os = "macos")]
+use crate::domain::port::PNG_MIME_TYPE;
 use crate::domain::port::{ClipboardPort, ImageData};
+#[cfg(target_os = "macos")]
+use crate::domain::representation::HTML_MIME_TYPE;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use crate::domain::representation::Representation;
+#[cfg(target_os = "linux")]
+use crate::infrastructure::selection_monitor::SelectionMonitor;
+#[cfg(target_os = "linux")]
+use crate::infrastructure::selection_owner;
+#[cfg(target_os = "linux")]
+use crate::infrastructure::watcher::SelectionWatcher;
+use crate::infrastructure::watcher::{PlatformWatcher, PollingWatcher};
+
+/// How long `set_image` waits for its own selection-change event on Linux.
+///
+/// Events are delivered asynchronously; without waiting, the caller could read
+/// the counter before our own write is counted and re-process the result.
+#[cfg(target_os = "linux")]
+const OWN_WRITE_TIMEOUT: Duration = Duration::from_millis(250);
 
 /// Errors that can occur during clipboard operations.
 #[derive(Debug)]
@@ -47,6 +73,67 @@ fn platform_change_count() -> Result<u64, ClipboardError> {
     Ok(count as u64)
 }
 
+/// Reads the macOS pasteboard's PNG data, if it holds any.
+#[cfg(target_os = "macos")]
+fn platform_get_png() -> Option<Vec<u8>> {
+    use objc2_app_kit::{NSPasteboard, NSPasteboardTypePNG};
+
+    let pasteboard = NSPasteboard::generalPasteboard();
+    // SAFETY: NSPasteboardTypePNG is an immutable framework constant
+    let data = pasteboard.dataForType(unsafe { NSPasteboardTypePNG })?;
+    Some(data.to_vec())
+}
+
+/// Replaces the macOS pasteboard contents with the PNG and HTML
+/// representations, and a TIFF converted from the PNG for applications that
+/// only paste bitmaps.
+///
+/// Returns `false` without touching the pasteboard if there is no PNG
+/// representation or it cannot be converted.
+#[cfg(target_os = "macos")]
+fn platform_set_representations(representations: &[Representation]) -> bool {
+    use objc2_app_kit::{
+        NSBitmapImageRep, NSPasteboard, NSPasteboardTypeHTML, NSPasteboardTypePNG,
+        NSPasteboardTypeTIFF,
+    };
+    use objc2_foundation::NSData;
+
+    let Some(png) = representations
+        .iter()
+        .find(|r| r.mime_type == PNG_MIME_TYPE)
+    else {
+        return false;
+    };
+    let png = NSData::with_bytes(&png.data);
+    let Some(tiff) =
+        NSBitmapImageRep::imageRepWithData(&png).and_then(|rep| rep.TIFFRepresentation())
+    else {
+        return false;
+    };
+
+    // SAFETY: the pasteboard type names are immutable framework constants
+    let (png_type, tiff_type, html_type) = unsafe {
+        (
+            NSPasteboardTypePNG,
+            NSPasteboardTypeTIFF,
+            NSPasteboardTypeHTML,
+        )
+    };
+    let mut entries = vec![(png_type, png), (tiff_type, tiff)];
+    entries.extend(
+        representations
+            .iter()
+            .filter(|r| r.mime_type == HTML_MIME_TYPE)
+            .map(|r| (html_type, NSData::with_bytes(&r.data))),
+    );
+
+    let pasteboard = NSPasteboard::generalPasteboard();
+    pasteboard.clearContents();
+    entries
+        .iter()
+        .all(|(pasteboard_type, data)| pasteboard.setData_forType(Some(data), pasteboard_type))
+}
+
 #[cfg(target_os = "windows")]
 fn platform_change_count() -> Result<u64, ClipboardError> {
     // TODO: implement using GetClipboardSequenceNumber
@@ -57,14 +144,104 @@ fn platform_change_count() -> Result<u64, ClipboardError> {
 
 /// Clipboard provider backed by the `arboard` crate.
 ///
-/// Provides cross-platform clipboard image access for macOS and Windows.
-#[derive(Debug)]
-pub struct ArboardClipboardProvider;
+/// Provides cross-platform clipboard image access for macOS, Windows and Linux.
+///
+/// On Linux, the clipboard is owned by the process that set it, so the
+/// provider keeps one `arboard::Clipboard` alive to keep serving written
+/// images, and tracks changes with a lazily started [`SelectionMonitor`].
+///
+/// On macOS, PNG data is also read directly
+/// ([`get_encoded`](ClipboardPort::get_encoded)), keeping the DPI and color
+/// profile of screenshots; elsewhere only raw pixels are read.
+///
+/// [`set_representations`](ClipboardPort::set_representations) offers every
+/// representation at once on macOS (PNG, TIFF and HTML) and X11; on Wayland
+/// and Windows only the image is written.
+pub struct ArboardClipboardProvider {
+    #[cfg(target_os = "linux")]
+    clipboard: Mutex<Option<Clipboard>>,
+    #[cfg(target_os = "linux")]
+    monitor: Mutex<Option<Arc<SelectionMonitor>>>,
+}
 
 impl ArboardClipboardProvider {
     /// Creates a new provider.
     pub fn new() -> Self {
-        Self
+        Self {
+            #[cfg(target_os = "linux")]
+            clipboard: Mutex::new(None),
+            #[cfg(target_os = "linux")]
+            monitor: Mutex::new(None),
+        }
+    }
+
+    /// Runs `f` with an `arboard::Clipboard` handle.
+    #[cfg(not(target_os = "linux"))]
+    fn with_clipboard<T>(
+        &self,
+        f: impl FnOnce(&mut Clipboard) -> Result<T, arboard::Error>,
+    ) -> Result<T, arboard::Error> {
+        f(&mut Clipboard::new()?)
+    }
+
+    /// Runs `f` with the provider's long-lived `arboard::Clipboard` handle.
+    ///
+    /// Dropping the last handle on X11 gives up selection ownership, which
+    /// would both lose the written image and count as a clipboard change.
+    #[cfg(target_os = "linux")]
+    fn with_clipboard<T>(
+        &self,
+        f: impl FnOnce(&mut Clipboard) -> Result<T, arboard::Error>,
+    ) -> Result<T, arboard::Error> {
+        let mut guard = self.clipboard.lock().unwrap_or_else(|e| e.into_inner());
+        let clipboard = match guard.as_mut() {
+            Some(clipboard) => clipboard,
+            None => guard.insert(Clipboard::new()?),
+        };
+        f(clipboard)
+    }
+
+    /// Returns a watcher for clipboard changes.
+    ///
+    /// On Linux this shares the provider's selection monitor, so changes are
+    /// pushed as they happen; if no display backend is available, or on other
+    /// platforms, it falls back to polling.
+    pub fn watcher(&self) -> PlatformWatcher {
+        #[cfg(target_os = "linux")]
+        match self.shared_monitor() {
+            Ok(monitor) => return PlatformWatcher::Selection(SelectionWatcher::new(monitor)),
+            Err(e) => log::warn!("falling back to clipboard polling: {e}"),
+        }
+        PlatformWatcher::Polling(PollingWatcher::default())
+    }
+
+    /// Returns the selection monitor, starting it on first use.
+    #[cfg(target_os = "linux")]
+    fn shared_monitor(&self) -> Result<Arc<SelectionMonitor>, ClipboardError> {
+        let mut guard = self.monitor.lock().unwrap_or_else(|e| e.into_inner());
+        let monitor = match guard.as_ref() {
+            Some(monitor) => monitor,
+            None => guard.insert(Arc::new(
+                SelectionMonitor::start().map_err(ClipboardError::ChangeCount)?,
+            )),
+        };
+        Ok(Arc::clone(monitor))
+    }
+
+    /// Runs `f` with the selection monitor, starting it on first use.
+    #[cfg(target_os = "linux")]
+    fn with_monitor<T>(
+        &self,
+        f: impl FnOnce(&SelectionMonitor) -> Result<T, String>,
+    ) -> Result<T, ClipboardError> {
+        f(&*self.shared_monitor()?).map_err(ClipboardError::ChangeCount)
+    }
+}
+
+impl std::fmt::Debug for ArboardClipboardProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArboardClipboardProvider")
+            .finish_non_exhaustive()
     }
 }
 
@@ -77,13 +254,18 @@ impl Default for ArboardClipboardProvider {
 impl ClipboardPort for ArboardClipboardProvider {
     type Error = ClipboardError;
 
+    #[cfg(not(target_os = "linux"))]
     fn change_count(&self) -> Result<u64, Self::Error> {
         platform_change_count()
     }
 
+    #[cfg(target_os = "linux")]
+    fn change_count(&self) -> Result<u64, Self::Error> {
+        self.with_monitor(SelectionMonitor::change_count)
+    }
+
     fn get_image(&self) -> Result<Option<ImageData>, Self::Error> {
-        let mut clipboard = Clipboard::new()?;
-        match clipboard.get_image() {
+        match self.with_clipboard(|clipboard| clipboard.get_image()) {
             Ok(img) => Ok(Some(ImageData {
                 pixels: img.bytes.into_owned(),
                 width: img.width as u32,
@@ -95,13 +277,61 @@ impl ClipboardPort for ArboardClipboardProvider {
     }
 
     fn set_image(&self, image: &ImageData) -> Result<(), Self::Error> {
-        let mut clipboard = Clipboard::new()?;
+        #[cfg(target_os = "linux")]
+        let before = self.change_count()?;
+
         let img = arboard::ImageData {
             width: image.width as usize,
             height: image.height as usize,
             bytes: Cow::Borrowed(&image.pixels),
         };
-        clipboard.set_image(img)?;
+        self.with_clipboard(|clipboard| clipboard.set_image(img))?;
+
+        #[cfg(target_os = "linux")]
+        self.with_monitor(|monitor| Ok(monitor.wait_for_change(before, OWN_WRITE_TIMEOUT)))?;
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    fn get_encoded(&self, mime_type: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(if mime_type == PNG_MIME_TYPE {
+            platform_get_png()
+        } else {
+            None
+        })
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set_representations(
+        &self,
+        image: &ImageData,
+        representations: &[Representation],
+    ) -> Result<(), Self::Error> {
+        if platform_set_representations(representations) {
+            return Ok(());
+        }
+        self.set_image(image)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_representations(
+        &self,
+        image: &ImageData,
+        representations: &[Representation],
+    ) -> Result<(), Self::Error> {
+        // arboard writes one format per call; only X11 can be served directly
+        if representations.is_empty() || std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            return self.set_image(image);
+        }
+
+        let before = self.change_count()?;
+        if let Err(e) = selection_owner::serve(representations.to_vec()) {
+            warn!("cannot offer multiple clipboard formats, writing the image only: {e}");
+            return self.set_image(image);
+        }
+        self.with_monitor(|monitor| Ok(monitor.wait_for_change(before, OWN_WRITE_TIMEOUT)))?;
+
         Ok(())
     }
 }
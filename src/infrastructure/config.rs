@@ -1,255 +1,3263 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::fs;
-use std::io;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
 
-use log::debug;
+use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
+use toml_edit::{ArrayOfTables, Document, Item, RawString, Table, TableLike, Value};
 
-use crate::domain::color::Color;
+use crate::domain::background::AutoDetect;
+use crate::domain::color::{Color, Rgba};
+use crate::domain::crop::Crop;
+use crate::domain::distance::{AlphaRamp, DistanceMetric, Tolerance};
+use crate::domain::flood_fill::{Connectivity, FloodFill, FloodSeeds, RemovalMode};
+use crate::domain::image_processor::PostProcessing;
 use crate::domain::port::ConfigPort;
+use crate::domain::profile::Profile;
+use crate::domain::target::{AlphaMatching, TargetColor};
+use crate::infrastructure::watcher::FileWatcher;
 
-/// Serializable configuration for the target color.
-#[derive(Debug, Serialize, Deserialize)]
+/// Version of the config file schema written by this build.
+///
+/// Files without a `version` key are version 1, the format that only had
+/// `[target_color]`. Older files are upgraded when they are read.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Serializable configuration file.
+///
+/// The top-level settings apply while no profile is active.
+///
+/// Read with [`parse_config`] rather than deserialized directly, because
+/// `#[serde(flatten)]` would lose the position of errors in the top-level
+/// settings.
+#[derive(Debug, Serialize)]
 struct ConfigFile {
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    active_profile: Option<String>,
+    #[serde(flatten)]
+    settings: SettingsConfig,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    profiles: BTreeMap<String, SettingsConfig>,
+}
+
+impl Default for ConfigFile {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            active_profile: None,
+            settings: SettingsConfig::default(),
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+impl ConfigFile {
+    /// Returns the settings of the active profile, or the top-level settings.
+    fn active_settings(&self) -> Result<&SettingsConfig, ConfigError> {
+        match &self.active_profile {
+            Some(name) => self.profiles.get(name).ok_or_else(|| unknown_profile(name)),
+            None => Ok(&self.settings),
+        }
+    }
+
+    /// Returns the settings of the active profile, or the top-level settings.
+    fn active_settings_mut(&mut self) -> Result<&mut SettingsConfig, ConfigError> {
+        match &self.active_profile {
+            Some(name) => self
+                .profiles
+                .get_mut(name)
+                .ok_or_else(|| unknown_profile(name)),
+            None => Ok(&mut self.settings),
+        }
+    }
+
+    /// Checks all settings, including those of inactive profiles, so a
+    /// mistake is reported when the file is loaded rather than on switching.
+    fn validate(&self) -> Result<(), ConfigError> {
+        self.settings.to_profile()?;
+        for (name, settings) in &self.profiles {
+            settings.to_profile().map_err(|e| match e {
+                ConfigError::Invalid(message) => {
+                    ConfigError::Invalid(format!("profiles.{name}: {message}"))
+                }
+                e => e,
+            })?;
+        }
+        self.active_settings().map(|_| ())
+    }
+}
+
+/// The keys of [`ConfigFile`] besides the top-level settings.
+#[derive(Deserialize)]
+struct ConfigHeader {
+    /// Files from before the key was added are version 1.
+    #[serde(default = "first_version")]
+    version: u32,
+    #[serde(default)]
+    active_profile: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, SettingsConfig>,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+fn unknown_profile(name: &str) -> ConfigError {
+    ConfigError::Invalid(format!("profile \"{name}\" is not defined"))
+}
+
+/// Processing settings, either at the top level or in a `[profiles.<name>]` table.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SettingsConfig {
+    #[serde(default)]
+    mode: ModeConfig,
+    #[serde(default)]
     target_color: ColorConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target_colors: Option<Vec<TargetColorConfig>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    auto_detect: Option<AutoDetectConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tolerance: Option<ToleranceConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alpha_matching: Option<AlphaMatchingConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    post_processing: Option<PostProcessingConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    flood: Option<FloodConfig>,
+}
+
+impl SettingsConfig {
+    fn target_colors(&self) -> Result<Vec<TargetColor>, ConfigError> {
+        match &self.target_colors {
+            Some(targets) if targets.is_empty() => Err(ConfigError::Invalid(
+                "target_colors must list at least one color".to_string(),
+            )),
+            Some(targets) => targets.iter().map(TargetColor::try_from).collect(),
+            None => Ok(vec![TargetColor::from(Rgba::try_from(&self.target_color)?)]),
+        }
+    }
+
+    fn set_target_colors(&mut self, targets: &[TargetColor]) {
+        // Keep the single-color key in sync for readers of the old format
+        if let Some(first) = targets.first() {
+            self.target_color = self.target_color.with_color(&first.rgba());
+        }
+        self.target_colors = Some(targets.iter().map(TargetColorConfig::from).collect());
+    }
+
+    fn auto_detect(&self) -> Result<Option<AutoDetect>, ConfigError> {
+        self.auto_detect
+            .as_ref()
+            .map(AutoDetect::try_from)
+            .transpose()
+    }
+
+    fn set_auto_detect(&mut self, auto: Option<&AutoDetect>) {
+        self.auto_detect = auto.map(|auto| AutoDetectConfig {
+            min_confidence: auto.min_confidence(),
+        });
+    }
+
+    fn tolerance(&self) -> Result<Tolerance, ConfigError> {
+        match &self.tolerance {
+            Some(tolerance) => Tolerance::try_from(tolerance),
+            None => Ok(Tolerance::exact()),
+        }
+    }
+
+    fn set_tolerance(&mut self, tolerance: &Tolerance) {
+        self.tolerance = (!tolerance.is_exact()).then(|| ToleranceConfig {
+            metric: tolerance.metric().into(),
+            threshold: tolerance.threshold(),
+            outer_threshold: None,
+        });
+    }
+
+    fn alpha_ramp(&self) -> Result<Option<AlphaRamp>, ConfigError> {
+        match &self.tolerance {
+            Some(tolerance) => tolerance.alpha_ramp(),
+            None => Ok(None),
+        }
+    }
+
+    fn set_alpha_ramp(&mut self, ramp: Option<&AlphaRamp>) {
+        match ramp {
+            Some(ramp) => {
+                self.tolerance = Some(ToleranceConfig {
+                    metric: ramp.metric().into(),
+                    threshold: ramp.inner(),
+                    outer_threshold: Some(ramp.outer()),
+                });
+            }
+            None => {
+                if let Some(tolerance) = self.tolerance.as_mut() {
+                    tolerance.outer_threshold = None;
+                }
+            }
+        }
+    }
+
+    fn alpha_matching(&self) -> AlphaMatching {
+        self.alpha_matching
+            .map(AlphaMatching::from)
+            .unwrap_or_default()
+    }
+
+    fn set_alpha_matching(&mut self, matching: AlphaMatching) {
+        self.alpha_matching =
+            (matching != AlphaMatching::default()).then(|| AlphaMatchingConfig::from(matching));
+    }
+
+    fn post_processing(&self) -> PostProcessing {
+        self.post_processing
+            .as_ref()
+            .map(PostProcessing::from)
+            .unwrap_or_default()
+    }
+
+    fn set_post_processing(&mut self, post: &PostProcessing) {
+        self.post_processing = Some(PostProcessingConfig::from(post));
+    }
+
+    fn removal_mode(&self) -> Result<RemovalMode, ConfigError> {
+        match self.mode {
+            ModeConfig::Global => Ok(RemovalMode::Global),
+            ModeConfig::Flood => {
+                let flood = self.flood.as_ref().map_or_else(
+                    || FloodFill::try_from(&FloodConfig::default()),
+                    FloodFill::try_from,
+                )?;
+                Ok(RemovalMode::Flood(flood))
+            }
+        }
+    }
+
+    fn set_removal_mode(&mut self, mode: &RemovalMode) {
+        match mode {
+            RemovalMode::Global => self.mode = ModeConfig::Global,
+            RemovalMode::Flood(flood) => {
+                self.mode = ModeConfig::Flood;
+                self.flood = Some(FloodConfig::from(flood));
+            }
+        }
+    }
+
+    fn to_profile(&self) -> Result<Profile, ConfigError> {
+        Ok(Profile {
+            targets: self.target_colors()?,
+            auto_detect: self.auto_detect()?,
+            tolerance: self.tolerance()?,
+            alpha_ramp: self.alpha_ramp()?,
+            alpha_matching: self.alpha_matching(),
+            mode: self.removal_mode()?,
+            post_processing: self.post_processing(),
+        })
+    }
+
+    fn from_profile(profile: &Profile) -> Self {
+        let mut settings = Self::default();
+        settings.set_profile(profile);
+        settings
+    }
+
+    /// Replaces every setting with those of `profile`, keeping the format of
+    /// the target color.
+    fn set_profile(&mut self, profile: &Profile) {
+        self.set_target_colors(&profile.targets);
+        if profile.targets.len() == 1 && profile.targets[0].tolerance.is_none() {
+            // A single plain color round-trips through the simpler format
+            self.target_colors = None;
+        }
+        self.set_auto_detect(profile.auto_detect.as_ref());
+        self.set_tolerance(&profile.tolerance);
+        if profile.alpha_ramp.is_some() {
+            self.set_alpha_ramp(profile.alpha_ramp.as_ref());
+        }
+        self.set_alpha_matching(profile.alpha_matching);
+        self.set_removal_mode(&profile.mode);
+        if profile.post_processing == PostProcessing::default() {
+            self.post_processing = None;
+        } else {
+            self.set_post_processing(&profile.post_processing);
+        }
+    }
+}
+
+/// Color value in the TOML config file.
+///
+/// Either a string in any format [`Rgba`] parses (`target_color = "#e7feb6"`)
+/// or a table of channels (`[target_color]` with `r`, `g`, `b` and an
+/// optional alpha `a`).
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum ColorConfig {
+    Text(String),
+    Rgb {
+        r: u8,
+        g: u8,
+        b: u8,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        a: Option<u8>,
+    },
+}
+
+impl ColorConfig {
+    /// Returns `color` in the same form as `self`, written as hex for strings.
+    fn with_color(&self, color: &Rgba) -> Self {
+        match self {
+            ColorConfig::Text(_) => ColorConfig::Text(hex(color)),
+            ColorConfig::Rgb { .. } => ColorConfig::from(color),
+        }
+    }
+}
+
+/// Writes `color` as `#rrggbb`, or `#rrggbbaa` if it is not opaque.
+fn hex(color: &Rgba) -> String {
+    let rgb = color.rgb();
+    let mut hex = format!("#{:02x}{:02x}{:02x}", rgb.r(), rgb.g(), rgb.b());
+    if !color.is_opaque() {
+        hex.push_str(&format!("{:02x}", color.alpha()));
+    }
+    hex
+}
+
+impl From<&Rgba> for ColorConfig {
+    fn from(color: &Rgba) -> Self {
+        let rgb = color.rgb();
+        ColorConfig::Rgb {
+            r: rgb.r(),
+            g: rgb.g(),
+            b: rgb.b(),
+            a: (!color.is_opaque()).then_some(color.alpha()),
+        }
+    }
+}
+
+impl TryFrom<&ColorConfig> for Rgba {
+    type Error = ConfigError;
+
+    fn try_from(config: &ColorConfig) -> Result<Self, Self::Error> {
+        match config {
+            ColorConfig::Text(text) => text
+                .parse()
+                .map_err(|e| ConfigError::Invalid(format!("target_color: {e}"))),
+            ColorConfig::Rgb { r, g, b, a } => Ok(Rgba::new(*r, *g, *b, a.unwrap_or(u8::MAX))),
+        }
+    }
 }
 
-/// RGB color section in the TOML config file.
+impl Default for ColorConfig {
+    fn default() -> Self {
+        Self::from(&Rgba::from(Color::default()))
+    }
+}
+
+/// One entry of the `[[target_colors]]` array in the TOML config file.
+///
+/// `tolerance` is an inline table with the same keys as `[tolerance]`,
+/// except that soft edges (`outer_threshold`) are not supported per color.
 #[derive(Debug, Serialize, Deserialize)]
-struct ColorConfig {
+struct TargetColorConfig {
     r: u8,
     g: u8,
     b: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    a: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tolerance: Option<ToleranceConfig>,
+}
+
+impl TryFrom<&TargetColorConfig> for TargetColor {
+    type Error = ConfigError;
+
+    fn try_from(config: &TargetColorConfig) -> Result<Self, Self::Error> {
+        let color = Color::new(config.r, config.g, config.b);
+        let alpha = config.a.unwrap_or(u8::MAX);
+        let Some(tolerance) = &config.tolerance else {
+            return Ok(TargetColor {
+                alpha,
+                ..TargetColor::new(color)
+            });
+        };
+        if tolerance.outer_threshold.is_some() {
+            return Err(ConfigError::Invalid(format!(
+                "target color {color}: outer_threshold is only supported in [tolerance]"
+            )));
+        }
+        Ok(TargetColor {
+            alpha,
+            ..TargetColor::with_tolerance(color, Tolerance::try_from(tolerance)?)
+        })
+    }
+}
+
+impl From<&TargetColor> for TargetColorConfig {
+    fn from(target: &TargetColor) -> Self {
+        Self {
+            r: target.color.r(),
+            g: target.color.g(),
+            b: target.color.b(),
+            a: (target.alpha != u8::MAX).then_some(target.alpha),
+            tolerance: target.tolerance.map(|tolerance| ToleranceConfig {
+                metric: tolerance.metric().into(),
+                threshold: tolerance.threshold(),
+                outer_threshold: None,
+            }),
+        }
+    }
+}
+
+/// Automatic background detection section in the TOML config file.
+///
+/// Its presence turns auto-detection on; `[target_color]` is then ignored.
+#[derive(Debug, Serialize, Deserialize)]
+struct AutoDetectConfig {
+    #[serde(default = "default_min_confidence")]
+    min_confidence: f64,
+}
+
+fn default_min_confidence() -> f64 {
+    AutoDetect::DEFAULT_MIN_CONFIDENCE
+}
+
+impl TryFrom<&AutoDetectConfig> for AutoDetect {
+    type Error = ConfigError;
+
+    fn try_from(config: &AutoDetectConfig) -> Result<Self, Self::Error> {
+        AutoDetect::new(config.min_confidence).ok_or_else(|| {
+            ConfigError::Invalid(format!(
+                "min_confidence must be between 0 and 1, got {}",
+                config.min_confidence
+            ))
+        })
+    }
 }
 
-/// Errors that can occur during config file operations.
-#[derive(Debug)]
-pub enum ConfigError {
-    /// Failed to read or write the config file.
-    Io(io::Error),
-    /// Failed to parse the TOML content.
-    Parse(toml::de::Error),
-    /// Failed to serialize the config to TOML.
-    Serialize(toml::ser::Error),
-}
+/// Color-matching tolerance section in the TOML config file.
+///
+/// When `outer_threshold` is set, pixels between `threshold` and
+/// `outer_threshold` fade out along an alpha ramp instead of staying opaque.
+#[derive(Debug, Serialize, Deserialize)]
+struct ToleranceConfig {
+    #[serde(default)]
+    metric: MetricConfig,
+    threshold: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    outer_threshold: Option<f64>,
+}
+
+impl TryFrom<&ToleranceConfig> for Tolerance {
+    type Error = ConfigError;
+
+    fn try_from(config: &ToleranceConfig) -> Result<Self, Self::Error> {
+        Tolerance::new(config.metric.into(), config.threshold).ok_or_else(|| {
+            ConfigError::Invalid(format!(
+                "tolerance threshold must be a non-negative number, got {}",
+                config.threshold
+            ))
+        })
+    }
+}
+
+impl ToleranceConfig {
+    /// Builds the alpha ramp, or `None` if `outer_threshold` is not set.
+    fn alpha_ramp(&self) -> Result<Option<AlphaRamp>, ConfigError> {
+        let Some(outer) = self.outer_threshold else {
+            return Ok(None);
+        };
+        AlphaRamp::new(self.metric.into(), self.threshold, outer)
+            .map(Some)
+            .ok_or_else(|| {
+                ConfigError::Invalid(format!(
+                    "outer_threshold ({outer}) must be a number no smaller than threshold ({})",
+                    self.threshold
+                ))
+            })
+    }
+}
+
+/// Post-processing section in the TOML config file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PostProcessingConfig {
+    #[serde(default)]
+    decontaminate_edges: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    crop: Option<CropConfig>,
+}
+
+/// Crop-to-content settings in the TOML config file.
+///
+/// ```toml
+/// [post_processing.crop]
+/// padding = 8
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CropConfig {
+    #[serde(default)]
+    padding: u32,
+}
+
+impl From<&PostProcessingConfig> for PostProcessing {
+    fn from(config: &PostProcessingConfig) -> Self {
+        PostProcessing {
+            decontaminate_edges: config.decontaminate_edges,
+            crop: config.crop.as_ref().map(|crop| Crop {
+                padding: crop.padding,
+            }),
+        }
+    }
+}
+
+impl From<&PostProcessing> for PostProcessingConfig {
+    fn from(post: &PostProcessing) -> Self {
+        Self {
+            decontaminate_edges: post.decontaminate_edges,
+            crop: post.crop.map(|crop| CropConfig {
+                padding: crop.padding,
+            }),
+        }
+    }
+}
+
+/// Alpha matching rule names accepted in the TOML config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AlphaMatchingConfig {
+    Ignore,
+    OpaqueOnly,
+    Premultiplied,
+}
+
+impl From<AlphaMatchingConfig> for AlphaMatching {
+    fn from(config: AlphaMatchingConfig) -> Self {
+        match config {
+            AlphaMatchingConfig::Ignore => AlphaMatching::Ignore,
+            AlphaMatchingConfig::OpaqueOnly => AlphaMatching::OpaqueOnly,
+            AlphaMatchingConfig::Premultiplied => AlphaMatching::Premultiplied,
+        }
+    }
+}
+
+impl From<AlphaMatching> for AlphaMatchingConfig {
+    fn from(matching: AlphaMatching) -> Self {
+        match matching {
+            AlphaMatching::Ignore => AlphaMatchingConfig::Ignore,
+            AlphaMatching::OpaqueOnly => AlphaMatchingConfig::OpaqueOnly,
+            AlphaMatching::Premultiplied => AlphaMatchingConfig::Premultiplied,
+        }
+    }
+}
+
+/// Removal mode names accepted in the TOML config file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ModeConfig {
+    #[default]
+    Global,
+    Flood,
+}
+
+/// Flood-fill section in the TOML config file.
+///
+/// Without `seeds`, the fill starts from every border pixel.
+#[derive(Debug, Serialize, Deserialize)]
+struct FloodConfig {
+    #[serde(default = "default_connectivity")]
+    connectivity: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seeds: Option<Vec<(u32, u32)>>,
+}
+
+fn default_connectivity() -> u8 {
+    4
+}
+
+impl Default for FloodConfig {
+    fn default() -> Self {
+        Self {
+            connectivity: default_connectivity(),
+            seeds: None,
+        }
+    }
+}
+
+impl TryFrom<&FloodConfig> for FloodFill {
+    type Error = ConfigError;
+
+    fn try_from(config: &FloodConfig) -> Result<Self, Self::Error> {
+        let connectivity = match config.connectivity {
+            4 => Connectivity::Four,
+            8 => Connectivity::Eight,
+            n => {
+                return Err(ConfigError::Invalid(format!(
+                    "flood connectivity must be 4 or 8, got {n}"
+                )));
+            }
+        };
+        let seeds = match &config.seeds {
+            Some(points) => FloodSeeds::Points(points.clone()),
+            None => FloodSeeds::Border,
+        };
+        Ok(FloodFill {
+            seeds,
+            connectivity,
+        })
+    }
+}
+
+impl From<&FloodFill> for FloodConfig {
+    fn from(flood: &FloodFill) -> Self {
+        Self {
+            connectivity: match flood.connectivity {
+                Connectivity::Four => 4,
+                Connectivity::Eight => 8,
+            },
+            seeds: match &flood.seeds {
+                FloodSeeds::Border => None,
+                FloodSeeds::Points(points) => Some(points.clone()),
+            },
+        }
+    }
+}
+
+/// Distance metric names accepted in the TOML config file.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MetricConfig {
+    #[default]
+    Euclidean,
+    Cie76,
+    Ciede2000,
+    ChannelMax,
+}
+
+impl From<MetricConfig> for DistanceMetric {
+    fn from(config: MetricConfig) -> Self {
+        match config {
+            MetricConfig::Euclidean => DistanceMetric::EuclideanRgb,
+            MetricConfig::Cie76 => DistanceMetric::Cie76,
+            MetricConfig::Ciede2000 => DistanceMetric::Ciede2000,
+            MetricConfig::ChannelMax => DistanceMetric::ChannelMax,
+        }
+    }
+}
+
+impl From<DistanceMetric> for MetricConfig {
+    fn from(metric: DistanceMetric) -> Self {
+        match metric {
+            DistanceMetric::EuclideanRgb => MetricConfig::Euclidean,
+            DistanceMetric::Cie76 => MetricConfig::Cie76,
+            DistanceMetric::Ciede2000 => MetricConfig::Ciede2000,
+            DistanceMetric::ChannelMax => MetricConfig::ChannelMax,
+        }
+    }
+}
+
+/// Upgrades a config document by one version: entry `i` turns version
+/// `i + 1` into version `i + 2`. The `version` key is updated by [`migrate`].
+type Migration = fn(&mut Document);
+
+/// Upgrades from each older schema version, oldest first.
+const MIGRATIONS: [Migration; CONFIG_VERSION as usize - 1] = [migrate_v1];
+
+/// Version 1 files hold a single `[target_color]` table of channels, which
+/// version 2 writes as a hex string like the colors it saves. The comments
+/// above the table move to the key. A table with anything but valid
+/// channels is left as it is, for the parser to report.
+fn migrate_v1(document: &mut Document) {
+    let root = document.as_table_mut();
+    let Some(Item::Table(table)) = root.get("target_color") else {
+        return;
+    };
+    if table
+        .iter()
+        .any(|(key, _)| !["r", "g", "b", "a"].contains(&key))
+    {
+        return;
+    }
+    let channel = |name| {
+        let value = table.get(name).and_then(Item::as_integer);
+        value.and_then(|v| u8::try_from(v).ok())
+    };
+    let alpha = match table.get("a") {
+        Some(_) => channel("a"),
+        None => Some(u8::MAX),
+    };
+    let (Some(r), Some(g), Some(b), Some(a)) = (channel("r"), channel("g"), channel("b"), alpha)
+    else {
+        return;
+    };
+    let comments = table.decor().prefix().and_then(RawString::as_str);
+    let comments = comments.unwrap_or_default().to_string();
+
+    root.insert(
+        "target_color",
+        toml_edit::value(hex(&Rgba::new(r, g, b, a))),
+    );
+    if let Some(decor) = root.key_decor_mut("target_color") {
+        decor.set_prefix(comments);
+    }
+}
+
+/// Upgrades `document` to [`CONFIG_VERSION`].
+///
+/// Returns the version it had, or `None` if it was already current. Fails
+/// if the version is not a number or is newer than this build supports.
+fn migrate(document: &mut Document) -> Result<Option<u32>, ConfigError> {
+    let version = match document.get("version") {
+        None => 1,
+        Some(item) => match item.as_integer() {
+            Some(v) if (1..=i64::from(CONFIG_VERSION)).contains(&v) => v as u32,
+            Some(v) if v > i64::from(CONFIG_VERSION) => {
+                return Err(ConfigError::Invalid(format!(
+                    "config version {v} is newer than this bgclipper supports ({CONFIG_VERSION})"
+                )));
+            }
+            _ => {
+                return Err(ConfigError::Invalid(format!(
+                    "version must be a whole number from 1 to {CONFIG_VERSION}, got {}",
+                    item.to_string().trim()
+                )));
+            }
+        },
+    };
+    if version == CONFIG_VERSION {
+        return Ok(None);
+    }
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(document);
+    }
+    document.insert("version", toml_edit::value(i64::from(CONFIG_VERSION)));
+    // Keep the version first, above the settings
+    document
+        .as_table_mut()
+        .sort_values_by(|a, _, b, _| (a.get() != "version").cmp(&(b.get() != "version")));
+    Ok(Some(version))
+}
+
+/// Expected shape of part of the config file, to tell its keys from
+/// unknown ones.
+#[derive(Debug, Clone, Copy)]
+enum Schema {
+    /// A value whose contents are not checked.
+    Any,
+    /// A table with these keys; other values (such as a color string) are
+    /// not checked.
+    Table(&'static [(&'static str, Schema)]),
+    /// A table of entries under any name, all with the same shape.
+    Named(&'static Schema),
+    /// An array whose items all have the same shape.
+    Array(&'static Schema),
+    /// The top level: the settings, plus `version`, `active_profile` and
+    /// `[profiles]`.
+    File,
+}
+
+impl Schema {
+    /// Returns the shape of `key` in a table of this shape, or `None` if the
+    /// config file does not define it.
+    fn key(self, key: &str) -> Option<Schema> {
+        match self {
+            Schema::Table(keys) => keys
+                .iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, schema)| *schema),
+            Schema::Named(entry) => Some(*entry),
+            Schema::File => match key {
+                "version" | "active_profile" => Some(Schema::Any),
+                "profiles" => Some(Schema::Named(&SETTINGS_SCHEMA)),
+                _ => SETTINGS_SCHEMA.key(key),
+            },
+            Schema::Any | Schema::Array(_) => None,
+        }
+    }
+}
+
+const COLOR_KEYS: [(&str, Schema); 4] = [
+    ("r", Schema::Any),
+    ("g", Schema::Any),
+    ("b", Schema::Any),
+    ("a", Schema::Any),
+];
+
+const TOLERANCE_SCHEMA: Schema = Schema::Table(&[
+    ("metric", Schema::Any),
+    ("threshold", Schema::Any),
+    ("outer_threshold", Schema::Any),
+]);
+
+/// Keys of [`SettingsConfig`], at the top level or in a profile.
+const SETTINGS_SCHEMA: Schema = Schema::Table(&[
+    ("mode", Schema::Any),
+    ("target_color", Schema::Table(&COLOR_KEYS)),
+    (
+        "target_colors",
+        Schema::Array(&Schema::Table(&[
+            COLOR_KEYS[0],
+            COLOR_KEYS[1],
+            COLOR_KEYS[2],
+            COLOR_KEYS[3],
+            ("tolerance", TOLERANCE_SCHEMA),
+        ])),
+    ),
+    (
+        "auto_detect",
+        Schema::Table(&[("min_confidence", Schema::Any)]),
+    ),
+    ("tolerance", TOLERANCE_SCHEMA),
+    ("alpha_matching", Schema::Any),
+    (
+        "post_processing",
+        Schema::Table(&[
+            ("decontaminate_edges", Schema::Any),
+            ("crop", Schema::Table(&[("padding", Schema::Any)])),
+        ]),
+    ),
+    (
+        "flood",
+        Schema::Table(&[("connectivity", Schema::Any), ("seeds", Schema::Any)]),
+    ),
+]);
+
+/// Returns the dotted paths of the keys in `document` that the config file
+/// does not define, and which are therefore ignored.
+fn unknown_keys(document: &Document) -> Vec<String> {
+    let mut unknown = Vec::new();
+    collect_unknown_in(document.as_table(), Schema::File, "", &mut unknown);
+    unknown
+}
+
+/// Adds the keys within `item` that `schema` does not define to `unknown`.
+fn collect_unknown(item: &Item, schema: Schema, path: &str, unknown: &mut Vec<String>) {
+    match (schema, item) {
+        (Schema::Array(entry), Item::ArrayOfTables(tables)) => {
+            for (i, table) in tables.iter().enumerate() {
+                collect_unknown_in(table, *entry, &format!("{path}[{i}]"), unknown);
+            }
+        }
+        (Schema::Array(entry), Item::Value(Value::Array(values))) => {
+            for (i, value) in values.iter().enumerate() {
+                if let Some(table) = value.as_inline_table() {
+                    collect_unknown_in(table, *entry, &format!("{path}[{i}]"), unknown);
+                }
+            }
+        }
+        (Schema::Table(_) | Schema::Named(_) | Schema::File, _) => {
+            if let Some(table) = item.as_table_like() {
+                collect_unknown_in(table, schema, path, unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Adds the keys of `table`, which has the given `schema`, that the schema
+/// does not define to `unknown`.
+fn collect_unknown_in(
+    table: &dyn TableLike,
+    schema: Schema,
+    path: &str,
+    unknown: &mut Vec<String>,
+) {
+    for (key, item) in table.iter() {
+        let path = if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}.{key}")
+        };
+        match schema.key(key) {
+            Some(schema) => collect_unknown(item, schema, &path, unknown),
+            None => unknown.push(path),
+        }
+    }
+}
+
+/// Applies the changes from `old` to `new` to `table`, keeping the comments
+/// and layout of everything else.
+///
+/// `old` and `new` are the config before and after an update, as written by
+/// the serializer, so defaults the file leaves out stay out, and unknown keys
+/// stay in.
+fn merge(table: &mut dyn TableLike, old: &dyn TableLike, new: &dyn TableLike) {
+    let removed: Vec<String> = old
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !new.contains_key(key))
+        .collect();
+    for key in removed {
+        table.remove(&key);
+    }
+
+    for (key, new_item) in new.iter() {
+        let old_item = old.get(key);
+        if old_item.is_some_and(|old_item| old_item.to_string() == new_item.to_string()) {
+            continue;
+        }
+        match table.get_mut(key) {
+            Some(item) => merge_item(item, old_item, new_item),
+            None => {
+                table.insert(key, detach(new_item));
+            }
+        }
+    }
+}
+
+/// Updates `item`, which was `old`, to the value of `new`, keeping its
+/// formatting where they agree.
+fn merge_item(item: &mut Item, old: Option<&Item>, new: &Item) {
+    let empty = Table::new();
+    if let (Some(table), Some(new_table)) = (item.as_table_like_mut(), new.as_table_like()) {
+        let old_table = old.and_then(Item::as_table_like).unwrap_or(&empty);
+        merge(table, old_table, new_table);
+        return;
+    }
+    match (item, new) {
+        (Item::ArrayOfTables(tables), Item::ArrayOfTables(new_tables))
+            if tables.len() == new_tables.len() =>
+        {
+            let old_tables = old.and_then(Item::as_array_of_tables);
+            for (i, (table, new_table)) in tables.iter_mut().zip(new_tables.iter()).enumerate() {
+                let old_table = old_tables.and_then(|old| old.get(i)).unwrap_or(&empty);
+                merge(table, old_table, new_table);
+            }
+        }
+        (Item::Value(value), Item::Value(new_value)) => {
+            if !same_value(value, new_value) {
+                let decor = value.decor().clone();
+                *value = new_value.clone();
+                *value.decor_mut() = decor;
+            }
+        }
+        (item, new) => *item = detach(new),
+    }
+}
+
+/// Whether two TOML values are equal, however they are written.
+fn same_value(a: &Value, b: &Value) -> bool {
+    let parse = |value: &Value| format!("v = {value}").parse::<toml::Table>().ok();
+    parse(a).is_some_and(|a| Some(a) == parse(b))
+}
+
+/// Copies `item` from another document, dropping the positions of its
+/// tables so that they are placed after their neighbours.
+fn detach(item: &Item) -> Item {
+    fn detach_table(table: &Table) -> Table {
+        let mut copy = Table::new();
+        copy.set_implicit(table.is_implicit());
+        *copy.decor_mut() = table.decor().clone();
+        for (key, item) in table.iter() {
+            copy.insert(key, detach(item));
+        }
+        copy
+    }
+
+    match item {
+        Item::Table(table) => Item::Table(detach_table(table)),
+        Item::ArrayOfTables(tables) => {
+            let mut copy = ArrayOfTables::new();
+            for table in tables.iter() {
+                copy.push(detach_table(table));
+            }
+            Item::ArrayOfTables(copy)
+        }
+        item => item.clone(),
+    }
+}
+
+/// Replaces the file at `path` with `content`, so that a crash leaves
+/// either the old or the new file, never a partial one.
+///
+/// The content is written to a temporary file next to `path`, synced to
+/// disk and renamed over it. If `path` is a symlink, its target is replaced
+/// and the link kept. On Unix, the file is only accessible to its owner
+/// (`0600`), as are the directories created for it (`0700`).
+pub(crate) fn write_private(path: &Path, content: impl AsRef<[u8]>) -> io::Result<()> {
+    // Dotfile managers link the config into place; write where it points
+    let path = &fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    create_private_dir(dir)?;
+
+    let (temp, file) = create_temp(dir, path)?;
+    if let Err(e) = write_synced(file, content.as_ref()).and_then(|()| fs::rename(&temp, path)) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    // Make the rename itself durable; not every platform can sync a directory
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Creates a new owner-only file in `dir` to be renamed to `path`, under a
+/// name unique to this process and call.
+///
+/// The file must not exist yet, so a file or link left there by someone
+/// else is never written through.
+fn create_temp(dir: &Path, path: &Path) -> io::Result<(PathBuf, fs::File)> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    loop {
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let temp = dir.join(format!(".{file_name}.{}.{count}.tmp", std::process::id()));
+        match options.open(&temp) {
+            Ok(file) => return Ok((temp, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Writes `content` to `file` and syncs it to disk.
+fn write_synced(mut file: fs::File, content: &[u8]) -> io::Result<()> {
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+/// Creates `dir` and its missing parents, accessible only to the owner on
+/// Unix.
+///
+/// An existing directory that others may write to, and so replace the files
+/// in, is made owner-only. Fails if it cannot be, as it belongs to someone
+/// else.
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+        let mode = fs::metadata(dir)?.permissions().mode();
+        if mode & 0o022 != 0 {
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o700)).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!(
+                        "directory {dir:?} is writable by others (mode {mode:o}) and cannot be made private: {e}"
+                    ),
+                )
+            })?;
+            warn!("directory {dir:?} was writable by others (mode {mode:o}), made private");
+        }
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    fs::create_dir_all(dir)
+}
+
+/// A TOML syntax error, or a value of the wrong type, with where it is in
+/// the file.
+#[derive(Debug)]
+pub struct ParseError {
+    /// 1-based line and column, if the error points at a position.
+    location: Option<(usize, usize)>,
+    message: String,
+    error: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl ParseError {
+    fn new(
+        error: impl std::error::Error + Send + Sync + 'static,
+        message: &str,
+        span: Option<Range<usize>>,
+        content: &str,
+    ) -> Self {
+        let location = span.map(|span| {
+            let before = content.get(..span.start).unwrap_or(content);
+            let line = before.matches('\n').count() + 1;
+            let column = before
+                .rsplit('\n')
+                .next()
+                .map_or(0, |line| line.chars().count())
+                + 1;
+            (line, column)
+        });
+        Self {
+            location,
+            message: message.trim().to_string(),
+            error: Box::new(error),
+        }
+    }
+
+    /// Drops the location, for an error found in text other than the file.
+    fn unlocated(self) -> Self {
+        Self {
+            location: None,
+            ..self
+        }
+    }
+
+    /// Returns the 1-based line and column of the error, if known.
+    pub fn location(&self) -> Option<(usize, usize)> {
+        self.location
+    }
+
+    /// Returns the error message, without the location.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Parses TOML `content`, locating any error in it.
+fn parse<T: serde::de::DeserializeOwned>(content: &str) -> Result<T, ConfigError> {
+    toml::from_str(content).map_err(|e| {
+        let (message, span) = (e.message().to_string(), e.span());
+        ConfigError::Parse(ParseError::new(e, &message, span, content))
+    })
+}
+
+/// Parses TOML `content` as a [`ConfigFile`], locating any error in it.
+///
+/// The top-level settings are read on their own, as the rest of the file is
+/// ignored as unknown keys, so their errors keep a position too.
+fn parse_config(content: &str) -> Result<ConfigFile, ConfigError> {
+    let header: ConfigHeader = parse(content)?;
+    Ok(ConfigFile {
+        version: header.version,
+        active_profile: header.active_profile,
+        settings: parse(content)?,
+        profiles: header.profiles,
+    })
+}
+
+/// Parses TOML `content` as a document that keeps its formatting.
+fn parse_document(content: &str) -> Result<Document, ConfigError> {
+    content.parse().map_err(|e: toml_edit::TomlError| {
+        let (message, span) = (e.message().to_string(), e.span());
+        ConfigError::Parse(ParseError::new(e, &message, span, content))
+    })
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(f, "line {line}, column {column}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+/// Errors that can occur during config file operations.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Failed to read or write the config file.
+    Io(io::Error),
+    /// Failed to parse the TOML content.
+    Parse(ParseError),
+    /// Failed to serialize the config to TOML.
+    Serialize(toml::ser::Error),
+    /// The config parsed successfully but contains an invalid value.
+    Invalid(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "config I/O error: {e}"),
+            ConfigError::Parse(e) => write!(f, "config parse error: {e}"),
+            ConfigError::Serialize(e) => write!(f, "config serialize error: {e}"),
+            ConfigError::Invalid(e) => write!(f, "config validation error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Parse(e) => Some(e),
+            ConfigError::Serialize(e) => Some(e),
+            ConfigError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(e: toml::ser::Error) -> Self {
+        ConfigError::Serialize(e)
+    }
+}
+
+/// Config file locations following the XDG Base Directory specification,
+/// as used on Linux and the BSDs.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::{Path, PathBuf};
+///
+/// use bgclipper::infrastructure::config::XdgPaths;
+///
+/// let paths = XdgPaths::from_vars(
+///     |var| (var == "XDG_CONFIG_HOME").then(|| "/home/me/.dotfiles".into()),
+///     Some(Path::new("/home/me")),
+/// );
+/// assert_eq!(
+///     paths.user,
+///     Some(PathBuf::from("/home/me/.dotfiles/bgclipper/config.toml"))
+/// );
+/// assert_eq!(paths.system, vec![PathBuf::from("/etc/xdg/bgclipper/config.toml")]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XdgPaths {
+    /// The user's config file: `$XDG_CONFIG_HOME/bgclipper/config.toml`, or
+    /// `~/.config/bgclipper/config.toml` if the variable is unset. `None`
+    /// if neither is known.
+    pub user: Option<PathBuf>,
+    /// System-wide config files, most important first:
+    /// `bgclipper/config.toml` in each of `$XDG_CONFIG_DIRS`, or in
+    /// `/etc/xdg` if the variable is unset.
+    pub system: Vec<PathBuf>,
+}
+
+impl XdgPaths {
+    /// Reads the locations from the environment.
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var_os(name), dirs::home_dir().as_deref())
+    }
+
+    /// Reads the locations from the variables `var` returns, with `home` as
+    /// the user's home directory. As the specification requires, relative
+    /// paths are ignored.
+    pub fn from_vars(var: impl Fn(&str) -> Option<OsString>, home: Option<&Path>) -> Self {
+        let file = |dir: &Path| dir.join("bgclipper").join("config.toml");
+        let user = var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| home.map(|home| home.join(".config")))
+            .map(|dir| file(&dir));
+
+        let mut system: Vec<PathBuf> = var("XDG_CONFIG_DIRS")
+            .map(|dirs| {
+                std::env::split_paths(&dirs)
+                    .filter(|dir| dir.is_absolute())
+                    .map(|dir| file(&dir))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if system.is_empty() {
+            system.push(file(Path::new("/etc/xdg")));
+        }
+        Self { user, system }
+    }
+}
+
+/// TOML-based configuration provider.
+///
+/// Reads and writes the target color from a TOML config file.
+/// The config file path is platform-dependent:
+/// - Linux and other Unix systems: `$XDG_CONFIG_HOME/bgclipper/config.toml`,
+///   or `~/.config/bgclipper/config.toml` (see [`XdgPaths`])
+/// - macOS: `~/.config/bgclipper/config.toml`
+/// - Windows: `%APPDATA%\bgclipper\config.toml`
+///
+/// On Linux and other Unix systems, while the user has no config file, the
+/// first system-wide file found (such as `/etc/xdg/bgclipper/config.toml`)
+/// is used instead. It is never written: the first change saves a copy with
+/// the change to the user's file.
+///
+/// The file is parsed and validated once, on first use, and kept in memory.
+/// [`reload`](ConfigPort::reload) re-reads it only if it changed, keeping the
+/// last valid config if the new content is invalid; [`watcher`](Self::watcher)
+/// tells when to call it.
+#[derive(Debug)]
+pub struct TomlConfigProvider {
+    path: PathBuf,
+    /// Read-only files used while `path` does not exist, first found wins.
+    fallbacks: Vec<PathBuf>,
+    cache: Mutex<Cache>,
+}
+
+/// The config as last loaded, so reads do not touch the file.
+#[derive(Debug, Default)]
+struct Cache {
+    /// Version of the source last read, valid or not.
+    version: Option<SourceVersion>,
+    /// The last config that parsed and validated.
+    config: Option<ConfigFile>,
+}
+
+/// Identity of a config file version, compared to detect changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileVersion {
+    Missing,
+    Present { modified: SystemTime, len: u64 },
+}
+
+impl FileVersion {
+    fn of(path: &Path) -> Result<Self, ConfigError> {
+        match fs::metadata(path) {
+            Ok(metadata) => Ok(FileVersion::Present {
+                modified: metadata.modified()?,
+                len: metadata.len(),
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(FileVersion::Missing),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Versions of the config file and its fallbacks, up to the first that
+/// exists: the one read. Changes when that file changes, or when a file
+/// taking precedence over it appears.
+type SourceVersion = Vec<FileVersion>;
+
+impl TomlConfigProvider {
+    /// Creates a provider using the platform-default config path, with the
+    /// system-wide files as fallbacks where the platform has them.
+    ///
+    /// Returns `None` if the platform config directory cannot be determined.
+    pub fn new() -> Option<Self> {
+        if cfg!(all(unix, not(target_os = "macos"))) {
+            let xdg = XdgPaths::from_env();
+            Some(Self::with_path(xdg.user?).with_fallbacks(xdg.system))
+        } else {
+            let config_dir = dirs::config_dir()?;
+            Some(Self::with_path(
+                config_dir.join("bgclipper").join("config.toml"),
+            ))
+        }
+    }
+
+    /// Creates a provider with an explicit config file path.
+    ///
+    /// Useful for testing with temporary directories.
+    pub fn with_path(path: PathBuf) -> Self {
+        Self {
+            path,
+            fallbacks: Vec::new(),
+            cache: Mutex::new(Cache::default()),
+        }
+    }
+
+    /// Uses the first of `fallbacks` that exists, read-only, while the
+    /// config file does not exist.
+    pub fn with_fallbacks(mut self, fallbacks: Vec<PathBuf>) -> Self {
+        self.fallbacks = fallbacks;
+        self
+    }
+
+    /// Returns the path of the config file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the file the settings come from: the config file, or the
+    /// fallback in use. Returns `None` if none exists and the defaults are
+    /// in use.
+    pub fn effective_path(&self) -> Option<&Path> {
+        self.sources()
+            .map(PathBuf::as_path)
+            .find(|path| path.exists())
+    }
+
+    /// Returns the content of the file in effect, or the default config if
+    /// there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read.
+    pub fn effective_content(&self) -> Result<String, ConfigError> {
+        match self.read_source()? {
+            Some((_, content)) => Ok(content),
+            None => Ok(toml::to_string(&ConfigFile::default())?),
+        }
+    }
+
+    /// Returns the content of the file in effect, or the default config, with
+    /// the active settings replaced by `settings`. Nothing is written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, or if
+    /// `settings` are invalid.
+    pub fn effective_content_with(&self, settings: &Profile) -> Result<String, ConfigError> {
+        let (_, document) = self.updated_config(|config| {
+            config.active_settings_mut()?.set_profile(settings);
+            Ok(())
+        })?;
+        Ok(document.to_string())
+    }
+
+    /// Creates the config file if it does not exist, as a copy of the
+    /// fallback in use or with the default settings, so it can be edited.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or written.
+    pub fn ensure_file(&self) -> Result<(), ConfigError> {
+        if self.path.exists() {
+            return Ok(());
+        }
+        let content = self.effective_content()?;
+        debug!("creating {:?}", self.path);
+        write_private(&self.path, content)?;
+        Ok(())
+    }
+
+    /// Moves the config file aside to `config.toml.bak`, so the fallback in
+    /// use or the default settings apply again.
+    ///
+    /// Returns the path of the backup, or `None` if there was no file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be moved or the settings that
+    /// apply next cannot be loaded.
+    pub fn reset(&self) -> Result<Option<PathBuf>, ConfigError> {
+        let mut cache = self.cache();
+        let mut backup = self.path.clone().into_os_string();
+        backup.push(".bak");
+        let backup = PathBuf::from(backup);
+        match fs::rename(&self.path, &backup) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        info!("config reset, previous file saved as {backup:?}");
+        *cache = Cache::default();
+        self.refresh(&mut cache)?;
+        Ok(Some(backup))
+    }
+
+    /// Returns a watcher that notifies when the config file or one of its
+    /// fallbacks changes, so edits to a system-wide file in use are noticed
+    /// too.
+    pub fn watcher(&self) -> FileWatcher {
+        FileWatcher::for_paths(
+            self.sources().cloned().collect(),
+            FileWatcher::DEFAULT_INTERVAL,
+        )
+    }
+
+    /// Returns the config file followed by its fallbacks, in the order they
+    /// are tried.
+    fn sources(&self) -> impl Iterator<Item = &PathBuf> {
+        std::iter::once(&self.path).chain(&self.fallbacks)
+    }
+
+    /// Returns the version of the config source: the config file, or the
+    /// fallback [`read_source`](Self::read_source) would read.
+    fn source_version(&self) -> Result<SourceVersion, ConfigError> {
+        let mut versions = Vec::new();
+        for path in self.sources() {
+            let version = FileVersion::of(path)?;
+            versions.push(version);
+            if version != FileVersion::Missing {
+                break;
+            }
+        }
+        Ok(versions)
+    }
+
+    /// Reads the config file as a document that keeps its formatting.
+    ///
+    /// A file written for an older schema is upgraded first, and its content
+    /// as written is returned with it. Unknown keys are logged and ignored.
+    /// Returns `None` if the file does not exist.
+    fn read_document(&self) -> Result<Option<(Document, Option<String>)>, ConfigError> {
+        let Some((path, content)) = self.read_source()? else {
+            debug!("config file not found, using defaults: {:?}", self.path);
+            return Ok(None);
+        };
+
+        let mut document = parse_document(&content)?;
+        let original = match migrate(&mut document)? {
+            Some(version) => {
+                if path == self.path {
+                    self.upgrade(&content, &document, version);
+                } else {
+                    debug!("{path:?} is version {version}, upgraded in memory only");
+                }
+                Some(content)
+            }
+            None => None,
+        };
+        for key in unknown_keys(&document) {
+            warn!("{path:?}: unknown key `{key}` is ignored");
+        }
+        Ok(Some((document, original)))
+    }
+
+    /// Reads the config file, or the first fallback that exists, returning
+    /// its path and content.
+    fn read_source(&self) -> Result<Option<(PathBuf, String)>, ConfigError> {
+        for path in self.sources() {
+            match fs::read_to_string(path) {
+                Ok(content) => return Ok(Some((path.clone(), content))),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads and parses the config file, without validating the settings.
+    ///
+    /// Returns the defaults and an empty document if the file does not exist.
+    /// Errors in an upgraded file are located in its content as written.
+    fn read_config(&self) -> Result<(ConfigFile, Document), ConfigError> {
+        let Some((document, original)) = self.read_document()? else {
+            return Ok((ConfigFile::default(), Document::new()));
+        };
+        let config = parse_config(&document.to_string()).map_err(|e| match original {
+            Some(original) => match (parse_config(&original), e) {
+                (Err(e), _) => e,
+                // Only wrong once upgraded, so there is no position to report
+                (Ok(_), ConfigError::Parse(e)) => ConfigError::Parse(e.unlocated()),
+                (Ok(_), e) => e,
+            },
+            None => e,
+        })?;
+        Ok((config, document))
+    }
+
+    /// Replaces the file written for schema `version` by the upgraded
+    /// `document`, keeping the `original` content as a backup next to it.
+    ///
+    /// If the files cannot be written, the upgrade only applies in memory.
+    fn upgrade(&self, original: &str, document: &Document, version: u32) {
+        let mut backup = self.path.clone().into_os_string();
+        backup.push(format!(".v{version}.bak"));
+        let backup = PathBuf::from(backup);
+
+        match write_private(&backup, original)
+            .and_then(|()| write_private(&self.path, document.to_string()))
+        {
+            Ok(()) => info!(
+                "config upgraded from version {version} to {CONFIG_VERSION}, original saved as {backup:?}"
+            ),
+            Err(e) => warn!("failed to upgrade {:?}, using it as is: {e}", self.path),
+        }
+    }
+
+    /// Loads the config file into `cache` if it changed since the last read.
+    ///
+    /// Returns `true` if a new config was loaded. An invalid file leaves the
+    /// cached config in place, and is not read again until it changes.
+    fn refresh(&self, cache: &mut Cache) -> Result<bool, ConfigError> {
+        if cache.config.is_some() && cache.version == Some(self.source_version()?) {
+            return Ok(false);
+        }
+        let loaded = self.read_config().and_then(|(config, _)| {
+            config.validate()?;
+            Ok(config)
+        });
+        // Taken after reading, as an upgrade rewrites the file
+        let version = self.source_version()?;
+        if loaded.is_ok() || cache.config.is_some() {
+            cache.version = Some(version);
+        }
+        cache.config = Some(loaded?);
+        debug!("config loaded from {:?}", self.path);
+        Ok(true)
+    }
+
+    /// Runs `read` on the loaded config, loading it on first use.
+    fn with_config<T>(
+        &self,
+        read: impl FnOnce(&ConfigFile) -> Result<T, ConfigError>,
+    ) -> Result<T, ConfigError> {
+        let mut cache = self.cache();
+        if let Some(config) = &cache.config {
+            return read(config);
+        }
+        self.refresh(&mut cache)?;
+        read(cache.config.as_ref().expect("refresh stores the config"))
+    }
+
+    /// Runs `read` on the settings of the active profile, or the top-level
+    /// settings.
+    fn with_settings<T>(
+        &self,
+        read: impl FnOnce(&SettingsConfig) -> Result<T, ConfigError>,
+    ) -> Result<T, ConfigError> {
+        self.with_config(|config| read(config.active_settings()?))
+    }
+
+    /// Applies `update` to the settings of the active profile (or the
+    /// top-level settings) and writes the config back.
+    fn update_settings(&self, update: impl FnOnce(&mut SettingsConfig)) -> Result<(), ConfigError> {
+        self.update_config(|config| {
+            update(config.active_settings_mut()?);
+            Ok(())
+        })
+    }
+
+    /// Applies `update` to the config file (or the defaults) and writes it
+    /// back, making the result the loaded config.
+    ///
+    /// Nothing is written if `update` fails or the result is invalid.
+    fn update_config(
+        &self,
+        update: impl FnOnce(&mut ConfigFile) -> Result<(), ConfigError>,
+    ) -> Result<(), ConfigError> {
+        let mut cache = self.cache();
+        let (config, document) = self.updated_config(update)?;
+
+        write_private(&self.path, document.to_string())?;
+        cache.version = Some(self.source_version()?);
+        cache.config = Some(config);
+        Ok(())
+    }
+
+    /// Applies `update` to the config file (or the defaults), returning the
+    /// result and the document to write for it.
+    ///
+    /// Only the changed values are rewritten, so comments, layout and unknown
+    /// keys are kept.
+    fn updated_config(
+        &self,
+        update: impl FnOnce(&mut ConfigFile) -> Result<(), ConfigError>,
+    ) -> Result<(ConfigFile, Document), ConfigError> {
+        let (mut config, mut document) = self.read_config()?;
+        // A new file gets the whole config, defaults included
+        let old = if document.is_empty() {
+            Document::new()
+        } else {
+            parse_document(&toml::to_string(&config)?)?
+        };
+        update(&mut config)?;
+        config.validate()?;
+
+        let new = parse_document(&toml::to_string(&config)?)?;
+        merge(document.as_table_mut(), old.as_table(), new.as_table());
+        Ok((config, document))
+    }
+
+    fn cache(&self) -> MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ConfigPort for TomlConfigProvider {
+    type Error = ConfigError;
+
+    fn load_settings(&self) -> Result<Profile, Self::Error> {
+        self.with_settings(SettingsConfig::to_profile)
+    }
+
+    fn save_settings(&self, settings: &Profile) -> Result<(), Self::Error> {
+        self.update_settings(|config| config.set_profile(settings))
+    }
+
+    fn load_target_color(&self) -> Result<Color, Self::Error> {
+        let color =
+            self.with_settings(|settings| Ok(Rgba::try_from(&settings.target_color)?.rgb()))?;
+
+        debug!("config loaded from {:?}: {color}", self.path);
+        Ok(color)
+    }
+
+    fn save_target_color(&self, color: &Color) -> Result<(), Self::Error> {
+        self.update_settings(|settings| {
+            settings.target_color = settings.target_color.with_color(&Rgba::from(*color));
+            settings.target_colors = None;
+        })
+    }
+
+    fn load_target_colors(&self) -> Result<Vec<TargetColor>, Self::Error> {
+        self.with_settings(SettingsConfig::target_colors)
+    }
+
+    fn save_target_colors(&self, targets: &[TargetColor]) -> Result<(), Self::Error> {
+        self.update_settings(|settings| settings.set_target_colors(targets))
+    }
+
+    fn load_auto_detect(&self) -> Result<Option<AutoDetect>, Self::Error> {
+        self.with_settings(SettingsConfig::auto_detect)
+    }
+
+    fn save_auto_detect(&self, auto: Option<&AutoDetect>) -> Result<(), Self::Error> {
+        self.update_settings(|settings| settings.set_auto_detect(auto))
+    }
+
+    fn load_tolerance(&self) -> Result<Tolerance, Self::Error> {
+        self.with_settings(SettingsConfig::tolerance)
+    }
+
+    fn save_tolerance(&self, tolerance: &Tolerance) -> Result<(), Self::Error> {
+        self.update_settings(|settings| settings.set_tolerance(tolerance))
+    }
+
+    fn load_alpha_ramp(&self) -> Result<Option<AlphaRamp>, Self::Error> {
+        self.with_settings(SettingsConfig::alpha_ramp)
+    }
+
+    fn save_alpha_ramp(&self, ramp: Option<&AlphaRamp>) -> Result<(), Self::Error> {
+        self.update_settings(|settings| settings.set_alpha_ramp(ramp))
+    }
+
+    fn load_post_processing(&self) -> Result<PostProcessing, Self::Error> {
+        self.with_settings(|settings| Ok(settings.post_processing()))
+    }
+
+    fn save_post_processing(&self, post: &PostProcessing) -> Result<(), Self::Error> {
+        self.update_settings(|settings| settings.set_post_processing(post))
+    }
+
+    fn load_removal_mode(&self) -> Result<RemovalMode, Self::Error> {
+        self.with_settings(SettingsConfig::removal_mode)
+    }
+
+    fn save_removal_mode(&self, mode: &RemovalMode) -> Result<(), Self::Error> {
+        self.update_settings(|settings| settings.set_removal_mode(mode))
+    }
+
+    fn load_alpha_matching(&self) -> Result<AlphaMatching, Self::Error> {
+        self.with_settings(|settings| Ok(settings.alpha_matching()))
+    }
+
+    fn save_alpha_matching(&self, matching: AlphaMatching) -> Result<(), Self::Error> {
+        self.update_settings(|settings| settings.set_alpha_matching(matching))
+    }
+
+    fn list_profiles(&self) -> Result<Vec<String>, Self::Error> {
+        self.with_config(|config| Ok(config.profiles.keys().cloned().collect()))
+    }
+
+    fn load_profile(&self, name: &str) -> Result<Option<Profile>, Self::Error> {
+        self.with_config(|config| {
+            config
+                .profiles
+                .get(name)
+                .map(SettingsConfig::to_profile)
+                .transpose()
+        })
+    }
+
+    fn save_profile(&self, name: &str, profile: &Profile) -> Result<(), Self::Error> {
+        if name.trim().is_empty() {
+            return Err(ConfigError::Invalid(
+                "profile name must not be empty".to_string(),
+            ));
+        }
+        self.update_config(|config| {
+            config
+                .profiles
+                .insert(name.to_string(), SettingsConfig::from_profile(profile));
+            Ok(())
+        })
+    }
+
+    fn load_active_profile(&self) -> Result<Option<String>, Self::Error> {
+        self.with_config(|config| Ok(config.active_profile.clone()))
+    }
+
+    fn save_active_profile(&self, name: Option<&str>) -> Result<(), Self::Error> {
+        self.update_config(|config| {
+            if let Some(name) = name
+                && !config.profiles.contains_key(name)
+            {
+                return Err(unknown_profile(name));
+            }
+            config.active_profile = name.map(str::to_string);
+            Ok(())
+        })
+    }
+
+    /// A system-wide fallback counts as existing, so that changes to it
+    /// keep applying until the user saves a setting.
+    fn ensure_config_exists(&self) -> Result<(), Self::Error> {
+        if self.effective_path().is_none() {
+            debug!("creating default config at {:?}", self.path);
+            self.update_config(|_| Ok(()))?;
+        } else {
+            debug!("config file already exists: {:?}", self.path);
+        }
+        Ok(())
+    }
+
+    fn reload(&self) -> Result<bool, Self::Error> {
+        self.refresh(&mut self.cache())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_provider() -> (TomlConfigProvider, tempfile::TempDir) {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let path = dir.path().join("config.toml");
+        (TomlConfigProvider::with_path(path), dir)
+    }
+
+    #[test]
+    fn load_returns_default_when_file_missing() {
+        let (provider, _dir) = temp_provider();
+        let color = provider.load_target_color().unwrap();
+        assert_eq!(color, Color::default());
+    }
+
+    #[test]
+    fn save_and_load_roundtrip() {
+        let (provider, _dir) = temp_provider();
+        let color = Color::new(100, 150, 200);
+        provider.save_target_color(&color).unwrap();
+        let loaded = provider.load_target_color().unwrap();
+        assert_eq!(loaded, color);
+    }
+
+    #[test]
+    fn save_creates_parent_directories() {
+        let dir = tempfile::tempdir().expect("failed to create temp dir");
+        let path = dir.path().join("nested").join("deep").join("config.toml");
+        let provider = TomlConfigProvider::with_path(path.clone());
+
+        provider.save_target_color(&Color::default()).unwrap();
+
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn load_returns_error_for_malformed_toml() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, "not valid toml [[[").unwrap();
+
+        let result = provider.load_target_color();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn saved_file_is_valid_toml() {
+        let (provider, _dir) = temp_provider();
+        let color = Color::new(10, 20, 30);
+        provider.save_target_color(&color).unwrap();
+
+        let content = fs::read_to_string(&provider.path).unwrap();
+        let config = parse_config(&content).unwrap();
+        assert!(matches!(
+            config.settings.target_color,
+            ColorConfig::Rgb {
+                r: 10,
+                g: 20,
+                b: 30,
+                a: None
+            }
+        ));
+    }
+
+    #[test]
+    fn load_target_color_from_string() {
+        let (provider, _dir) = temp_provider();
+        for (value, expected) in [
+            ("#e7feb6", Color::new(0xe7, 0xfe, 0xb6)),
+            ("#fff", Color::new(255, 255, 255)),
+            ("hsl(120, 100%, 50%)", Color::new(0, 255, 0)),
+            ("teal", Color::new(0, 128, 128)),
+        ] {
+            fs::write(&provider.path, format!("target_color = \"{value}\"\n")).unwrap();
+            provider.reload().unwrap();
+            assert_eq!(provider.load_target_color().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn load_target_color_rejects_invalid_string() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, "target_color = \"#12345\"\n").unwrap();
+
+        let err = provider.load_target_color().unwrap_err();
+        assert!(matches!(&err, ConfigError::Invalid(msg) if msg.contains("#12345")));
+    }
+
+    #[test]
+    fn save_target_color_keeps_string_form() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, "target_color = \"white\"\n").unwrap();
+
+        provider
+            .save_target_color(&Color::new(0xe7, 0xfe, 0xb6))
+            .unwrap();
+
+        let content = fs::read_to_string(&provider.path).unwrap();
+        assert!(content.contains("target_color = \"#e7feb6\""));
+        assert_eq!(
+            provider.load_target_color().unwrap(),
+            Color::new(0xe7, 0xfe, 0xb6)
+        );
+    }
+
+    #[test]
+    fn overwrite_existing_config() {
+        let (provider, _dir) = temp_provider();
+
+        provider.save_target_color(&Color::new(0, 0, 0)).unwrap();
+        provider
+            .save_target_color(&Color::new(255, 128, 64))
+            .unwrap();
+
+        let loaded = provider.load_target_color().unwrap();
+        assert_eq!(loaded, Color::new(255, 128, 64));
+    }
+
+    #[test]
+    fn ensure_config_exists_creates_file_when_missing() {
+        let (provider, _dir) = temp_provider();
+        assert!(!provider.path.exists());
+
+        provider.ensure_config_exists().unwrap();
+
+        assert!(provider.path.exists());
+        let loaded = provider.load_target_color().unwrap();
+        assert_eq!(loaded, Color::default());
+    }
+
+    #[test]
+    fn ensure_config_exists_does_not_overwrite() {
+        let (provider, _dir) = temp_provider();
+        let custom = Color::new(10, 20, 30);
+        provider.save_target_color(&custom).unwrap();
+
+        provider.ensure_config_exists().unwrap();
+
+        let loaded = provider.load_target_color().unwrap();
+        assert_eq!(loaded, custom);
+    }
+
+    #[test]
+    fn load_target_colors_falls_back_to_single_color() {
+        let (provider, _dir) = temp_provider();
+        provider.save_target_color(&Color::new(1, 2, 3)).unwrap();
+
+        assert_eq!(
+            provider.load_target_colors().unwrap(),
+            vec![TargetColor::new(Color::new(1, 2, 3))]
+        );
+    }
+
+    #[test]
+    fn load_target_colors_defaults_when_file_missing() {
+        let (provider, _dir) = temp_provider();
+        assert_eq!(
+            provider.load_target_colors().unwrap(),
+            vec![TargetColor::new(Color::default())]
+        );
+    }
+
+    #[test]
+    fn load_target_colors_from_toml() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "[[target_colors]]\nr = 255\ng = 255\nb = 255\n\n\
+             [[target_colors]]\nr = 250\ng = 250\nb = 250\n\
+             tolerance = { metric = \"channel_max\", threshold = 2.0 }\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            provider.load_target_colors().unwrap(),
+            vec![
+                TargetColor::new(Color::new(255, 255, 255)),
+                TargetColor::with_tolerance(
+                    Color::new(250, 250, 250),
+                    Tolerance::new(DistanceMetric::ChannelMax, 2.0).unwrap()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn load_target_colors_rejects_empty_list() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, "target_colors = []\n").unwrap();
+
+        let result = provider.load_target_colors();
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn load_target_colors_rejects_per_color_outer_threshold() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "[[target_colors]]\nr = 255\ng = 255\nb = 255\n\
+             tolerance = { threshold = 2.0, outer_threshold = 9.0 }\n",
+        )
+        .unwrap();
+
+        let result = provider.load_target_colors();
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn save_target_colors_roundtrip_keeps_single_color_in_sync() {
+        let (provider, _dir) = temp_provider();
+        let targets = vec![
+            TargetColor::new(Color::new(250, 250, 250)),
+            TargetColor::with_tolerance(
+                Color::new(255, 255, 255),
+                Tolerance::new(DistanceMetric::Cie76, 1.5).unwrap(),
+            ),
+        ];
+
+        provider.save_target_colors(&targets).unwrap();
+
+        assert_eq!(provider.load_target_colors().unwrap(), targets);
+        assert_eq!(
+            provider.load_target_color().unwrap(),
+            Color::new(250, 250, 250)
+        );
+    }
+
+    #[test]
+    fn save_target_color_replaces_list() {
+        let (provider, _dir) = temp_provider();
+        provider
+            .save_target_colors(&[
+                TargetColor::new(Color::new(1, 1, 1)),
+                TargetColor::new(Color::new(2, 2, 2)),
+            ])
+            .unwrap();
+
+        provider.save_target_color(&Color::new(3, 3, 3)).unwrap();
+
+        assert_eq!(
+            provider.load_target_colors().unwrap(),
+            vec![TargetColor::new(Color::new(3, 3, 3))]
+        );
+    }
+
+    #[test]
+    fn load_auto_detect_is_none_by_default() {
+        let (provider, _dir) = temp_provider();
+        provider.save_target_color(&Color::default()).unwrap();
+        assert!(provider.load_auto_detect().unwrap().is_none());
+    }
+
+    #[test]
+    fn load_auto_detect_without_target_color() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, "[auto_detect]\n").unwrap();
+
+        let auto = provider.load_auto_detect().unwrap().unwrap();
+        assert_eq!(auto.min_confidence(), AutoDetect::DEFAULT_MIN_CONFIDENCE);
+        assert_eq!(provider.load_target_color().unwrap(), Color::default());
+    }
+
+    #[test]
+    fn load_auto_detect_rejects_out_of_range_confidence() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, "[auto_detect]\nmin_confidence = 1.5\n").unwrap();
+
+        let result = provider.load_auto_detect();
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn save_auto_detect_roundtrip_and_clear() {
+        let (provider, _dir) = temp_provider();
+        let auto = AutoDetect::new(0.95).unwrap();
+
+        provider.save_auto_detect(Some(&auto)).unwrap();
+        assert_eq!(provider.load_auto_detect().unwrap(), Some(auto));
+
+        provider.save_auto_detect(None).unwrap();
+        assert!(provider.load_auto_detect().unwrap().is_none());
+    }
+
+    #[test]
+    fn load_tolerance_defaults_to_exact() {
+        let (provider, _dir) = temp_provider();
+        provider.save_target_color(&Color::default()).unwrap();
+        assert_eq!(provider.load_tolerance().unwrap(), Tolerance::exact());
+    }
+
+    #[test]
+    fn load_tolerance_from_toml() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "[target_color]\nr = 255\ng = 255\nb = 255\n\n\
+             [tolerance]\nmetric = \"ciede2000\"\nthreshold = 2.3\n",
+        )
+        .unwrap();
+
+        let tolerance = provider.load_tolerance().unwrap();
+        assert_eq!(tolerance.metric(), DistanceMetric::Ciede2000);
+        assert_eq!(tolerance.threshold(), 2.3);
+    }
+
+    #[test]
+    fn load_tolerance_defaults_metric_to_euclidean() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "[target_color]\nr = 0\ng = 0\nb = 0\n\n[tolerance]\nthreshold = 4\n",
+        )
+        .unwrap();
+
+        let tolerance = provider.load_tolerance().unwrap();
+        assert_eq!(tolerance.metric(), DistanceMetric::EuclideanRgb);
+        assert_eq!(tolerance.threshold(), 4.0);
+    }
+
+    #[test]
+    fn load_tolerance_rejects_negative_threshold() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "[target_color]\nr = 0\ng = 0\nb = 0\n\n[tolerance]\nthreshold = -1.0\n",
+        )
+        .unwrap();
+
+        let result = provider.load_tolerance();
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn save_tolerance_preserves_target_color() {
+        let (provider, _dir) = temp_provider();
+        let color = Color::new(231, 254, 182);
+        provider.save_target_color(&color).unwrap();
+
+        let tolerance = Tolerance::new(DistanceMetric::ChannelMax, 6.0).unwrap();
+        provider.save_tolerance(&tolerance).unwrap();
+
+        assert_eq!(provider.load_target_color().unwrap(), color);
+        assert_eq!(provider.load_tolerance().unwrap(), tolerance);
+    }
+
+    #[test]
+    fn save_target_color_preserves_tolerance() {
+        let (provider, _dir) = temp_provider();
+        let tolerance = Tolerance::new(DistanceMetric::Cie76, 3.5).unwrap();
+        provider.save_tolerance(&tolerance).unwrap();
+
+        provider.save_target_color(&Color::new(1, 2, 3)).unwrap();
+
+        assert_eq!(provider.load_tolerance().unwrap(), tolerance);
+    }
+
+    #[test]
+    fn load_alpha_ramp_is_none_without_outer_threshold() {
+        let (provider, _dir) = temp_provider();
+        let tolerance = Tolerance::new(DistanceMetric::Cie76, 3.0).unwrap();
+        provider.save_tolerance(&tolerance).unwrap();
+
+        assert_eq!(provider.load_alpha_ramp().unwrap(), None);
+    }
+
+    #[test]
+    fn load_alpha_ramp_from_toml() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "[target_color]\nr = 255\ng = 255\nb = 255\n\n\
+             [tolerance]\nmetric = \"cie76\"\nthreshold = 2.0\nouter_threshold = 12.0\n",
+        )
+        .unwrap();
+
+        let ramp = provider.load_alpha_ramp().unwrap().unwrap();
+        assert_eq!(ramp.metric(), DistanceMetric::Cie76);
+        assert_eq!(ramp.inner(), 2.0);
+        assert_eq!(ramp.outer(), 12.0);
+    }
+
+    #[test]
+    fn load_alpha_ramp_rejects_outer_below_inner() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "[target_color]\nr = 0\ng = 0\nb = 0\n\n\
+             [tolerance]\nthreshold = 10.0\nouter_threshold = 5.0\n",
+        )
+        .unwrap();
+
+        let result = provider.load_alpha_ramp();
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn save_alpha_ramp_roundtrip_and_clear() {
+        let (provider, _dir) = temp_provider();
+        let ramp = AlphaRamp::new(DistanceMetric::Ciede2000, 1.0, 8.0).unwrap();
+
+        provider.save_alpha_ramp(Some(&ramp)).unwrap();
+        assert_eq!(provider.load_alpha_ramp().unwrap(), Some(ramp));
+
+        provider.save_alpha_ramp(None).unwrap();
+        assert_eq!(provider.load_alpha_ramp().unwrap(), None);
+        // The inner threshold stays as a plain tolerance
+        let tolerance = provider.load_tolerance().unwrap();
+        assert_eq!(tolerance.metric(), DistanceMetric::Ciede2000);
+        assert_eq!(tolerance.threshold(), 1.0);
+    }
+
+    #[test]
+    fn load_post_processing_defaults_to_disabled() {
+        let (provider, _dir) = temp_provider();
+        provider.save_target_color(&Color::default()).unwrap();
+        assert_eq!(
+            provider.load_post_processing().unwrap(),
+            PostProcessing::default()
+        );
+    }
+
+    #[test]
+    fn load_post_processing_from_toml() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "[target_color]\nr = 0\ng = 255\nb = 0\n\n\
+             [post_processing]\ndecontaminate_edges = true\n",
+        )
+        .unwrap();
+
+        assert!(provider.load_post_processing().unwrap().decontaminate_edges);
+    }
+
+    #[test]
+    fn save_post_processing_roundtrip() {
+        let (provider, _dir) = temp_provider();
+        let post = PostProcessing {
+            decontaminate_edges: true,
+            crop: Some(Crop { padding: 6 }),
+        };
+        provider.save_post_processing(&post).unwrap();
+        assert_eq!(provider.load_post_processing().unwrap(), post);
+    }
+
+    #[test]
+    fn crop_is_set_per_profile() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "active_profile = \"logos\"\n\n\
+             [profiles.logos.post_processing.crop]\npadding = 4\n\n\
+             [profiles.photos]\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            provider.load_post_processing().unwrap().crop,
+            Some(Crop { padding: 4 })
+        );
+        provider.save_active_profile(Some("photos")).unwrap();
+        assert_eq!(provider.load_post_processing().unwrap().crop, None);
+    }
+
+    #[test]
+    fn crop_padding_defaults_to_zero() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, "[post_processing.crop]\n").unwrap();
+
+        assert_eq!(
+            provider.load_post_processing().unwrap().crop,
+            Some(Crop::default())
+        );
+    }
+
+    #[test]
+    fn load_removal_mode_defaults_to_global() {
+        let (provider, _dir) = temp_provider();
+        provider.save_target_color(&Color::default()).unwrap();
+        assert_eq!(provider.load_removal_mode().unwrap(), RemovalMode::Global);
+    }
+
+    #[test]
+    fn load_flood_mode_defaults_to_border_and_four_connectivity() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "mode = \"flood\"\n\n[target_color]\nr = 255\ng = 255\nb = 255\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            provider.load_removal_mode().unwrap(),
+            RemovalMode::Flood(FloodFill::default())
+        );
+    }
+
+    #[test]
+    fn load_flood_mode_with_seeds_and_eight_connectivity() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "mode = \"flood\"\n\n[target_color]\nr = 255\ng = 255\nb = 255\n\n\
+             [flood]\nconnectivity = 8\nseeds = [[0, 0], [10, 20]]\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            provider.load_removal_mode().unwrap(),
+            RemovalMode::Flood(FloodFill {
+                seeds: FloodSeeds::Points(vec![(0, 0), (10, 20)]),
+                connectivity: Connectivity::Eight,
+            })
+        );
+    }
+
+    #[test]
+    fn load_flood_mode_rejects_invalid_connectivity() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "mode = \"flood\"\n\n[target_color]\nr = 0\ng = 0\nb = 0\n\n\
+             [flood]\nconnectivity = 6\n",
+        )
+        .unwrap();
+
+        let result = provider.load_removal_mode();
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn load_rejects_unknown_mode() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "mode = \"magic\"\n\n[target_color]\nr = 0\ng = 0\nb = 0\n",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            provider.load_removal_mode(),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn save_removal_mode_roundtrip() {
+        let (provider, _dir) = temp_provider();
+        let mode = RemovalMode::Flood(FloodFill {
+            seeds: FloodSeeds::Points(vec![(3, 4)]),
+            connectivity: Connectivity::Eight,
+        });
+        provider.save_removal_mode(&mode).unwrap();
+        assert_eq!(provider.load_removal_mode().unwrap(), mode);
+
+        provider.save_removal_mode(&RemovalMode::Global).unwrap();
+        assert_eq!(provider.load_removal_mode().unwrap(), RemovalMode::Global);
+    }
+
+    #[test]
+    fn load_alpha_matching_defaults_to_ignore() {
+        let (provider, _dir) = temp_provider();
+        assert_eq!(
+            provider.load_alpha_matching().unwrap(),
+            AlphaMatching::Ignore
+        );
+    }
+
+    #[test]
+    fn load_alpha_matching_from_toml() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, "alpha_matching = \"opaque_only\"\n").unwrap();
+        assert_eq!(
+            provider.load_alpha_matching().unwrap(),
+            AlphaMatching::OpaqueOnly
+        );
+
+        fs::write(&provider.path, "alpha_matching = \"sometimes\"\n").unwrap();
+        assert!(matches!(provider.reload(), Err(ConfigError::Parse(_))));
+    }
+
+    #[test]
+    fn save_alpha_matching_roundtrip() {
+        let (provider, _dir) = temp_provider();
+        provider
+            .save_alpha_matching(AlphaMatching::Premultiplied)
+            .unwrap();
+        assert_eq!(
+            provider.load_alpha_matching().unwrap(),
+            AlphaMatching::Premultiplied
+        );
+
+        provider.save_alpha_matching(AlphaMatching::Ignore).unwrap();
+        let content = fs::read_to_string(&provider.path).unwrap();
+        assert!(!content.contains("alpha_matching"));
+    }
+
+    #[test]
+    fn load_target_colors_with_alpha() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "\
+[[target_colors]]
+r = 255
+g = 255
+b = 255
+a = 128
+
+[[target_colors]]
+r = 0
+g = 0
+b = 0
+",
+        )
+        .unwrap();
+
+        let targets = provider.load_target_colors().unwrap();
+        assert_eq!(targets[0].rgba(), Rgba::new(255, 255, 255, 128));
+        assert_eq!(targets[1].alpha, 255);
+
+        provider.save_target_colors(&targets).unwrap();
+        assert_eq!(provider.load_target_colors().unwrap(), targets);
+    }
+
+    #[test]
+    fn target_color_string_may_carry_alpha() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, "target_color = \"#ffffff80\"\n").unwrap();
+
+        assert_eq!(
+            provider.load_target_colors().unwrap(),
+            vec![TargetColor::from(Rgba::new(255, 255, 255, 128))]
+        );
+        assert_eq!(
+            provider.load_target_color().unwrap(),
+            Color::new(255, 255, 255)
+        );
+    }
+
+    const PROFILES_TOML: &str = "\
+active_profile = \"green\"
+
+[target_color]
+r = 255
+g = 255
+b = 255
+
+[profiles.green]
+mode = \"flood\"
+
+[profiles.green.target_color]
+r = 0
+g = 255
+b = 0
+
+[profiles.green.tolerance]
+metric = \"cie76\"
+threshold = 3.0
+
+[profiles.dark]
+
+[[profiles.dark.target_colors]]
+r = 30
+g = 30
+b = 30
+";
+
+    #[test]
+    fn list_profiles_sorted() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, PROFILES_TOML).unwrap();
+        assert_eq!(provider.list_profiles().unwrap(), vec!["dark", "green"]);
+    }
+
+    #[test]
+    fn list_profiles_empty_without_file() {
+        let (provider, _dir) = temp_provider();
+        assert!(provider.list_profiles().unwrap().is_empty());
+        assert_eq!(provider.load_active_profile().unwrap(), None);
+    }
+
+    #[test]
+    fn settings_are_read_from_active_profile() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, PROFILES_TOML).unwrap();
+
+        assert_eq!(
+            provider.load_active_profile().unwrap().as_deref(),
+            Some("green")
+        );
+        assert_eq!(provider.load_target_color().unwrap(), Color::new(0, 255, 0));
+        assert_eq!(
+            provider.load_tolerance().unwrap(),
+            Tolerance::new(DistanceMetric::Cie76, 3.0).unwrap()
+        );
+        assert_eq!(
+            provider.load_removal_mode().unwrap(),
+            RemovalMode::Flood(FloodFill::default())
+        );
+    }
+
+    #[test]
+    fn top_level_settings_used_without_active_profile() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, PROFILES_TOML).unwrap();
+
+        provider.save_active_profile(None).unwrap();
+
+        assert_eq!(
+            provider.load_target_color().unwrap(),
+            Color::new(255, 255, 255)
+        );
+        assert_eq!(provider.load_tolerance().unwrap(), Tolerance::exact());
+        assert_eq!(provider.list_profiles().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn saving_settings_writes_to_active_profile() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, PROFILES_TOML).unwrap();
+
+        provider.save_target_color(&Color::new(1, 2, 3)).unwrap();
+
+        assert_eq!(
+            provider.load_profile("green").unwrap().unwrap().targets,
+            vec![TargetColor::new(Color::new(1, 2, 3))]
+        );
+        provider.save_active_profile(None).unwrap();
+        assert_eq!(
+            provider.load_target_color().unwrap(),
+            Color::new(255, 255, 255)
+        );
+    }
+
+    #[test]
+    fn save_settings_replaces_all_settings_keeping_color_format() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "version = 2\ntarget_color = \"white\"\n\n[auto_detect]\n",
+        )
+        .unwrap();
+        let settings = Profile {
+            targets: vec![TargetColor::new(Color::new(1, 2, 3))],
+            tolerance: Tolerance::new(DistanceMetric::ChannelMax, 8.0).unwrap(),
+            ..Profile::default()
+        };
+
+        provider.save_settings(&settings).unwrap();
+
+        assert_eq!(provider.load_settings().unwrap(), settings);
+        let content = fs::read_to_string(&provider.path).unwrap();
+        assert!(content.contains("target_color = \"#010203\""), "{content}");
+    }
+
+    #[test]
+    fn load_profile_returns_bundle() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, PROFILES_TOML).unwrap();
+
+        let dark = provider.load_profile("dark").unwrap().unwrap();
+        assert_eq!(dark.targets, vec![TargetColor::new(Color::new(30, 30, 30))]);
+        assert_eq!(dark.mode, RemovalMode::Global);
+        assert!(provider.load_profile("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn save_profile_roundtrip() {
+        let (provider, _dir) = temp_provider();
+        let profile = Profile {
+            targets: vec![
+                TargetColor::new(Color::new(255, 255, 255)),
+                TargetColor::new(Color::new(250, 250, 250)),
+            ],
+            auto_detect: None,
+            tolerance: Tolerance::new(DistanceMetric::EuclideanRgb, 4.0).unwrap(),
+            alpha_ramp: Some(AlphaRamp::new(DistanceMetric::EuclideanRgb, 4.0, 20.0).unwrap()),
+            alpha_matching: AlphaMatching::OpaqueOnly,
+            mode: RemovalMode::Flood(FloodFill::default()),
+            post_processing: PostProcessing {
+                decontaminate_edges: true,
+                crop: Some(Crop { padding: 2 }),
+            },
+        };
+
+        provider.save_profile("mockups", &profile).unwrap();
+
+        assert_eq!(provider.load_profile("mockups").unwrap(), Some(profile));
+        // Saving a profile does not activate it
+        assert_eq!(provider.load_active_profile().unwrap(), None);
+    }
+
+    #[test]
+    fn save_profile_rejects_empty_name() {
+        let (provider, _dir) = temp_provider();
+        let result = provider.save_profile(" ", &Profile::default());
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn save_active_profile_rejects_unknown_name() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, PROFILES_TOML).unwrap();
+
+        let result = provider.save_active_profile(Some("missing"));
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+        assert_eq!(
+            provider.load_active_profile().unwrap().as_deref(),
+            Some("green")
+        );
+    }
+
+    #[test]
+    fn file_is_read_again_only_after_reload() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, "target_color = \"#000000\"\n").unwrap();
+        assert_eq!(provider.load_target_color().unwrap(), Color::new(0, 0, 0));
+
+        fs::write(&provider.path, "target_color = \"#ffffff80\"\n").unwrap();
+        assert_eq!(provider.load_target_color().unwrap(), Color::new(0, 0, 0));
+
+        assert!(provider.reload().unwrap());
+        assert!(!provider.reload().unwrap());
+        assert_eq!(
+            provider.load_target_color().unwrap(),
+            Color::new(255, 255, 255)
+        );
+    }
+
+    #[test]
+    fn invalid_reload_keeps_last_valid_config() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, PROFILES_TOML).unwrap();
+        assert_eq!(provider.load_target_color().unwrap(), Color::new(0, 255, 0));
+
+        fs::write(
+            &provider.path,
+            "active_profile = \"green\"\n[target_color\n",
+        )
+        .unwrap();
+        let err = provider.reload().unwrap_err();
+
+        let ConfigError::Parse(parse) = &err else {
+            panic!("expected a parse error, got {err:?}");
+        };
+        assert_eq!(parse.location(), Some((2, 14)));
+        assert!(err.to_string().contains("line 2, column 14"));
+        assert_eq!(provider.load_target_color().unwrap(), Color::new(0, 255, 0));
+        // Reported once, until the file changes again
+        assert!(!provider.reload().unwrap());
+    }
+
+    #[test]
+    fn type_errors_have_a_location() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "version = 2\n\n[profiles.dark.tolerance]\nmetric = \"cie76\"\nthreshold = \"high\"\n",
+        )
+        .unwrap();
 
-impl std::fmt::Display for ConfigError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ConfigError::Io(e) => write!(f, "config I/O error: {e}"),
-            ConfigError::Parse(e) => write!(f, "config parse error: {e}"),
-            ConfigError::Serialize(e) => write!(f, "config serialize error: {e}"),
-        }
+        let Err(ConfigError::Parse(parse)) = provider.load_tolerance() else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(parse.location(), Some((5, 13)));
     }
-}
 
-impl std::error::Error for ConfigError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match self {
-            ConfigError::Io(e) => Some(e),
-            ConfigError::Parse(e) => Some(e),
-            ConfigError::Serialize(e) => Some(e),
-        }
+    #[test]
+    fn top_level_type_errors_have_a_location() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "version = 2\n[tolerance]\nthreshold = \"high\"\n",
+        )
+        .unwrap();
+
+        let Err(ConfigError::Parse(parse)) = provider.load_tolerance() else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(parse.location(), Some((3, 13)));
     }
-}
 
-impl From<io::Error> for ConfigError {
-    fn from(e: io::Error) -> Self {
-        ConfigError::Io(e)
+    #[test]
+    fn upgraded_file_errors_are_located_as_written() {
+        let (provider, _dir) = temp_provider();
+        // Upgrading adds a `version` line above the settings
+        fs::write(&provider.path, "[tolerance]\nthreshold = \"high\"\n").unwrap();
+
+        let Err(ConfigError::Parse(parse)) = provider.load_tolerance() else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(parse.location(), Some((2, 13)));
     }
-}
 
-impl From<toml::de::Error> for ConfigError {
-    fn from(e: toml::de::Error) -> Self {
-        ConfigError::Parse(e)
+    #[test]
+    fn inactive_profiles_are_validated() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "[profiles.broken]\ntarget_color = \"#12345\"\n",
+        )
+        .unwrap();
+
+        let err = provider.load_target_color().unwrap_err();
+        assert!(matches!(&err, ConfigError::Invalid(msg) if msg.starts_with("profiles.broken:")));
     }
-}
 
-impl From<toml::ser::Error> for ConfigError {
-    fn from(e: toml::ser::Error) -> Self {
-        ConfigError::Serialize(e)
+    #[test]
+    fn saving_updates_loaded_config() {
+        let (provider, _dir) = temp_provider();
+        provider.save_target_color(&Color::new(1, 2, 3)).unwrap();
+        assert_eq!(provider.load_target_color().unwrap(), Color::new(1, 2, 3));
+
+        provider.save_target_color(&Color::new(4, 5, 6)).unwrap();
+        assert_eq!(provider.load_target_color().unwrap(), Color::new(4, 5, 6));
+        assert!(!provider.reload().unwrap());
     }
-}
 
-/// TOML-based configuration provider.
-///
-/// Reads and writes the target color from a TOML config file.
-/// The config file path is platform-dependent:
-/// - macOS: `~/.config/bgclipper/config.toml`
-/// - Windows: `%APPDATA%\bgclipper\config.toml`
-#[derive(Debug)]
-pub struct TomlConfigProvider {
-    path: PathBuf,
-}
+    #[test]
+    fn dangling_active_profile_is_reported() {
+        let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, "active_profile = \"gone\"\n").unwrap();
 
-impl TomlConfigProvider {
-    /// Creates a provider using the platform-default config path.
-    ///
-    /// Returns `None` if the platform config directory cannot be determined.
-    pub fn new() -> Option<Self> {
-        let config_dir = dirs::config_dir()?;
-        Some(Self {
-            path: config_dir.join("bgclipper").join("config.toml"),
-        })
+        let result = provider.load_target_color();
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
     }
 
-    /// Creates a provider with an explicit config file path.
-    ///
-    /// Useful for testing with temporary directories.
-    pub fn with_path(path: PathBuf) -> Self {
-        Self { path }
+    #[test]
+    fn v1_file_is_upgraded_in_place_with_backup() {
+        let (provider, dir) = temp_provider();
+        let v1 = "[target_color]\nr = 1\ng = 2\nb = 3\n";
+        fs::write(&provider.path, v1).unwrap();
+
+        assert_eq!(provider.load_target_color().unwrap(), Color::new(1, 2, 3));
+
+        let upgraded: toml::Table =
+            toml::from_str(&fs::read_to_string(&provider.path).unwrap()).unwrap();
+        assert_eq!(
+            upgraded.get("version"),
+            Some(&toml::Value::Integer(CONFIG_VERSION.into()))
+        );
+        let backup = dir.path().join("config.toml.v1.bak");
+        assert_eq!(fs::read_to_string(backup).unwrap(), v1);
+        assert!(!provider.reload().unwrap());
     }
-}
 
-impl ConfigPort for TomlConfigProvider {
-    type Error = ConfigError;
+    #[test]
+    fn v1_color_table_is_rewritten_as_hex_keeping_the_rest() {
+        let (provider, _dir) = temp_provider();
+        let v1 = "\
+# off-white scanner background
+[target_color]
+r = 250
+g = 250
+b = 245
 
-    fn load_target_color(&self) -> Result<Color, Self::Error> {
-        let content = match fs::read_to_string(&self.path) {
-            Ok(c) => c,
-            Err(e) if e.kind() == io::ErrorKind::NotFound => {
-                debug!("config file not found, using defaults: {:?}", self.path);
-                return Ok(Color::default());
-            }
-            Err(e) => return Err(e.into()),
-        };
+# softer edges
+[tolerance]
+threshold = 3.0   # keep it tight
+";
+        fs::write(&provider.path, v1).unwrap();
+
+        assert_eq!(
+            provider.load_target_color().unwrap(),
+            Color::new(250, 250, 245)
+        );
+
+        assert_eq!(
+            fs::read_to_string(&provider.path).unwrap(),
+            format!(
+                "\
+version = {CONFIG_VERSION}
+# off-white scanner background
+target_color = \"#fafaf5\"
 
-        let config: ConfigFile = toml::from_str(&content)?;
-        debug!(
-            "config loaded from {:?}: RGB({}, {}, {})",
-            self.path, config.target_color.r, config.target_color.g, config.target_color.b
+# softer edges
+[tolerance]
+threshold = 3.0   # keep it tight
+"
+            )
         );
-        Ok(Color::new(
-            config.target_color.r,
-            config.target_color.g,
-            config.target_color.b,
-        ))
     }
 
-    fn save_target_color(&self, color: &Color) -> Result<(), Self::Error> {
-        let config = ConfigFile {
-            target_color: ColorConfig {
-                r: color.r(),
-                g: color.g(),
-                b: color.b(),
-            },
+    #[test]
+    fn invalid_v1_color_table_is_left_to_the_parser() {
+        let (provider, _dir) = temp_provider();
+        fs::write(
+            &provider.path,
+            "[target_color]\nr = 250\ng = 250\nb = 300\n",
+        )
+        .unwrap();
+
+        let Err(ConfigError::Parse(parse)) = provider.load_target_color() else {
+            panic!("expected a parse error");
         };
+        // At the table, as written above the `version` line the upgrade adds
+        assert_eq!(parse.location(), Some((1, 1)));
+    }
 
-        let content = toml::to_string(&config)?;
+    #[test]
+    fn current_file_is_not_rewritten() {
+        let (provider, dir) = temp_provider();
+        let content = "version = 2\ntarget_color = \"teal\"\n";
+        fs::write(&provider.path, content).unwrap();
 
-        if let Some(parent) = self.path.parent() {
-            fs::create_dir_all(parent)?;
-        }
+        assert_eq!(
+            provider.load_target_color().unwrap(),
+            Color::new(0, 128, 128)
+        );
 
-        fs::write(&self.path, content)?;
-        Ok(())
+        assert_eq!(fs::read_to_string(&provider.path).unwrap(), content);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
     }
 
-    fn ensure_config_exists(&self) -> Result<(), Self::Error> {
-        if !self.path.exists() {
-            debug!("creating default config at {:?}", self.path);
-            self.save_target_color(&Color::default())?;
-        } else {
-            debug!("config file already exists: {:?}", self.path);
-        }
-        Ok(())
+    #[test]
+    fn saved_file_has_current_version() {
+        let (provider, _dir) = temp_provider();
+        provider.save_target_color(&Color::new(1, 2, 3)).unwrap();
+
+        let content = fs::read_to_string(&provider.path).unwrap();
+        assert!(content.starts_with(&format!("version = {CONFIG_VERSION}\n")));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
+    #[test]
+    fn saving_keeps_comments_layout_and_unknown_keys() {
+        let (provider, _dir) = temp_provider();
+        let content = "\
+# bgclipper settings
+version = 2
+target_color = 'teal'  # slide background
+colour = \"red\"
 
-    fn temp_provider() -> (TomlConfigProvider, tempfile::TempDir) {
-        let dir = tempfile::tempdir().expect("failed to create temp dir");
-        let path = dir.path().join("config.toml");
-        (TomlConfigProvider::with_path(path), dir)
+# How close a pixel must be
+[tolerance]
+metric = \"cie76\"
+threshold = 2.0  # tuned for screenshots
+
+[profiles.dark]
+target_color = \"#202020\"
+";
+        fs::write(&provider.path, content).unwrap();
+
+        provider
+            .save_tolerance(&Tolerance::new(DistanceMetric::Cie76, 5.0).unwrap())
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&provider.path).unwrap(),
+            content.replace("threshold = 2.0", "threshold = 5.0")
+        );
     }
 
     #[test]
-    fn load_returns_default_when_file_missing() {
+    fn saving_adds_new_sections_after_existing_ones() {
         let (provider, _dir) = temp_provider();
-        let color = provider.load_target_color().unwrap();
-        assert_eq!(color, Color::default());
+        let content = "\
+version = 2
+target_color = \"teal\"
+
+# Dark slides
+[profiles.dark]
+target_color = \"#202020\"
+";
+        fs::write(&provider.path, content).unwrap();
+
+        provider
+            .save_post_processing(&PostProcessing {
+                decontaminate_edges: true,
+                crop: None,
+            })
+            .unwrap();
+
+        let saved = fs::read_to_string(&provider.path).unwrap();
+        assert!(saved.starts_with(content), "{saved}");
+        assert!(saved.contains("[post_processing]\ndecontaminate_edges = true\n"));
+        provider.reload().unwrap();
+        assert!(provider.load_post_processing().unwrap().decontaminate_edges);
     }
 
     #[test]
-    fn save_and_load_roundtrip() {
-        let (provider, _dir) = temp_provider();
-        let color = Color::new(100, 150, 200);
-        provider.save_target_color(&color).unwrap();
-        let loaded = provider.load_target_color().unwrap();
-        assert_eq!(loaded, color);
+    fn saving_replaces_the_file_without_leftovers() {
+        let (provider, dir) = temp_provider();
+        provider.save_target_color(&Color::new(1, 2, 3)).unwrap();
+        provider.save_target_color(&Color::new(4, 5, 6)).unwrap();
+
+        let names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec!["config.toml"]);
     }
 
+    #[cfg(unix)]
     #[test]
-    fn save_creates_parent_directories() {
-        let dir = tempfile::tempdir().expect("failed to create temp dir");
-        let path = dir.path().join("nested").join("deep").join("config.toml");
-        let provider = TomlConfigProvider::with_path(path.clone());
+    fn saving_does_not_write_through_a_planted_temp_file() {
+        let (provider, dir) = temp_provider();
+        let victim = dir.path().join("victim");
+        fs::write(&victim, "untouched").unwrap();
+        std::os::unix::fs::symlink(&victim, dir.path().join(".config.toml.tmp")).unwrap();
 
-        provider.save_target_color(&Color::default()).unwrap();
+        provider.save_target_color(&Color::new(1, 2, 3)).unwrap();
 
-        assert!(path.exists());
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "untouched");
+        assert_eq!(provider.load_target_color().unwrap(), Color::new(1, 2, 3));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
     }
 
+    #[cfg(unix)]
     #[test]
-    fn load_returns_error_for_malformed_toml() {
-        let (provider, _dir) = temp_provider();
-        fs::write(&provider.path, "not valid toml [[[").unwrap();
+    fn saved_file_and_created_directories_are_private() {
+        use std::os::unix::fs::PermissionsExt;
 
-        let result = provider.load_target_color();
-        assert!(result.is_err());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bgclipper").join("config.toml");
+        fs::create_dir(dir.path().join("bgclipper")).unwrap();
+        fs::write(&path, "version = 2\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o666)).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        TomlConfigProvider::with_path(path.clone())
+            .save_target_color(&Color::new(1, 2, 3))
+            .unwrap();
+        assert_eq!(mode(&path), 0o600);
+
+        let nested = dir.path().join("new").join("config.toml");
+        TomlConfigProvider::with_path(nested.clone())
+            .save_target_color(&Color::new(1, 2, 3))
+            .unwrap();
+        assert_eq!(mode(&nested), 0o600);
+        assert_eq!(mode(&dir.path().join("new")), 0o700);
     }
 
+    #[cfg(unix)]
     #[test]
-    fn saved_file_is_valid_toml() {
-        let (provider, _dir) = temp_provider();
-        let color = Color::new(10, 20, 30);
-        provider.save_target_color(&color).unwrap();
+    fn shared_config_directory_is_made_private() {
+        use std::os::unix::fs::PermissionsExt;
 
-        let content = fs::read_to_string(&provider.path).unwrap();
-        let config: ConfigFile = toml::from_str(&content).unwrap();
-        assert_eq!(config.target_color.r, 10);
-        assert_eq!(config.target_color.g, 20);
-        assert_eq!(config.target_color.b, 30);
+        let dir = tempfile::tempdir().unwrap();
+        let shared = dir.path().join("shared");
+        fs::create_dir(&shared).unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o777)).unwrap();
+
+        TomlConfigProvider::with_path(shared.join("config.toml"))
+            .save_target_color(&Color::new(1, 2, 3))
+            .unwrap();
+
+        let mode = fs::metadata(&shared).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o700);
     }
 
+    #[cfg(unix)]
     #[test]
-    fn overwrite_existing_config() {
+    fn saving_writes_through_a_symlinked_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let dotfiles = dir.path().join("dotfiles");
+        fs::create_dir(&dotfiles).unwrap();
+        let target = dotfiles.join("bgclipper.toml");
+        fs::write(&target, "version = 2\n").unwrap();
+        let link = dir.path().join("config.toml");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        TomlConfigProvider::with_path(link.clone())
+            .save_target_color(&Color::new(1, 2, 3))
+            .unwrap();
+
+        assert!(
+            fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink()
+        );
+        assert_eq!(
+            TomlConfigProvider::with_path(target)
+                .load_target_color()
+                .unwrap(),
+            Color::new(1, 2, 3)
+        );
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn upgrade_keeps_comments() {
         let (provider, _dir) = temp_provider();
+        fs::write(&provider.path, "# my color\ntarget_color = \"teal\"\n").unwrap();
+
+        provider.load_target_color().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&provider.path).unwrap(),
+            format!("version = {CONFIG_VERSION}\n# my color\ntarget_color = \"teal\"\n")
+        );
+    }
+
+    #[test]
+    fn xdg_paths_ignore_relative_directories() {
+        let vars = |home: &'static str, dirs: &'static str| {
+            move |name: &str| match name {
+                "XDG_CONFIG_HOME" => Some(OsString::from(home)),
+                "XDG_CONFIG_DIRS" => Some(OsString::from(dirs)),
+                _ => None,
+            }
+        };
+
+        let paths = XdgPaths::from_vars(vars("/cfg", "/opt/xdg:etc:/etc/xdg"), None);
+        assert_eq!(
+            paths.user,
+            Some(PathBuf::from("/cfg/bgclipper/config.toml"))
+        );
+        assert_eq!(
+            paths.system,
+            vec![
+                PathBuf::from("/opt/xdg/bgclipper/config.toml"),
+                PathBuf::from("/etc/xdg/bgclipper/config.toml"),
+            ]
+        );
+
+        let paths = XdgPaths::from_vars(vars("cfg", ""), Some(Path::new("/home/me")));
+        assert_eq!(
+            paths.user,
+            Some(PathBuf::from("/home/me/.config/bgclipper/config.toml"))
+        );
+        assert_eq!(
+            paths.system,
+            vec![PathBuf::from("/etc/xdg/bgclipper/config.toml")]
+        );
+        assert_eq!(XdgPaths::from_vars(|_| None, None).user, None);
+    }
+
+    /// A provider whose config file is missing, with a system-wide fallback.
+    fn provider_with_fallback(system: &str) -> (TomlConfigProvider, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let fallback = dir.path().join("xdg").join("config.toml");
+        fs::create_dir(dir.path().join("xdg")).unwrap();
+        fs::write(&fallback, system).unwrap();
+        let provider = TomlConfigProvider::with_path(dir.path().join("user").join("config.toml"))
+            .with_fallbacks(vec![dir.path().join("missing.toml"), fallback]);
+        (provider, dir)
+    }
+
+    #[test]
+    fn system_file_applies_until_a_setting_is_saved() {
+        let system = "version = 2\n# Company slides\ntarget_color = \"teal\"\n";
+        let (provider, dir) = provider_with_fallback(system);
+
+        provider.ensure_config_exists().unwrap();
+        assert!(!provider.path().exists());
+        assert_eq!(
+            provider.effective_path(),
+            Some(&*dir.path().join("xdg/config.toml"))
+        );
+        assert_eq!(
+            provider.load_target_color().unwrap(),
+            Color::new(0, 128, 128)
+        );
 
-        provider.save_target_color(&Color::new(0, 0, 0)).unwrap();
         provider
-            .save_target_color(&Color::new(255, 128, 64))
+            .save_alpha_matching(AlphaMatching::OpaqueOnly)
             .unwrap();
 
-        let loaded = provider.load_target_color().unwrap();
-        assert_eq!(loaded, Color::new(255, 128, 64));
+        assert_eq!(provider.effective_path(), Some(provider.path()));
+        let saved = fs::read_to_string(provider.path()).unwrap();
+        assert!(saved.starts_with(system), "{saved}");
+        assert!(
+            saved.contains("alpha_matching = \"opaque_only\""),
+            "{saved}"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("xdg/config.toml")).unwrap(),
+            system
+        );
     }
 
     #[test]
-    fn ensure_config_exists_creates_file_when_missing() {
+    fn reload_follows_the_system_file_in_use() {
+        let (provider, dir) = provider_with_fallback("version = 2\ntarget_color = \"teal\"\n");
+        assert_eq!(
+            provider.load_target_color().unwrap(),
+            Color::new(0, 128, 128)
+        );
+
+        fs::write(
+            dir.path().join("xdg/config.toml"),
+            "version = 2\ntarget_color = \"black\"\n",
+        )
+        .unwrap();
+        assert!(provider.reload().unwrap());
+        assert_eq!(provider.load_target_color().unwrap(), Color::new(0, 0, 0));
+        assert!(!provider.reload().unwrap());
+
+        fs::create_dir(dir.path().join("user")).unwrap();
+        fs::write(provider.path(), "version = 2\ntarget_color = \"navy\"\n").unwrap();
+        assert!(provider.reload().unwrap());
+        assert_eq!(provider.load_target_color().unwrap(), Color::new(0, 0, 128));
+    }
+
+    #[test]
+    fn old_system_file_is_upgraded_in_memory_only() {
+        let system = "target_color = \"teal\"\n";
+        let (provider, dir) = provider_with_fallback(system);
+
+        assert_eq!(
+            provider.load_target_color().unwrap(),
+            Color::new(0, 128, 128)
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("xdg/config.toml")).unwrap(),
+            system
+        );
+        assert!(!provider.path().exists());
+    }
+
+    #[test]
+    fn ensure_file_copies_the_settings_in_effect() {
+        let (provider, _dir) = provider_with_fallback("version = 2\ntarget_color = \"teal\"\n");
+        provider.ensure_file().unwrap();
+        assert_eq!(
+            fs::read_to_string(provider.path()).unwrap(),
+            "version = 2\ntarget_color = \"teal\"\n"
+        );
+
         let (provider, _dir) = temp_provider();
-        assert!(!provider.path.exists());
+        assert_eq!(provider.effective_path(), None);
+        provider.ensure_file().unwrap();
+        assert_eq!(
+            parse_config(&fs::read_to_string(provider.path()).unwrap())
+                .unwrap()
+                .version,
+            CONFIG_VERSION
+        );
+    }
 
-        provider.ensure_config_exists().unwrap();
+    #[test]
+    fn reset_moves_the_file_aside() {
+        let (provider, dir) = temp_provider();
+        assert_eq!(provider.reset().unwrap(), None);
 
-        assert!(provider.path.exists());
-        let loaded = provider.load_target_color().unwrap();
-        assert_eq!(loaded, Color::default());
+        provider.save_target_color(&Color::new(1, 2, 3)).unwrap();
+        let backup = provider.reset().unwrap().unwrap();
+
+        assert_eq!(backup, dir.path().join("config.toml.bak"));
+        assert!(fs::read_to_string(backup).unwrap().contains("r = 1"));
+        assert!(!provider.path().exists());
+        assert_eq!(provider.load_target_color().unwrap(), Color::default());
     }
 
     #[test]
-    fn ensure_config_exists_does_not_overwrite() {
+    fn newer_or_malformed_version_is_rejected() {
         let (provider, _dir) = temp_provider();
-        let custom = Color::new(10, 20, 30);
-        provider.save_target_color(&custom).unwrap();
+        fs::write(&provider.path, "version = 99\n").unwrap();
+        let err = provider.load_target_color().unwrap_err();
+        assert!(matches!(&err, ConfigError::Invalid(msg) if msg.contains("newer")));
 
-        provider.ensure_config_exists().unwrap();
+        fs::write(&provider.path, "version = \"two\"\n").unwrap();
+        assert!(matches!(
+            provider.load_target_color(),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
 
-        let loaded = provider.load_target_color().unwrap();
-        assert_eq!(loaded, custom);
+    #[test]
+    fn unknown_keys_are_ignored_with_their_path() {
+        let (provider, _dir) = temp_provider();
+        let content = "\
+version = 2
+colour = \"red\"
+target_color = \"teal\"
+
+[tolerance]
+threshold = 2.0
+treshold = 3.0
+
+[[target_colors]]
+r = 0
+g = 128
+b = 128
+alpha = 1
+
+[profiles.dark.flood]
+seed = [0, 0]
+";
+        fs::write(&provider.path, content).unwrap();
+
+        let document = parse_document(content).unwrap();
+        assert_eq!(
+            unknown_keys(&document),
+            vec![
+                "colour",
+                "tolerance.treshold",
+                "target_colors[0].alpha",
+                "profiles.dark.flood.seed",
+            ]
+        );
+        assert_eq!(
+            provider.load_target_color().unwrap(),
+            Color::new(0, 128, 128)
+        );
+    }
+
+    fn full_settings() -> SettingsConfig {
+        let tolerance = || ToleranceConfig {
+            metric: MetricConfig::Cie76,
+            threshold: 1.0,
+            outer_threshold: Some(2.0),
+        };
+        SettingsConfig {
+            mode: ModeConfig::Flood,
+            target_color: ColorConfig::Rgb {
+                r: 1,
+                g: 2,
+                b: 3,
+                a: Some(4),
+            },
+            target_colors: Some(vec![TargetColorConfig {
+                r: 1,
+                g: 2,
+                b: 3,
+                a: Some(4),
+                tolerance: Some(tolerance()),
+            }]),
+            auto_detect: Some(AutoDetectConfig {
+                min_confidence: 0.5,
+            }),
+            tolerance: Some(tolerance()),
+            alpha_matching: Some(AlphaMatchingConfig::OpaqueOnly),
+            post_processing: Some(PostProcessingConfig {
+                decontaminate_edges: true,
+                crop: Some(CropConfig { padding: 1 }),
+            }),
+            flood: Some(FloodConfig {
+                connectivity: 8,
+                seeds: Some(vec![(1, 2)]),
+            }),
+        }
+    }
+
+    #[test]
+    fn schema_knows_every_written_key() {
+        let config = ConfigFile {
+            version: CONFIG_VERSION,
+            active_profile: Some("full".to_string()),
+            settings: full_settings(),
+            profiles: BTreeMap::from([("full".to_string(), full_settings())]),
+        };
+
+        let document = parse_document(&toml::to_string(&config).unwrap()).unwrap();
+
+        assert!(
+            unknown_keys(&document).is_empty(),
+            "{:?}",
+            unknown_keys(&document)
+        );
     }
 }
@@ -0,0 +1,306 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use crate::domain::port::{ClipboardPort, ImageData, PNG_MIME_TYPE};
+use crate::domain::representation::Representation;
+use crate::infrastructure::image_file::{self, ImageFileError};
+
+/// File name written by [`DirectoryClipboard`] when the directory holds no
+/// PNG yet.
+const DEFAULT_FILE_NAME: &str = "clipboard.png";
+
+/// A clipboard backed by a directory, for headless use and CI.
+///
+/// The newest `.png` file directly inside the directory (by modification
+/// time) is the clipboard content, so dropping a screenshot into the folder
+/// is the equivalent of copying it. Writing replaces that file in place,
+/// atomically; an empty directory gets a `clipboard.png`. The files are also
+/// offered as encoded `image/png`, so their metadata can be kept.
+///
+/// The change counter increases whenever the newest file is replaced or
+/// modified. Pair it with a
+/// [`PollingWatcher`](crate::infrastructure::watcher::PollingWatcher) to
+/// process new files as they arrive.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bgclipper::application::clipboard_service::ClipboardService;
+/// use bgclipper::infrastructure::config::TomlConfigProvider;
+/// use bgclipper::infrastructure::directory_clipboard::DirectoryClipboard;
+///
+/// let clipboard = DirectoryClipboard::new("/home/me/Screenshots");
+/// let config = TomlConfigProvider::new().unwrap();
+/// let service = ClipboardService::new(clipboard, config);
+/// service.process_clipboard()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct DirectoryClipboard {
+    dir: PathBuf,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    change_count: u64,
+    /// The newest file when the counter was last read or written.
+    newest: Option<Snapshot>,
+}
+
+/// Identity of a file version, compared to detect changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Snapshot {
+    path: PathBuf,
+    modified: SystemTime,
+    len: u64,
+}
+
+impl DirectoryClipboard {
+    /// Creates a clipboard backed by the PNG files in `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Returns the backing directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Finds the most recently modified PNG file; ties go to the later name.
+    fn newest(&self) -> Result<Option<Snapshot>, ImageFileError> {
+        let mut newest: Option<Snapshot> = None;
+        for path in image_file::collect_png_files(std::slice::from_ref(&self.dir))? {
+            let metadata = fs::metadata(&path)?;
+            let snapshot = Snapshot {
+                path,
+                modified: metadata.modified()?,
+                len: metadata.len(),
+            };
+            if newest
+                .as_ref()
+                .is_none_or(|n| (snapshot.modified, &snapshot.path) >= (n.modified, &n.path))
+            {
+                newest = Some(snapshot);
+            }
+        }
+        Ok(newest)
+    }
+
+    /// Replaces the newest PNG (or creates `clipboard.png`) with the file
+    /// `write` creates at the given temporary path.
+    fn replace_newest(
+        &self,
+        write: impl FnOnce(&Path) -> Result<(), ImageFileError>,
+    ) -> Result<(), ImageFileError> {
+        let target = match self.newest()? {
+            Some(newest) => newest.path,
+            None => self.dir.join(DEFAULT_FILE_NAME),
+        };
+        // Write next to the target and rename, so readers never see a partial
+        // file; the dot prefix and extension keep it out of `newest`
+        let file_name = target.file_name().unwrap_or_default().to_string_lossy();
+        let temp = self.dir.join(format!(".{file_name}.tmp"));
+        if let Err(e) = write(&temp).and_then(|()| Ok(fs::rename(&temp, &target)?)) {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+
+        let newest = self.newest()?;
+        let mut state = self.state();
+        state.newest = newest;
+        state.change_count += 1;
+        Ok(())
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ClipboardPort for DirectoryClipboard {
+    type Error = ImageFileError;
+
+    fn change_count(&self) -> Result<u64, Self::Error> {
+        let newest = self.newest()?;
+        let mut state = self.state();
+        if newest != state.newest {
+            state.newest = newest;
+            state.change_count += 1;
+        }
+        Ok(state.change_count)
+    }
+
+    fn get_image(&self) -> Result<Option<ImageData>, Self::Error> {
+        match self.newest()? {
+            Some(newest) => image_file::read_png(&newest.path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn set_image(&self, image: &ImageData) -> Result<(), Self::Error> {
+        self.replace_newest(|path| image_file::write_png(path, image))
+    }
+
+    fn get_encoded(&self, mime_type: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        if mime_type != PNG_MIME_TYPE {
+            return Ok(None);
+        }
+        match self.newest()? {
+            Some(newest) => image_file::read_encoded(&newest.path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes the PNG representation as is, keeping its metadata; other
+    /// representations have no place in a directory of PNG files.
+    fn set_representations(
+        &self,
+        image: &ImageData,
+        representations: &[Representation],
+    ) -> Result<(), Self::Error> {
+        match representations
+            .iter()
+            .find(|r| r.mime_type == PNG_MIME_TYPE)
+        {
+            Some(png) => self.replace_newest(|path| Ok(fs::write(path, &png.data)?)),
+            None => self.set_image(image),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn pixel(value: u8) -> ImageData {
+        ImageData {
+            pixels: vec![value, value, value, 255],
+            width: 1,
+            height: 1,
+        }
+    }
+
+    /// Writes `image` to `path` with a modification time `secs` after the epoch.
+    fn write_at(path: &Path, image: &ImageData, secs: u64) {
+        image_file::write_png(path, image).unwrap();
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn empty_directory_has_no_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let clipboard = DirectoryClipboard::new(dir.path());
+
+        assert_eq!(clipboard.change_count().unwrap(), 0);
+        assert_eq!(clipboard.get_image().unwrap(), None);
+    }
+
+    #[test]
+    fn newest_png_is_the_content() {
+        let dir = tempfile::tempdir().unwrap();
+        write_at(&dir.path().join("b.png"), &pixel(1), 100);
+        write_at(&dir.path().join("a.png"), &pixel(2), 200);
+        fs::write(dir.path().join("notes.txt"), b"newer, not a png").unwrap();
+        let clipboard = DirectoryClipboard::new(dir.path());
+
+        assert_eq!(clipboard.get_image().unwrap(), Some(pixel(2)));
+    }
+
+    #[test]
+    fn counter_changes_only_when_newest_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        write_at(&dir.path().join("a.png"), &pixel(1), 100);
+        let clipboard = DirectoryClipboard::new(dir.path());
+
+        let first = clipboard.change_count().unwrap();
+        assert_eq!(clipboard.change_count().unwrap(), first);
+
+        write_at(&dir.path().join("old.png"), &pixel(2), 50);
+        assert_eq!(clipboard.change_count().unwrap(), first);
+
+        write_at(&dir.path().join("new.png"), &pixel(3), 300);
+        assert_eq!(clipboard.change_count().unwrap(), first + 1);
+    }
+
+    #[test]
+    fn set_image_replaces_newest_file() {
+        let dir = tempfile::tempdir().unwrap();
+        write_at(&dir.path().join("old.png"), &pixel(1), 100);
+        write_at(&dir.path().join("new.png"), &pixel(2), 200);
+        let clipboard = DirectoryClipboard::new(dir.path());
+        let before = clipboard.change_count().unwrap();
+
+        clipboard.set_image(&pixel(9)).unwrap();
+
+        let after = clipboard.change_count().unwrap();
+        assert_eq!(after, before + 1);
+        assert_eq!(clipboard.change_count().unwrap(), after);
+        assert_eq!(
+            image_file::read_png(&dir.path().join("new.png")).unwrap(),
+            pixel(9)
+        );
+        assert_eq!(
+            image_file::read_png(&dir.path().join("old.png")).unwrap(),
+            pixel(1)
+        );
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn set_image_on_empty_directory_creates_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let clipboard = DirectoryClipboard::new(dir.path());
+
+        clipboard.set_image(&pixel(7)).unwrap();
+
+        assert_eq!(
+            image_file::read_png(&dir.path().join(DEFAULT_FILE_NAME)).unwrap(),
+            pixel(7)
+        );
+        assert_eq!(clipboard.get_image().unwrap(), Some(pixel(7)));
+    }
+
+    #[test]
+    fn encoded_access_is_png_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.png");
+        write_at(&path, &pixel(1), 100);
+        let clipboard = DirectoryClipboard::new(dir.path());
+
+        let png = clipboard.get_encoded(PNG_MIME_TYPE).unwrap().unwrap();
+        assert_eq!(png, fs::read(&path).unwrap());
+        assert_eq!(clipboard.get_encoded("image/jpeg").unwrap(), None);
+
+        let html = Representation::new("text/html", b"<img>".to_vec());
+        clipboard
+            .set_representations(&pixel(2), std::slice::from_ref(&html))
+            .unwrap();
+        assert_eq!(image_file::read_png(&path).unwrap(), pixel(2));
+
+        let png = Representation::new(PNG_MIME_TYPE, b"png".to_vec());
+        clipboard
+            .set_representations(&pixel(3), &[html, png])
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"png");
+    }
+
+    #[test]
+    fn missing_directory_is_io_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let clipboard = DirectoryClipboard::new(dir.path().join("missing"));
+
+        assert!(matches!(
+            clipboard.change_count(),
+            Err(ImageFileError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,551 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::history::{HistoryEntry, HistoryImage, HistoryRecord, RecordedImage};
+use crate::domain::port::{HistoryPort, ImageData, PNG_MIME_TYPE};
+use crate::domain::representation::Representation;
+use crate::infrastructure::config::write_private;
+use crate::infrastructure::image_file::{self, ImageFileError};
+
+/// Number of processed images kept by default.
+pub const DEFAULT_HISTORY_LIMIT: usize = 10;
+
+/// Name of the index file in a persistent history directory.
+const INDEX_FILE: &str = "history.toml";
+
+/// Errors that can occur while reading or writing the history.
+#[derive(Debug)]
+pub enum HistoryError {
+    /// Failed to read or write a history file.
+    Io(io::Error),
+    /// Failed to encode or decode a history image.
+    Image(ImageFileError),
+    /// Failed to parse the index file.
+    Parse(toml::de::Error),
+    /// Failed to serialize the index file.
+    Serialize(toml::ser::Error),
+}
+
+impl std::fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HistoryError::Io(e) => write!(f, "history I/O error: {e}"),
+            HistoryError::Image(e) => write!(f, "history image error: {e}"),
+            HistoryError::Parse(e) => write!(f, "history index parse error: {e}"),
+            HistoryError::Serialize(e) => write!(f, "history index serialize error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HistoryError::Io(e) => Some(e),
+            HistoryError::Image(e) => Some(e),
+            HistoryError::Parse(e) => Some(e),
+            HistoryError::Serialize(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for HistoryError {
+    fn from(e: io::Error) -> Self {
+        HistoryError::Io(e)
+    }
+}
+
+impl From<ImageFileError> for HistoryError {
+    fn from(e: ImageFileError) -> Self {
+        HistoryError::Image(e)
+    }
+}
+
+impl From<toml::de::Error> for HistoryError {
+    fn from(e: toml::de::Error) -> Self {
+        HistoryError::Parse(e)
+    }
+}
+
+impl From<toml::ser::Error> for HistoryError {
+    fn from(e: toml::ser::Error) -> Self {
+        HistoryError::Serialize(e)
+    }
+}
+
+/// Serializable index of a persistent history, oldest entry first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IndexFile {
+    #[serde(default)]
+    entries: Vec<IndexEntry>,
+}
+
+/// A history entry without its thumbnail, which is kept as a PNG file.
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexEntry {
+    id: u64,
+    /// Milliseconds since the Unix epoch.
+    timestamp: u64,
+    width: u32,
+    height: u32,
+    changed_pixels: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+}
+
+impl IndexEntry {
+    fn new(entry: &HistoryEntry) -> Self {
+        let timestamp = entry
+            .timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        Self {
+            id: entry.id,
+            timestamp,
+            width: entry.width,
+            height: entry.height,
+            changed_pixels: entry.changed_pixels as u64,
+            profile: entry.profile.clone(),
+        }
+    }
+
+    fn into_entry(self, thumbnail: ImageData) -> HistoryEntry {
+        HistoryEntry {
+            id: self.id,
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_millis(self.timestamp),
+            width: self.width,
+            height: self.height,
+            changed_pixels: self.changed_pixels as usize,
+            profile: self.profile,
+            thumbnail,
+        }
+    }
+}
+
+/// History of processed images, kept in memory or persisted to a directory.
+///
+/// Keeps the last `limit` entries. In memory, the images are held as PNG
+/// data. A persistent history writes each entry's result, original and
+/// thumbnail as PNG files next to a `history.toml` index, and picks them up
+/// again on the next start.
+///
+/// Images recorded as encoded PNG are kept as they are, metadata included,
+/// and returned by [`load_encoded`](HistoryPort::load_encoded). Images
+/// recorded as pixels are encoded as PNG; other formats are refused.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::SystemTime;
+///
+/// use bgclipper::domain::history::{HistoryImage, HistoryRecord, RecordedImage};
+/// use bgclipper::domain::port::{HistoryPort, ImageData};
+/// use bgclipper::infrastructure::history::HistoryStore;
+///
+/// let history = HistoryStore::in_memory(10);
+/// let image = ImageData { pixels: vec![255, 255, 255, 255], width: 1, height: 1 };
+/// let entry = history
+///     .record(HistoryRecord {
+///         timestamp: SystemTime::now(),
+///         width: 1,
+///         height: 1,
+///         changed_pixels: 0,
+///         profile: None,
+///         thumbnail: image.clone(),
+///         result: RecordedImage::Pixels(image.clone()),
+///         original: RecordedImage::Pixels(image.clone()),
+///     })
+///     .unwrap();
+/// assert_eq!(history.entries().unwrap(), vec![entry.clone()]);
+/// assert_eq!(history.load_image(entry.id, HistoryImage::Original).unwrap(), Some(image));
+/// ```
+#[derive(Debug)]
+pub struct HistoryStore {
+    limit: usize,
+    /// Directory the history is persisted to, if any.
+    dir: Option<PathBuf>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    next_id: u64,
+    /// Oldest first.
+    entries: VecDeque<Stored>,
+}
+
+#[derive(Debug)]
+struct Stored {
+    entry: HistoryEntry,
+    /// PNG data of the result and original; `None` if persisted to files.
+    images: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl HistoryStore {
+    /// Creates an empty history held in memory, keeping `limit` entries.
+    pub fn in_memory(limit: usize) -> Self {
+        Self {
+            limit,
+            dir: None,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Opens the history persisted in `dir`, keeping `limit` entries.
+    ///
+    /// The directory is created on the first record. On Unix, it and the
+    /// files in it are only accessible to their owner. Entries whose
+    /// thumbnail cannot be read are dropped with a warning.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index exists but cannot be read or parsed.
+    pub fn persistent(dir: impl Into<PathBuf>, limit: usize) -> Result<Self, HistoryError> {
+        let dir = dir.into();
+        let index: IndexFile = match fs::read_to_string(dir.join(INDEX_FILE)) {
+            Ok(content) => toml::from_str(&content)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => IndexFile::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = VecDeque::new();
+        for entry in index.entries {
+            let path = image_path(&dir, entry.id, "thumbnail");
+            match image_file::read_png(&path) {
+                Ok(thumbnail) => entries.push_back(Stored {
+                    entry: entry.into_entry(thumbnail),
+                    images: None,
+                }),
+                Err(e) => warn!("dropping history entry {}: {e}", entry.id),
+            }
+        }
+        debug!("history loaded from {dir:?}: {} entries", entries.len());
+
+        let state = State {
+            next_id: entries.back().map_or(0, |stored| stored.entry.id + 1),
+            entries,
+        };
+        let store = Self {
+            limit,
+            dir: Some(dir),
+            state: Mutex::new(state),
+        };
+        store.trim(&mut store.state())?;
+        Ok(store)
+    }
+
+    /// Returns the directory the history is persisted to, if any.
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Drops the oldest entries beyond the limit and saves the index.
+    fn trim(&self, state: &mut State) -> Result<(), HistoryError> {
+        while state.entries.len() > self.limit {
+            let Some(old) = state.entries.pop_front() else {
+                break;
+            };
+            if let Some(dir) = &self.dir {
+                for kind in ["result", "original", "thumbnail"] {
+                    let path = image_path(dir, old.entry.id, kind);
+                    if let Err(e) = fs::remove_file(&path)
+                        && e.kind() != io::ErrorKind::NotFound
+                    {
+                        warn!("failed to remove {path:?}: {e}");
+                    }
+                }
+            }
+        }
+
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let index = IndexFile {
+            entries: state
+                .entries
+                .iter()
+                .map(|stored| IndexEntry::new(&stored.entry))
+                .collect(),
+        };
+        write_private(&dir.join(INDEX_FILE), toml::to_string(&index)?)?;
+        Ok(())
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Path of the `kind` image (`result`, `original` or `thumbnail`) of entry `id`.
+fn image_path(dir: &Path, id: u64, kind: &str) -> PathBuf {
+    dir.join(format!("{id}-{kind}.png"))
+}
+
+/// Returns the PNG data of `image`, keeping it as it is if it is already
+/// encoded as PNG.
+fn png_data(image: RecordedImage) -> Result<Vec<u8>, ImageFileError> {
+    match image {
+        RecordedImage::Pixels(pixels) => encode_png(&pixels),
+        RecordedImage::Encoded(encoded) if encoded.mime_type == PNG_MIME_TYPE => Ok(encoded.data),
+        RecordedImage::Encoded(_) => Err(ImageFileError::UnsupportedFormat(None)),
+    }
+}
+
+fn encode_png(image: &ImageData) -> Result<Vec<u8>, ImageFileError> {
+    let mut png = Vec::new();
+    image_file::write_png_to(&mut png, image)?;
+    Ok(png)
+}
+
+/// File name part of the `image` of an entry.
+fn image_kind(image: HistoryImage) -> &'static str {
+    match image {
+        HistoryImage::Result => "result",
+        HistoryImage::Original => "original",
+    }
+}
+
+impl HistoryPort for HistoryStore {
+    type Error = HistoryError;
+
+    fn record(&self, record: HistoryRecord) -> Result<HistoryEntry, Self::Error> {
+        let mut state = self.state();
+        let entry = record.entry(state.next_id);
+        let result = png_data(record.result)?;
+        let original = png_data(record.original)?;
+        let images = match &self.dir {
+            Some(dir) => {
+                let thumbnail = encode_png(&entry.thumbnail)?;
+                write_private(&image_path(dir, entry.id, "result"), result)?;
+                write_private(&image_path(dir, entry.id, "original"), original)?;
+                write_private(&image_path(dir, entry.id, "thumbnail"), thumbnail)?;
+                None
+            }
+            None => Some((result, original)),
+        };
+
+        state.next_id += 1;
+        state.entries.push_back(Stored {
+            entry: entry.clone(),
+            images,
+        });
+        self.trim(&mut state)?;
+        Ok(entry)
+    }
+
+    fn entries(&self) -> Result<Vec<HistoryEntry>, Self::Error> {
+        Ok(self
+            .state()
+            .entries
+            .iter()
+            .rev()
+            .map(|stored| stored.entry.clone())
+            .collect())
+    }
+
+    fn load_image(&self, id: u64, image: HistoryImage) -> Result<Option<ImageData>, Self::Error> {
+        let state = self.state();
+        let Some(stored) = state.entries.iter().find(|stored| stored.entry.id == id) else {
+            return Ok(None);
+        };
+        let loaded = match (&stored.images, &self.dir) {
+            (Some((result, original)), _) => image_file::decode_png(match image {
+                HistoryImage::Result => result,
+                HistoryImage::Original => original,
+            })?,
+            (None, Some(dir)) => image_file::read_png(&image_path(dir, id, image_kind(image)))?,
+            (None, None) => return Ok(None),
+        };
+        Ok(Some(loaded))
+    }
+
+    fn load_encoded(
+        &self,
+        id: u64,
+        image: HistoryImage,
+    ) -> Result<Option<Representation>, Self::Error> {
+        let state = self.state();
+        let Some(stored) = state.entries.iter().find(|stored| stored.entry.id == id) else {
+            return Ok(None);
+        };
+        let png = match (&stored.images, &self.dir) {
+            (Some((result, original)), _) => match image {
+                HistoryImage::Result => result.clone(),
+                HistoryImage::Original => original.clone(),
+            },
+            (None, Some(dir)) => fs::read(image_path(dir, id, image_kind(image)))?,
+            (None, None) => return Ok(None),
+        };
+        Ok(Some(Representation::new(PNG_MIME_TYPE, png)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(value: u8) -> ImageData {
+        ImageData {
+            pixels: vec![value, value, value, 255],
+            width: 1,
+            height: 1,
+        }
+    }
+
+    fn record(value: u8) -> HistoryRecord {
+        HistoryRecord {
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123),
+            width: 1,
+            height: 1,
+            changed_pixels: usize::from(value),
+            profile: Some("logos".to_string()),
+            thumbnail: pixel(value),
+            result: RecordedImage::Pixels(pixel(value)),
+            original: RecordedImage::Pixels(pixel(255 - value)),
+        }
+    }
+
+    fn ids(history: &HistoryStore) -> Vec<u64> {
+        history
+            .entries()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.id)
+            .collect()
+    }
+
+    #[test]
+    fn in_memory_keeps_newest_entries_up_to_limit() {
+        let history = HistoryStore::in_memory(2);
+        for value in 1..=3 {
+            history.record(record(value)).unwrap();
+        }
+
+        assert_eq!(ids(&history), vec![2, 1]);
+        assert_eq!(history.load_image(0, HistoryImage::Result).unwrap(), None);
+        assert_eq!(
+            history.load_image(1, HistoryImage::Result).unwrap(),
+            Some(pixel(2))
+        );
+        assert_eq!(
+            history.load_image(1, HistoryImage::Original).unwrap(),
+            Some(pixel(253))
+        );
+    }
+
+    #[test]
+    fn encoded_png_is_kept_as_recorded() {
+        let history = HistoryStore::in_memory(2);
+        let mut png = encode_png(&pixel(9)).unwrap();
+        png.extend_from_slice(b"trailing metadata");
+        let encoded = Representation::new(PNG_MIME_TYPE, png);
+        history
+            .record(HistoryRecord {
+                result: RecordedImage::Encoded(encoded.clone()),
+                ..record(9)
+            })
+            .unwrap();
+
+        assert_eq!(
+            history.load_encoded(0, HistoryImage::Result).unwrap(),
+            Some(encoded)
+        );
+
+        let other = Representation::new("image/webp", b"RIFF".to_vec());
+        let refused = history.record(HistoryRecord {
+            original: RecordedImage::Encoded(other),
+            ..record(9)
+        });
+        assert!(matches!(refused, Err(HistoryError::Image(_))));
+    }
+
+    #[test]
+    fn persistent_history_survives_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = HistoryStore::persistent(dir.path().join("history"), 5).unwrap();
+        let recorded = history.record(record(7)).unwrap();
+        drop(history);
+
+        let history = HistoryStore::persistent(dir.path().join("history"), 5).unwrap();
+
+        assert_eq!(history.entries().unwrap(), vec![recorded]);
+        assert_eq!(
+            history.load_image(0, HistoryImage::Original).unwrap(),
+            Some(pixel(248))
+        );
+        assert_eq!(history.record(record(8)).unwrap().id, 1);
+    }
+
+    #[test]
+    fn persistent_history_removes_dropped_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = HistoryStore::persistent(dir.path(), 3).unwrap();
+        for value in 1..=3 {
+            history.record(record(value)).unwrap();
+        }
+        drop(history);
+
+        let history = HistoryStore::persistent(dir.path(), 1).unwrap();
+
+        assert_eq!(ids(&history), vec![2]);
+        assert!(!image_path(dir.path(), 0, "result").exists());
+        assert!(!image_path(dir.path(), 1, "thumbnail").exists());
+        assert!(image_path(dir.path(), 2, "original").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn persistent_history_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let history_dir = dir.path().join("history");
+        let history = HistoryStore::persistent(&history_dir, 5).unwrap();
+        history.record(record(7)).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        assert_eq!(mode(&history_dir), 0o700);
+        assert_eq!(mode(&history_dir.join(INDEX_FILE)), 0o600);
+        for kind in ["result", "original", "thumbnail"] {
+            assert_eq!(mode(&image_path(&history_dir, 0, kind)), 0o600);
+        }
+    }
+
+    #[test]
+    fn entries_with_missing_thumbnail_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = HistoryStore::persistent(dir.path(), 5).unwrap();
+        history.record(record(1)).unwrap();
+        history.record(record(2)).unwrap();
+        fs::remove_file(image_path(dir.path(), 0, "thumbnail")).unwrap();
+
+        let history = HistoryStore::persistent(dir.path(), 5).unwrap();
+
+        assert_eq!(ids(&history), vec![1]);
+    }
+
+    #[test]
+    fn missing_directory_is_empty_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = HistoryStore::persistent(dir.path().join("missing"), 5).unwrap();
+
+        assert!(history.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn corrupt_index_is_parse_error() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(INDEX_FILE), "entries = 3").unwrap();
+
+        assert!(matches!(
+            HistoryStore::persistent(dir.path(), 5),
+            Err(HistoryError::Parse(_))
+        ));
+    }
+}
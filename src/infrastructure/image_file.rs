@@ -0,0 +1,296 @@
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+
+use image::{ImageFormat, ImageReader, RgbaImage};
+
+use crate::domain::port::ImageData;
+
+/// Errors that can occur while reading or writing image files.
+#[derive(Debug)]
+pub enum ImageFileError {
+    /// Failed to read or write the file.
+    Io(io::Error),
+    /// Failed to decode or encode the image.
+    Image(image::ImageError),
+    /// The data is not a PNG image. Holds the detected format, if any.
+    UnsupportedFormat(Option<ImageFormat>),
+    /// The encoded data exceeds [`MAX_INPUT_BYTES`].
+    InputTooLarge,
+    /// The image dimensions exceed [`MAX_PIXELS`].
+    TooLarge { width: u32, height: u32 },
+}
+
+impl std::fmt::Display for ImageFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageFileError::Io(e) => write!(f, "image I/O error: {e}"),
+            ImageFileError::Image(e) => write!(f, "image codec error: {e}"),
+            ImageFileError::UnsupportedFormat(Some(format)) => {
+                write!(f, "unsupported image format {format:?}, expected PNG")
+            }
+            ImageFileError::UnsupportedFormat(None) => {
+                write!(f, "unrecognized image format, expected PNG")
+            }
+            ImageFileError::InputTooLarge => {
+                write!(f, "image data exceeds {MAX_INPUT_BYTES} bytes")
+            }
+            ImageFileError::TooLarge { width, height } => write!(
+                f,
+                "image is {width}x{height} pixels, more than the {MAX_PIXELS} allowed"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImageFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImageFileError::Io(e) => Some(e),
+            ImageFileError::Image(e) => Some(e),
+            ImageFileError::UnsupportedFormat(_)
+            | ImageFileError::InputTooLarge
+            | ImageFileError::TooLarge { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImageFileError {
+    fn from(e: io::Error) -> Self {
+        ImageFileError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for ImageFileError {
+    fn from(e: image::ImageError) -> Self {
+        ImageFileError::Image(e)
+    }
+}
+
+/// Largest encoded image accepted, in bytes (256 MiB).
+pub const MAX_INPUT_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Largest image accepted, in pixels (100 megapixels, 400 MB as RGBA).
+pub const MAX_PIXELS: u64 = 100_000_000;
+
+/// Reads a PNG file as RGBA pixel data.
+pub fn read_png(path: &Path) -> Result<ImageData, ImageFileError> {
+    read_png_from(fs::File::open(path)?)
+}
+
+/// Reads a PNG stream, such as standard input, as RGBA pixel data.
+///
+/// At most [`MAX_INPUT_BYTES`] are read; see [`decode_png`] for the checks
+/// made before decoding.
+pub fn read_png_from(reader: impl Read) -> Result<ImageData, ImageFileError> {
+    decode_png(&read_encoded_from(reader)?)
+}
+
+/// Reads an encoded image file without decoding it.
+pub fn read_encoded(path: &Path) -> Result<Vec<u8>, ImageFileError> {
+    read_encoded_from(fs::File::open(path)?)
+}
+
+/// Reads an encoded image stream without decoding it, failing once more
+/// than [`MAX_INPUT_BYTES`] arrive.
+pub fn read_encoded_from(reader: impl Read) -> Result<Vec<u8>, ImageFileError> {
+    read_limited(reader, MAX_INPUT_BYTES)
+}
+
+/// Reads all of `reader`, failing once more than `limit` bytes arrive.
+fn read_limited(reader: impl Read, limit: u64) -> Result<Vec<u8>, ImageFileError> {
+    let mut bytes = Vec::new();
+    reader.take(limit + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > limit {
+        return Err(ImageFileError::InputTooLarge);
+    }
+    Ok(bytes)
+}
+
+/// Decodes PNG data as RGBA pixel data.
+///
+/// The format is detected from the data's signature rather than trusted, and
+/// the dimensions are checked against [`MAX_PIXELS`] from the header before
+/// any pixels are decoded.
+pub fn decode_png(bytes: &[u8]) -> Result<ImageData, ImageFileError> {
+    match image::guess_format(bytes) {
+        Ok(ImageFormat::Png) => {}
+        Ok(format) => return Err(ImageFileError::UnsupportedFormat(Some(format))),
+        Err(_) => return Err(ImageFileError::UnsupportedFormat(None)),
+    }
+
+    let reader = || ImageReader::with_format(Cursor::new(bytes), ImageFormat::Png);
+    let (width, height) = reader().into_dimensions()?;
+    if u64::from(width) * u64::from(height) > MAX_PIXELS {
+        return Err(ImageFileError::TooLarge { width, height });
+    }
+
+    let rgba = reader().decode()?.to_rgba8();
+    Ok(ImageData {
+        pixels: rgba.into_raw(),
+        width,
+        height,
+    })
+}
+
+/// Writes RGBA pixel data to a PNG file, replacing any existing file.
+///
+/// # Panics
+///
+/// Panics if `image.pixels.len()` is not `width * height * 4`.
+pub fn write_png(path: &Path, image: &ImageData) -> Result<(), ImageFileError> {
+    let rgba = RgbaImage::from_raw(image.width, image.height, image.pixels.clone())
+        .expect("pixel buffer length must be width * height * 4");
+    rgba.save_with_format(path, ImageFormat::Png)?;
+    Ok(())
+}
+
+/// Writes RGBA pixel data as a PNG stream, such as standard output.
+///
+/// # Panics
+///
+/// Panics if `image.pixels.len()` is not `width * height * 4`.
+pub fn write_png_to(mut writer: impl Write, image: &ImageData) -> Result<(), ImageFileError> {
+    let rgba = RgbaImage::from_raw(image.width, image.height, image.pixels.clone())
+        .expect("pixel buffer length must be width * height * 4");
+    let mut bytes = Cursor::new(Vec::new());
+    rgba.write_to(&mut bytes, ImageFormat::Png)?;
+    writer.write_all(bytes.get_ref())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Expands `inputs` into the list of PNG files to process.
+///
+/// Files are taken as given; directories contribute the `.png` files
+/// directly inside them (not recursively), in sorted order.
+pub fn collect_png_files(inputs: &[PathBuf]) -> Result<Vec<PathBuf>, ImageFileError> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if !input.is_dir() {
+            files.push(input.clone());
+            continue;
+        }
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(input)? {
+            let path = entry?.path();
+            if path.is_file() && is_png(&path) {
+                entries.push(path);
+            }
+        }
+        entries.sort();
+        files.extend(entries);
+    }
+    Ok(files)
+}
+
+fn is_png(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::png_codec;
+
+    fn sample() -> ImageData {
+        ImageData {
+            pixels: vec![255, 0, 0, 255, 0, 255, 0, 128],
+            width: 2,
+            height: 1,
+        }
+    }
+
+    #[test]
+    fn png_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.png");
+
+        write_png(&path, &sample()).unwrap();
+
+        assert_eq!(read_png(&path).unwrap(), sample());
+    }
+
+    #[test]
+    fn read_missing_file_is_io_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = read_png(&dir.path().join("missing.png"));
+        assert!(matches!(result, Err(ImageFileError::Io(_))));
+    }
+
+    #[test]
+    fn read_non_png_is_unsupported_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fake.png");
+        fs::write(&path, b"not a png").unwrap();
+
+        assert!(matches!(
+            read_png(&path),
+            Err(ImageFileError::UnsupportedFormat(None))
+        ));
+    }
+
+    #[test]
+    fn stream_roundtrip() {
+        let mut bytes = Vec::new();
+        write_png_to(&mut bytes, &sample()).unwrap();
+
+        assert_eq!(read_png_from(bytes.as_slice()).unwrap(), sample());
+    }
+
+    #[test]
+    fn decode_detects_other_formats() {
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F'];
+        assert!(matches!(
+            decode_png(&jpeg),
+            Err(ImageFileError::UnsupportedFormat(Some(ImageFormat::Jpeg)))
+        ));
+    }
+
+    #[test]
+    fn decode_rejects_oversized_dimensions_before_decoding() {
+        let mut bytes = Vec::new();
+        write_png_to(&mut bytes, &sample()).unwrap();
+        // Claim 2^20 x 2^20 pixels in the IHDR chunk; the image data is far
+        // too short for that, so decoding would fail if it were attempted
+        bytes[16..20].copy_from_slice(&(1u32 << 20).to_be_bytes());
+        bytes[20..24].copy_from_slice(&(1u32 << 20).to_be_bytes());
+        let crc = png_codec::crc32(&bytes[12..29]);
+        bytes[29..33].copy_from_slice(&crc.to_be_bytes());
+
+        assert!(matches!(
+            decode_png(&bytes),
+            Err(ImageFileError::TooLarge {
+                width: 1_048_576,
+                height: 1_048_576
+            })
+        ));
+    }
+
+    #[test]
+    fn read_limited_rejects_oversized_input() {
+        assert_eq!(read_limited(&[1u8, 2][..], 2).unwrap(), vec![1, 2]);
+        assert!(matches!(
+            read_limited(io::repeat(0), 2),
+            Err(ImageFileError::InputTooLarge)
+        ));
+    }
+
+    #[test]
+    fn collect_expands_directories_to_sorted_pngs() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.png", "a.PNG", "notes.txt"] {
+            fs::write(dir.path().join(name), b"").unwrap();
+        }
+        fs::create_dir(dir.path().join("nested.png")).unwrap();
+        let explicit = PathBuf::from("explicit.png");
+
+        let files = collect_png_files(&[explicit.clone(), dir.path().to_path_buf()]).unwrap();
+
+        assert_eq!(
+            files,
+            vec![explicit, dir.path().join("a.PNG"), dir.path().join("b.png")]
+        );
+    }
+}
@@ -0,0 +1,573 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::domain::background::AutoDetect;
+use crate::domain::color::{Color, ParseColorError, Rgba};
+use crate::domain::distance::{AlphaRamp, Tolerance};
+use crate::domain::flood_fill::RemovalMode;
+use crate::domain::image_processor::PostProcessing;
+use crate::domain::port::ConfigPort;
+use crate::domain::profile::Profile;
+use crate::domain::target::{AlphaMatching, TargetColor};
+use crate::infrastructure::config::{ConfigError, TomlConfigProvider};
+
+/// Environment variable naming the config file to use.
+pub const CONFIG_VAR: &str = "BGCLIPPER_CONFIG";
+
+/// Environment variable holding a target color that replaces the configured
+/// ones.
+pub const TARGET_COLOR_VAR: &str = "BGCLIPPER_TARGET_COLOR";
+
+/// An environment variable that could not be used.
+#[derive(Debug)]
+pub enum OverrideError {
+    /// The variable is not valid Unicode.
+    NotUnicode(&'static str),
+    /// The variable is not a color.
+    Color {
+        /// Name of the variable.
+        var: &'static str,
+        /// Its value.
+        value: String,
+        /// Why it is not a color.
+        error: ParseColorError,
+    },
+}
+
+impl std::fmt::Display for OverrideError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OverrideError::NotUnicode(var) => write!(f, "{var} is not valid Unicode"),
+            OverrideError::Color { var, value, error } => {
+                write!(f, "{var}: invalid color {value:?}: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for OverrideError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OverrideError::NotUnicode(_) => None,
+            OverrideError::Color { error, .. } => Some(error),
+        }
+    }
+}
+
+/// Configuration given on the command line or in the environment, which
+/// takes precedence over the config file.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::PathBuf;
+///
+/// use bgclipper::domain::color::Rgba;
+/// use bgclipper::infrastructure::layered_config::ConfigOverrides;
+///
+/// let env = ConfigOverrides::from_vars(|var| match var {
+///     "BGCLIPPER_CONFIG" => Some("/etc/bgclipper.toml".into()),
+///     "BGCLIPPER_TARGET_COLOR" => Some("white".into()),
+///     _ => None,
+/// })
+/// .unwrap();
+/// let cli = ConfigOverrides {
+///     path: Some(PathBuf::from("ci.toml")),
+///     ..ConfigOverrides::default()
+/// };
+///
+/// let overrides = cli.over(env);
+/// assert_eq!(overrides.path, Some(PathBuf::from("ci.toml")));
+/// assert_eq!(overrides.target_colors, vec![Rgba::new(255, 255, 255, 255)]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigOverrides {
+    /// Config file to use instead of the default one.
+    pub path: Option<PathBuf>,
+    /// Target colors replacing the configured target colors and
+    /// auto-detection; empty to keep the configured ones.
+    pub target_colors: Vec<Rgba>,
+}
+
+impl ConfigOverrides {
+    /// Reads [`BGCLIPPER_CONFIG`](CONFIG_VAR) and
+    /// [`BGCLIPPER_TARGET_COLOR`](TARGET_COLOR_VAR) from the environment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the target color is not a color.
+    pub fn from_env() -> Result<Self, OverrideError> {
+        Self::from_vars(|name| std::env::var_os(name))
+    }
+
+    /// Reads the overrides from the variables `var` returns. Empty variables
+    /// count as unset.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the target color is not a color.
+    pub fn from_vars(var: impl Fn(&str) -> Option<OsString>) -> Result<Self, OverrideError> {
+        let set = |name| var(name).filter(|value| !value.is_empty());
+        let target_colors = match set(TARGET_COLOR_VAR) {
+            Some(value) => {
+                let value = value
+                    .into_string()
+                    .map_err(|_| OverrideError::NotUnicode(TARGET_COLOR_VAR))?;
+                let color =
+                    Rgba::parse_with_bare_hex(&value).map_err(|error| OverrideError::Color {
+                        var: TARGET_COLOR_VAR,
+                        value: value.clone(),
+                        error,
+                    })?;
+                vec![color]
+            }
+            None => Vec::new(),
+        };
+        Ok(Self {
+            path: set(CONFIG_VAR).map(PathBuf::from),
+            target_colors,
+        })
+    }
+
+    /// Layers `self` over `lower`: each value set in `self` wins, the others
+    /// come from `lower`.
+    pub fn over(self, lower: Self) -> Self {
+        Self {
+            path: self.path.or(lower.path),
+            target_colors: if self.target_colors.is_empty() {
+                lower.target_colors
+            } else {
+                self.target_colors
+            },
+        }
+    }
+
+    /// Replaces the target colors of `profile` with the override, if any.
+    pub fn apply(&self, mut profile: Profile) -> Profile {
+        if !self.target_colors.is_empty() {
+            profile.targets = self
+                .target_colors
+                .iter()
+                .copied()
+                .map(TargetColor::from)
+                .collect();
+            profile.auto_detect = None;
+        }
+        profile
+    }
+}
+
+/// A [`ConfigPort`] that layers [`ConfigOverrides`] over another one,
+/// usually the config file.
+///
+/// Settings are read from the overrides where set, falling back to the
+/// inner config, which falls back to the defaults. Everything saved goes to
+/// the inner config, so the overrides are never written to the file and
+/// stay in effect until the process exits.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bgclipper::domain::port::ConfigPort;
+/// use bgclipper::infrastructure::layered_config::{ConfigOverrides, LayeredConfig};
+///
+/// let config = LayeredConfig::open(ConfigOverrides::from_env()?).unwrap();
+/// println!("removing {:?}", config.load_settings()?.targets);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct LayeredConfig<C> {
+    inner: C,
+    overrides: ConfigOverrides,
+}
+
+impl<C: ConfigPort> LayeredConfig<C> {
+    /// Layers `overrides` over `inner`. The override path is not used; see
+    /// [`open`](LayeredConfig::open).
+    pub fn new(inner: C, overrides: ConfigOverrides) -> Self {
+        Self { inner, overrides }
+    }
+
+    /// Returns the config the overrides are layered over.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Returns the overrides layered over the inner config.
+    pub fn overrides(&self) -> &ConfigOverrides {
+        &self.overrides
+    }
+
+    /// Returns `true` if the overrides replace any setting of the inner
+    /// config.
+    pub fn is_overridden(&self) -> bool {
+        !self.overrides.target_colors.is_empty()
+    }
+}
+
+impl LayeredConfig<TomlConfigProvider> {
+    /// Opens the config file given by `overrides`, or the platform-default
+    /// one, with the overrides layered over it.
+    ///
+    /// Returns `None` if no path is given and the platform config directory
+    /// cannot be determined.
+    pub fn open(overrides: ConfigOverrides) -> Option<Self> {
+        let file = match &overrides.path {
+            Some(path) => TomlConfigProvider::with_path(path.clone()),
+            None => TomlConfigProvider::new()?,
+        };
+        Some(Self::new(file, overrides))
+    }
+
+    /// Returns the path of the config file.
+    pub fn path(&self) -> &Path {
+        self.inner.path()
+    }
+
+    /// Returns the file the settings come from, if any; see
+    /// [`TomlConfigProvider::effective_path`].
+    pub fn effective_path(&self) -> Option<&Path> {
+        self.inner.effective_path()
+    }
+
+    /// Returns the content of the file in effect, or the default config, with
+    /// the overrides applied to its active settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, or cannot be parsed when
+    /// there are overrides to apply.
+    pub fn effective_content(&self) -> Result<String, ConfigError> {
+        if self.is_overridden() {
+            self.inner.effective_content_with(&self.load_settings()?)
+        } else {
+            self.inner.effective_content()
+        }
+    }
+}
+
+impl<C: ConfigPort> ConfigPort for LayeredConfig<C> {
+    type Error = C::Error;
+
+    fn load_settings(&self) -> Result<Profile, Self::Error> {
+        self.inner
+            .load_settings()
+            .map(|profile| self.overrides.apply(profile))
+    }
+
+    fn save_settings(&self, settings: &Profile) -> Result<(), Self::Error> {
+        self.inner.save_settings(settings)
+    }
+
+    fn load_target_color(&self) -> Result<Color, Self::Error> {
+        match self.overrides.target_colors.first() {
+            Some(color) => Ok(color.rgb()),
+            None => self.inner.load_target_color(),
+        }
+    }
+
+    fn save_target_color(&self, color: &Color) -> Result<(), Self::Error> {
+        self.inner.save_target_color(color)
+    }
+
+    fn load_target_colors(&self) -> Result<Vec<TargetColor>, Self::Error> {
+        if self.is_overridden() {
+            Ok(self.overrides.apply(Profile::default()).targets)
+        } else {
+            self.inner.load_target_colors()
+        }
+    }
+
+    fn save_target_colors(&self, targets: &[TargetColor]) -> Result<(), Self::Error> {
+        self.inner.save_target_colors(targets)
+    }
+
+    fn load_auto_detect(&self) -> Result<Option<AutoDetect>, Self::Error> {
+        if self.is_overridden() {
+            Ok(None)
+        } else {
+            self.inner.load_auto_detect()
+        }
+    }
+
+    fn save_auto_detect(&self, auto: Option<&AutoDetect>) -> Result<(), Self::Error> {
+        self.inner.save_auto_detect(auto)
+    }
+
+    fn load_tolerance(&self) -> Result<Tolerance, Self::Error> {
+        self.inner.load_tolerance()
+    }
+
+    fn save_tolerance(&self, tolerance: &Tolerance) -> Result<(), Self::Error> {
+        self.inner.save_tolerance(tolerance)
+    }
+
+    fn load_alpha_ramp(&self) -> Result<Option<AlphaRamp>, Self::Error> {
+        self.inner.load_alpha_ramp()
+    }
+
+    fn save_alpha_ramp(&self, ramp: Option<&AlphaRamp>) -> Result<(), Self::Error> {
+        self.inner.save_alpha_ramp(ramp)
+    }
+
+    fn load_post_processing(&self) -> Result<PostProcessing, Self::Error> {
+        self.inner.load_post_processing()
+    }
+
+    fn save_post_processing(&self, post: &PostProcessing) -> Result<(), Self::Error> {
+        self.inner.save_post_processing(post)
+    }
+
+    fn load_removal_mode(&self) -> Result<RemovalMode, Self::Error> {
+        self.inner.load_removal_mode()
+    }
+
+    fn save_removal_mode(&self, mode: &RemovalMode) -> Result<(), Self::Error> {
+        self.inner.save_removal_mode(mode)
+    }
+
+    fn load_alpha_matching(&self) -> Result<AlphaMatching, Self::Error> {
+        self.inner.load_alpha_matching()
+    }
+
+    fn save_alpha_matching(&self, matching: AlphaMatching) -> Result<(), Self::Error> {
+        self.inner.save_alpha_matching(matching)
+    }
+
+    fn list_profiles(&self) -> Result<Vec<String>, Self::Error> {
+        self.inner.list_profiles()
+    }
+
+    fn load_profile(&self, name: &str) -> Result<Option<Profile>, Self::Error> {
+        Ok(self
+            .inner
+            .load_profile(name)?
+            .map(|profile| self.overrides.apply(profile)))
+    }
+
+    fn save_profile(&self, name: &str, profile: &Profile) -> Result<(), Self::Error> {
+        self.inner.save_profile(name, profile)
+    }
+
+    fn load_active_profile(&self) -> Result<Option<String>, Self::Error> {
+        self.inner.load_active_profile()
+    }
+
+    fn save_active_profile(&self, name: Option<&str>) -> Result<(), Self::Error> {
+        self.inner.save_active_profile(name)
+    }
+
+    fn ensure_config_exists(&self) -> Result<(), Self::Error> {
+        self.inner.ensure_config_exists()
+    }
+
+    fn reload(&self) -> Result<bool, Self::Error> {
+        self.inner.reload()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use crate::domain::distance::DistanceMetric;
+
+    fn vars(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<OsString> {
+        let pairs: Vec<(String, OsString)> = pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), OsString::from(value)))
+            .collect();
+        move |name| {
+            pairs
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value.clone())
+        }
+    }
+
+    fn layered(
+        file: &str,
+        overrides: ConfigOverrides,
+    ) -> (LayeredConfig<TomlConfigProvider>, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, file).unwrap();
+        let overrides = ConfigOverrides {
+            path: Some(path),
+            ..overrides
+        };
+        (LayeredConfig::open(overrides).unwrap(), dir)
+    }
+
+    #[test]
+    fn reads_overrides_from_variables() {
+        let overrides = ConfigOverrides::from_vars(vars(&[
+            (CONFIG_VAR, "/tmp/bg.toml"),
+            (TARGET_COLOR_VAR, " #00ff0080 "),
+        ]))
+        .unwrap();
+
+        assert_eq!(overrides.path, Some(PathBuf::from("/tmp/bg.toml")));
+        assert_eq!(overrides.target_colors, vec![Rgba::new(0, 255, 0, 128)]);
+    }
+
+    #[test]
+    fn target_color_accepts_bare_hex_like_the_command_line() {
+        let overrides = ConfigOverrides::from_vars(vars(&[(TARGET_COLOR_VAR, "ffffff")])).unwrap();
+        assert_eq!(overrides.target_colors, vec![Rgba::new(255, 255, 255, 255)]);
+    }
+
+    #[test]
+    fn empty_variables_are_unset() {
+        let overrides =
+            ConfigOverrides::from_vars(vars(&[(CONFIG_VAR, ""), (TARGET_COLOR_VAR, "")])).unwrap();
+        assert_eq!(overrides, ConfigOverrides::default());
+    }
+
+    #[test]
+    fn invalid_color_names_the_variable() {
+        let err = ConfigOverrides::from_vars(vars(&[(TARGET_COLOR_VAR, "nope")])).unwrap_err();
+        assert!(
+            err.to_string().starts_with("BGCLIPPER_TARGET_COLOR: "),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn upper_layer_wins_where_set() {
+        let cli = ConfigOverrides {
+            path: Some(PathBuf::from("cli.toml")),
+            target_colors: Vec::new(),
+        };
+        let env = ConfigOverrides {
+            path: Some(PathBuf::from("env.toml")),
+            target_colors: vec![Rgba::new(1, 2, 3, 255)],
+        };
+
+        assert_eq!(
+            cli.clone().over(env.clone()),
+            ConfigOverrides {
+                path: Some(PathBuf::from("cli.toml")),
+                target_colors: vec![Rgba::new(1, 2, 3, 255)],
+            }
+        );
+
+        let colors = ConfigOverrides {
+            target_colors: vec![Rgba::new(4, 5, 6, 255), Rgba::new(7, 8, 9, 255)],
+            ..cli
+        };
+        assert_eq!(colors.clone().over(env).target_colors, colors.target_colors);
+    }
+
+    #[test]
+    fn target_color_override_replaces_file_targets() {
+        let file = "\
+version = 2
+active_profile = \"dark\"
+
+[auto_detect]
+
+[tolerance]
+threshold = 4.0
+
+[profiles.dark]
+target_color = \"black\"
+
+[profiles.dark.tolerance]
+threshold = 3.0
+";
+        let overrides = ConfigOverrides {
+            target_colors: vec![Rgba::new(0, 128, 128, 255)],
+            ..ConfigOverrides::default()
+        };
+        let (config, _dir) = layered(file, overrides);
+
+        let settings = config.load_settings().unwrap();
+        assert_eq!(
+            settings.targets,
+            vec![TargetColor::new(Color::new(0, 128, 128))]
+        );
+        assert_eq!(settings.auto_detect, None);
+        assert_eq!(
+            settings.tolerance,
+            Tolerance::new(DistanceMetric::default(), 3.0).unwrap()
+        );
+        assert_eq!(config.load_target_color().unwrap(), Color::new(0, 128, 128));
+        assert_eq!(
+            config.load_profile("dark").unwrap().unwrap().targets.len(),
+            1
+        );
+    }
+
+    #[test]
+    fn file_applies_without_overrides() {
+        let (config, _dir) = layered(
+            "version = 2\ntarget_color = \"black\"\n",
+            ConfigOverrides::default(),
+        );
+
+        assert_eq!(config.load_target_color().unwrap(), Color::new(0, 0, 0));
+    }
+
+    #[test]
+    fn saves_go_to_the_file_and_keep_the_override() {
+        let overrides = ConfigOverrides {
+            target_colors: vec![Rgba::new(0, 128, 128, 255)],
+            ..ConfigOverrides::default()
+        };
+        let (config, _dir) = layered("version = 2\n", overrides);
+
+        config.save_target_color(&Color::new(1, 2, 3)).unwrap();
+
+        assert_eq!(config.load_target_color().unwrap(), Color::new(0, 128, 128));
+        assert_eq!(
+            config.inner().load_target_color().unwrap(),
+            Color::new(1, 2, 3)
+        );
+        let content = fs::read_to_string(config.path()).unwrap();
+        assert!(!content.contains("128"), "{content}");
+    }
+
+    #[test]
+    fn target_colors_are_layered_and_saved_to_the_file() {
+        let overrides = ConfigOverrides {
+            target_colors: vec![Rgba::new(0, 128, 128, 255)],
+            ..ConfigOverrides::default()
+        };
+        let (config, _dir) = layered("version = 2\ntarget_color = \"black\"\n", overrides);
+        let targets = vec![
+            TargetColor::new(Color::new(1, 2, 3)),
+            TargetColor::new(Color::new(4, 5, 6)),
+        ];
+
+        config.save_target_colors(&targets).unwrap();
+
+        assert_eq!(
+            config.load_target_colors().unwrap(),
+            vec![TargetColor::new(Color::new(0, 128, 128))]
+        );
+        assert_eq!(config.inner().load_target_colors().unwrap(), targets);
+    }
+
+    #[test]
+    fn effective_content_applies_the_overrides() {
+        let file = "version = 2\n# the scanner background\ntarget_color = \"black\"\n";
+        let (plain, _dir) = layered(file, ConfigOverrides::default());
+        assert_eq!(plain.effective_content().unwrap(), file);
+
+        let overrides = ConfigOverrides {
+            target_colors: vec![Rgba::new(0, 128, 128, 255)],
+            ..ConfigOverrides::default()
+        };
+        let (config, _dir) = layered(file, overrides);
+
+        let content = config.effective_content().unwrap();
+        assert!(content.contains("# the scanner background\n"), "{content}");
+        assert!(!content.contains("black"), "{content}");
+        let shown = TomlConfigProvider::with_path(config.path().with_extension("shown"));
+        fs::write(shown.path(), &content).unwrap();
+        assert_eq!(shown.load_target_color().unwrap(), Color::new(0, 128, 128));
+        assert_eq!(fs::read_to_string(config.path()).unwrap(), file);
+    }
+}
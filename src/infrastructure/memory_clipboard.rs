@@ -0,0 +1,304 @@
+use std::sync::{Mutex, MutexGuard};
+
+use crate::domain::port::{ClipboardPort, ImageData};
+use crate::domain::representation::Representation;
+
+/// A [`ClipboardPort`] operation, used to target injected failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardOperation {
+    /// [`ClipboardPort::change_count`].
+    ChangeCount,
+    /// [`ClipboardPort::get_image`].
+    GetImage,
+    /// [`ClipboardPort::set_image`].
+    SetImage,
+    /// [`ClipboardPort::get_encoded`].
+    GetEncoded,
+    /// [`ClipboardPort::set_representations`].
+    SetRepresentations,
+}
+
+/// A failure injected with [`InMemoryClipboard::fail_next`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InjectedError {
+    /// The operation that failed.
+    pub operation: ClipboardOperation,
+    /// The message given when the failure was injected.
+    pub message: String,
+}
+
+impl std::fmt::Display for InjectedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} failed: {}", self.operation, self.message)
+    }
+}
+
+impl std::error::Error for InjectedError {}
+
+/// A clipboard held in memory, for tests and headless use.
+///
+/// Behaves like the system clipboard — every write bumps the change counter —
+/// and can be scripted: [`copy`](Self::copy) and
+/// [`copy_encoded`](Self::copy_encoded) simulate another application copying
+/// an image, [`fail_next`](Self::fail_next) makes the next call of an
+/// operation fail, and [`writes`](Self::writes) records the images written
+/// through the port.
+///
+/// Content copied with [`copy_encoded`](Self::copy_encoded) is only offered
+/// encoded; images written with representations are offered in all forms.
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::port::{ClipboardPort, ImageData};
+/// use bgclipper::infrastructure::memory_clipboard::{ClipboardOperation, InMemoryClipboard};
+///
+/// let clipboard = InMemoryClipboard::new();
+/// let image = ImageData { pixels: vec![255, 255, 255, 255], width: 1, height: 1 };
+/// clipboard.copy(image.clone());
+/// assert_eq!(clipboard.change_count().unwrap(), 1);
+/// assert_eq!(clipboard.get_image().unwrap(), Some(image));
+///
+/// clipboard.fail_next(ClipboardOperation::GetImage, "clipboard busy");
+/// assert!(clipboard.get_image().is_err());
+/// assert!(clipboard.get_image().is_ok());
+/// ```
+#[derive(Debug, Default)]
+pub struct InMemoryClipboard {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    change_count: u64,
+    image: Option<ImageData>,
+    representations: Vec<Representation>,
+    writes: Vec<ImageData>,
+    failures: Vec<InjectedError>,
+}
+
+impl InMemoryClipboard {
+    /// Creates an empty clipboard with a change counter of 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Puts `image` on the clipboard as another application would, bumping
+    /// the change counter. Not recorded in [`writes`](Self::writes).
+    pub fn copy(&self, image: ImageData) {
+        let mut state = self.state();
+        state.image = Some(image);
+        state.representations.clear();
+        state.change_count += 1;
+    }
+
+    /// Puts encoded `data` of `mime_type` on the clipboard as another
+    /// application would, bumping the change counter.
+    pub fn copy_encoded(&self, mime_type: &str, data: Vec<u8>) {
+        let mut state = self.state();
+        state.image = None;
+        state.representations = vec![Representation::new(mime_type, data)];
+        state.change_count += 1;
+    }
+
+    /// Replaces the clipboard contents with something other than an image,
+    /// bumping the change counter.
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.image = None;
+        state.representations.clear();
+        state.change_count += 1;
+    }
+
+    /// Sets the change counter without touching the contents.
+    pub fn set_change_count(&self, count: u64) {
+        self.state().change_count = count;
+    }
+
+    /// Returns the current clipboard image, if any.
+    pub fn image(&self) -> Option<ImageData> {
+        self.state().image.clone()
+    }
+
+    /// Returns the encoded forms of the current content, in order of preference.
+    pub fn representations(&self) -> Vec<Representation> {
+        self.state().representations.clone()
+    }
+
+    /// Returns the images written through the port, oldest first.
+    pub fn writes(&self) -> Vec<ImageData> {
+        self.state().writes.clone()
+    }
+
+    /// Makes the next call of `operation` fail with `message`.
+    ///
+    /// Failures queue up: injecting two makes the next two calls fail.
+    pub fn fail_next(&self, operation: ClipboardOperation, message: impl Into<String>) {
+        self.state().failures.push(InjectedError {
+            operation,
+            message: message.into(),
+        });
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl State {
+    /// Takes the first failure queued for `operation`, if any.
+    fn check(&mut self, operation: ClipboardOperation) -> Result<(), InjectedError> {
+        match self.failures.iter().position(|f| f.operation == operation) {
+            Some(index) => Err(self.failures.remove(index)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ClipboardPort for InMemoryClipboard {
+    type Error = InjectedError;
+
+    fn change_count(&self) -> Result<u64, Self::Error> {
+        let mut state = self.state();
+        state.check(ClipboardOperation::ChangeCount)?;
+        Ok(state.change_count)
+    }
+
+    fn get_image(&self) -> Result<Option<ImageData>, Self::Error> {
+        let mut state = self.state();
+        state.check(ClipboardOperation::GetImage)?;
+        Ok(state.image.clone())
+    }
+
+    fn set_image(&self, image: &ImageData) -> Result<(), Self::Error> {
+        let mut state = self.state();
+        state.check(ClipboardOperation::SetImage)?;
+        state.image = Some(image.clone());
+        state.representations.clear();
+        state.writes.push(image.clone());
+        state.change_count += 1;
+        Ok(())
+    }
+
+    fn get_encoded(&self, mime_type: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        let mut state = self.state();
+        state.check(ClipboardOperation::GetEncoded)?;
+        Ok(state
+            .representations
+            .iter()
+            .find(|r| r.mime_type == mime_type)
+            .map(|r| r.data.clone()))
+    }
+
+    fn set_representations(
+        &self,
+        image: &ImageData,
+        representations: &[Representation],
+    ) -> Result<(), Self::Error> {
+        let mut state = self.state();
+        state.check(ClipboardOperation::SetRepresentations)?;
+        state.image = Some(image.clone());
+        state.representations = representations.to_vec();
+        state.writes.push(image.clone());
+        state.change_count += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(value: u8) -> ImageData {
+        ImageData {
+            pixels: vec![value, value, value, 255],
+            width: 1,
+            height: 1,
+        }
+    }
+
+    #[test]
+    fn starts_empty() {
+        let clipboard = InMemoryClipboard::new();
+        assert_eq!(clipboard.change_count().unwrap(), 0);
+        assert_eq!(clipboard.get_image().unwrap(), None);
+    }
+
+    #[test]
+    fn copy_and_clear_bump_counter() {
+        let clipboard = InMemoryClipboard::new();
+        clipboard.copy(pixel(1));
+        assert_eq!(clipboard.change_count().unwrap(), 1);
+        assert_eq!(clipboard.image(), Some(pixel(1)));
+
+        clipboard.clear();
+        assert_eq!(clipboard.change_count().unwrap(), 2);
+        assert_eq!(clipboard.get_image().unwrap(), None);
+        assert!(clipboard.writes().is_empty());
+    }
+
+    #[test]
+    fn set_image_is_recorded() {
+        let clipboard = InMemoryClipboard::new();
+        clipboard.set_change_count(41);
+        clipboard.set_image(&pixel(1)).unwrap();
+        clipboard.set_image(&pixel(2)).unwrap();
+
+        assert_eq!(clipboard.change_count().unwrap(), 43);
+        assert_eq!(clipboard.image(), Some(pixel(2)));
+        assert_eq!(clipboard.writes(), vec![pixel(1), pixel(2)]);
+    }
+
+    #[test]
+    fn encoded_content_is_offered_only_in_its_format() {
+        let clipboard = InMemoryClipboard::new();
+        clipboard.copy_encoded("image/png", vec![1, 2, 3]);
+
+        assert_eq!(clipboard.change_count().unwrap(), 1);
+        assert_eq!(
+            clipboard.get_encoded("image/png").unwrap(),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(clipboard.get_encoded("image/jpeg").unwrap(), None);
+        assert_eq!(clipboard.get_image().unwrap(), None);
+    }
+
+    #[test]
+    fn representations_are_offered_with_the_pixels() {
+        let clipboard = InMemoryClipboard::new();
+        let png = Representation::new("image/png", vec![4]);
+        let html = Representation::new("text/html", vec![5]);
+
+        clipboard
+            .set_representations(&pixel(1), &[png.clone(), html.clone()])
+            .unwrap();
+
+        assert_eq!(clipboard.change_count().unwrap(), 1);
+        assert_eq!(clipboard.get_image().unwrap(), Some(pixel(1)));
+        assert_eq!(clipboard.get_encoded("text/html").unwrap(), Some(vec![5]));
+        assert_eq!(clipboard.representations(), vec![png, html]);
+        assert_eq!(clipboard.writes(), vec![pixel(1)]);
+
+        clipboard.set_image(&pixel(2)).unwrap();
+        assert_eq!(clipboard.get_encoded("image/png").unwrap(), None);
+    }
+
+    #[test]
+    fn injected_failures_hit_only_their_operation_once() {
+        let clipboard = InMemoryClipboard::new();
+        clipboard.fail_next(ClipboardOperation::SetImage, "denied");
+        clipboard.fail_next(ClipboardOperation::SetImage, "denied again");
+
+        assert_eq!(clipboard.change_count().unwrap(), 0);
+        let err = clipboard.set_image(&pixel(1)).unwrap_err();
+        assert_eq!(err.operation, ClipboardOperation::SetImage);
+        assert_eq!(err.to_string(), "SetImage failed: denied");
+        assert_eq!(
+            clipboard.set_image(&pixel(1)).unwrap_err().message,
+            "denied again"
+        );
+
+        clipboard.set_image(&pixel(1)).unwrap();
+        assert_eq!(clipboard.writes(), vec![pixel(1)]);
+    }
+}
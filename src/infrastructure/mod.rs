@@ -1,2 +1,13 @@
 pub mod clipboard;
 pub mod config;
+pub mod directory_clipboard;
+pub mod history;
+pub mod image_file;
+pub mod layered_config;
+pub mod memory_clipboard;
+pub mod png_codec;
+#[cfg(target_os = "linux")]
+mod selection_monitor;
+#[cfg(target_os = "linux")]
+mod selection_owner;
+pub mod watcher;
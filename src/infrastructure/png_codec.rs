@@ -0,0 +1,345 @@
+use std::ops::Range;
+
+use crate::domain::port::{ImageCodec, ImageData, PNG_MIME_TYPE};
+use crate::infrastructure::image_file::{self, ImageFileError};
+
+/// The 8-byte signature every PNG file starts with.
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// Ancillary chunks carried over when an image is re-encoded.
+///
+/// Processing only changes alpha and edge colors, so physical size, color
+/// space and textual metadata still describe the result.
+const PRESERVED_CHUNKS: [&[u8; 4]; 10] = [
+    b"pHYs", b"iCCP", b"sRGB", b"gAMA", b"cHRM", b"cICP", b"eXIf", b"tEXt", b"zTXt", b"iTXt",
+];
+
+/// `pHYs` unit specifier for pixels per meter.
+const UNIT_METER: u8 = 1;
+
+/// Meters per inch, for converting pixels per meter to DPI.
+const METERS_PER_INCH: f64 = 0.0254;
+
+/// PNG metadata kept from a decoded image: physical pixel size (DPI), ICC
+/// profile and other color-space chunks, Exif and text chunks.
+///
+/// The chunks are kept verbatim and written back unchanged by
+/// [`PngCodec::encode`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PngMetadata {
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Chunk {
+    kind: [u8; 4],
+    data: Vec<u8>,
+}
+
+impl PngMetadata {
+    /// Collects the preserved metadata chunks from PNG data.
+    ///
+    /// Chunks with a bad checksum are skipped, and reading stops at the first
+    /// truncated chunk, so this never fails.
+    pub fn read(png: &[u8]) -> Self {
+        let chunks = chunks(png)
+            .filter(|chunk| PRESERVED_CHUNKS.contains(&&chunk.kind) && chunk.crc_ok)
+            .map(|chunk| Chunk {
+                kind: chunk.kind,
+                data: chunk.data.to_vec(),
+            })
+            .collect();
+        Self { chunks }
+    }
+
+    /// Returns `true` if no metadata was found.
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Returns the horizontal and vertical resolution in dots per inch, if
+    /// the image specifies its physical pixel size.
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        let data: &[u8; 9] = self.chunk(b"pHYs")?.data.as_slice().try_into().ok()?;
+        if data[8] != UNIT_METER {
+            return None;
+        }
+        let x = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let y = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+        Some((
+            f64::from(x) * METERS_PER_INCH,
+            f64::from(y) * METERS_PER_INCH,
+        ))
+    }
+
+    /// Returns `true` if the image embeds an ICC color profile.
+    pub fn has_icc_profile(&self) -> bool {
+        self.chunk(b"iCCP").is_some()
+    }
+
+    fn chunk(&self, kind: &[u8; 4]) -> Option<&Chunk> {
+        self.chunks.iter().find(|chunk| &chunk.kind == kind)
+    }
+
+    /// Inserts the metadata chunks into `png` right after its header,
+    /// replacing any chunks of the same types.
+    ///
+    /// Returns `png` unchanged if there is no metadata or it has no header.
+    fn apply(&self, png: &[u8]) -> Vec<u8> {
+        let Some(header) = chunks(png).next().filter(|chunk| &chunk.kind == b"IHDR") else {
+            return png.to_vec();
+        };
+        if self.is_empty() {
+            return png.to_vec();
+        }
+
+        let mut out = png[..header.range.end].to_vec();
+        for chunk in &self.chunks {
+            write_chunk(&mut out, &chunk.kind, &chunk.data);
+        }
+        let mut rest = header.range.end;
+        for chunk in chunks(png).skip(1) {
+            if self.chunk(&chunk.kind).is_some() {
+                out.extend_from_slice(&png[rest..chunk.range.start]);
+                rest = chunk.range.end;
+            }
+        }
+        out.extend_from_slice(&png[rest..]);
+        out
+    }
+}
+
+/// A chunk as found in PNG data.
+struct RawChunk<'a> {
+    kind: [u8; 4],
+    data: &'a [u8],
+    /// Byte range of the whole chunk, including length and checksum.
+    range: Range<usize>,
+    crc_ok: bool,
+}
+
+/// Iterates over the chunks of `png`, stopping after `IEND` or at the first
+/// truncated chunk. Yields nothing if the signature is missing.
+fn chunks(png: &[u8]) -> impl Iterator<Item = RawChunk<'_>> {
+    let mut offset = if png.starts_with(&SIGNATURE) {
+        SIGNATURE.len()
+    } else {
+        png.len()
+    };
+    std::iter::from_fn(move || {
+        let header = png.get(offset..offset.checked_add(8)?)?;
+        let len = u32::from_be_bytes(header[..4].try_into().ok()?) as usize;
+        let kind: [u8; 4] = header[4..].try_into().ok()?;
+        let data_start = offset + 8;
+        let data_end = data_start.checked_add(len)?;
+        let end = data_end.checked_add(4)?;
+        let crc = u32::from_be_bytes(png.get(data_end..end)?.try_into().ok()?);
+        let chunk = RawChunk {
+            kind,
+            data: &png[data_start..data_end],
+            range: offset..end,
+            crc_ok: crc32(&png[offset + 4..data_end]) == crc,
+        };
+        offset = if &kind == b"IEND" { png.len() } else { end };
+        Some(chunk)
+    })
+}
+
+/// Appends a chunk with its length and checksum to `out`.
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let start = out.len();
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start + 4..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// CRC-32 as used by PNG chunks.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// PNG codec that keeps DPI, color profile and text metadata from decoding
+/// to re-encoding.
+///
+/// Decoding applies the same format and size checks as
+/// [`decode_png`](image_file::decode_png).
+///
+/// # Examples
+///
+/// ```
+/// use bgclipper::domain::port::{ImageCodec, ImageData};
+/// use bgclipper::infrastructure::png_codec::{PngCodec, PngMetadata};
+///
+/// let image = ImageData { pixels: vec![255, 255, 255, 255], width: 1, height: 1 };
+/// let png = PngCodec.encode(&image, &PngMetadata::default()).unwrap();
+/// let (decoded, metadata) = PngCodec.decode(&png).unwrap();
+/// assert_eq!(decoded, image);
+/// assert_eq!(metadata.dpi(), None);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PngCodec;
+
+impl ImageCodec for PngCodec {
+    type Error = ImageFileError;
+    type Metadata = PngMetadata;
+
+    fn mime_type(&self) -> &str {
+        PNG_MIME_TYPE
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<(ImageData, PngMetadata), ImageFileError> {
+        let image = image_file::decode_png(data)?;
+        Ok((image, PngMetadata::read(data)))
+    }
+
+    fn encode(&self, image: &ImageData, metadata: &PngMetadata) -> Result<Vec<u8>, ImageFileError> {
+        let mut png = Vec::new();
+        image_file::write_png_to(&mut png, image)?;
+        Ok(metadata.apply(&png))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> ImageData {
+        ImageData {
+            pixels: vec![255, 0, 0, 255, 0, 255, 0, 128],
+            width: 2,
+            height: 1,
+        }
+    }
+
+    fn chunk(kind: &[u8; 4], data: &[u8]) -> Chunk {
+        Chunk {
+            kind: *kind,
+            data: data.to_vec(),
+        }
+    }
+
+    /// `pHYs` data for the given pixels per meter.
+    fn phys(x: u32, y: u32) -> Vec<u8> {
+        let mut data = x.to_be_bytes().to_vec();
+        data.extend_from_slice(&y.to_be_bytes());
+        data.push(UNIT_METER);
+        data
+    }
+
+    /// A PNG of `sample()` with the given chunks after the header.
+    fn png_with(chunks: Vec<Chunk>) -> Vec<u8> {
+        PngCodec.encode(&sample(), &PngMetadata { chunks }).unwrap()
+    }
+
+    fn kinds(png: &[u8]) -> Vec<[u8; 4]> {
+        chunks(png).map(|chunk| chunk.kind).collect()
+    }
+
+    #[test]
+    fn crc32_matches_png_reference() {
+        // CRC of the IEND chunk type, present in every PNG
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+
+    #[test]
+    fn read_keeps_only_preserved_chunks() {
+        let png = png_with(vec![
+            chunk(b"pHYs", &phys(5669, 5669)),
+            chunk(b"prVt", b"private"),
+            chunk(b"tEXt", b"Software\0grim"),
+        ]);
+
+        let metadata = PngMetadata::read(&png);
+
+        assert_eq!(
+            metadata.chunks,
+            vec![
+                chunk(b"pHYs", &phys(5669, 5669)),
+                chunk(b"tEXt", b"Software\0grim")
+            ]
+        );
+    }
+
+    #[test]
+    fn read_skips_chunks_with_bad_checksum() {
+        let mut png = png_with(vec![chunk(b"tEXt", b"Title\0x")]);
+        let text = chunks(&png).nth(1).unwrap().range;
+        png[text.end - 1] ^= 0xFF;
+
+        assert!(PngMetadata::read(&png).is_empty());
+    }
+
+    #[test]
+    fn read_tolerates_non_png_and_truncated_data() {
+        assert!(PngMetadata::read(b"not a png").is_empty());
+
+        let png = png_with(vec![chunk(b"pHYs", &phys(1, 1))]);
+        let header_end = chunks(&png).next().unwrap().range.end;
+        assert!(PngMetadata::read(&png[..header_end + 10]).is_empty());
+    }
+
+    #[test]
+    fn dpi_from_physical_pixel_size() {
+        let metadata = PngMetadata {
+            chunks: vec![chunk(b"pHYs", &phys(5669, 2835))],
+        };
+        let (x, y) = metadata.dpi().unwrap();
+        assert!((x - 144.0).abs() < 0.01);
+        assert!((y - 72.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn dpi_requires_meter_unit() {
+        let mut data = phys(5669, 5669);
+        data[8] = 0;
+        let metadata = PngMetadata {
+            chunks: vec![chunk(b"pHYs", &data)],
+        };
+        assert_eq!(metadata.dpi(), None);
+        assert_eq!(PngMetadata::default().dpi(), None);
+    }
+
+    #[test]
+    fn codec_roundtrip_keeps_pixels_and_metadata() {
+        let metadata = PngMetadata {
+            chunks: vec![
+                chunk(b"iCCP", b"Display P3\0\0profile"),
+                chunk(b"pHYs", &phys(5669, 5669)),
+            ],
+        };
+
+        let png = PngCodec.encode(&sample(), &metadata).unwrap();
+        let (image, decoded) = PngCodec.decode(&png).unwrap();
+
+        assert_eq!(image, sample());
+        assert_eq!(decoded, metadata);
+        assert!(decoded.has_icc_profile());
+        assert_eq!(&kinds(&png)[..3], [*b"IHDR", *b"iCCP", *b"pHYs"]);
+    }
+
+    #[test]
+    fn apply_replaces_chunks_of_same_type() {
+        let png = png_with(vec![chunk(b"pHYs", &phys(1, 1))]);
+        let metadata = PngMetadata {
+            chunks: vec![chunk(b"pHYs", &phys(2, 2))],
+        };
+
+        let updated = metadata.apply(&png);
+
+        assert_eq!(PngMetadata::read(&updated), metadata);
+        assert_eq!(kinds(&updated), kinds(&png));
+    }
+}
@@ -0,0 +1,397 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+/// Monotonically increasing clipboard change counter.
+///
+/// Bumped by a backend thread whenever the clipboard selection changes owner.
+/// Readers can either poll the current value or block until it moves past a
+/// value they have already seen.
+#[derive(Debug, Default)]
+struct ChangeCounter {
+    count: Mutex<u64>,
+    changed: Condvar,
+}
+
+impl ChangeCounter {
+    /// Increments the counter and wakes any waiters.
+    fn bump(&self) {
+        let mut count = self.count.lock().unwrap_or_else(|e| e.into_inner());
+        *count += 1;
+        self.changed.notify_all();
+    }
+
+    /// Returns the current counter value.
+    fn get(&self) -> u64 {
+        *self.count.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Blocks until the counter differs from `seen` or `timeout` elapses.
+    ///
+    /// Returns the counter value at the time of return.
+    fn wait_past(&self, seen: u64, timeout: Duration) -> u64 {
+        let deadline = Instant::now() + timeout;
+        let mut count = self.count.lock().unwrap_or_else(|e| e.into_inner());
+        while *count == seen {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            count = self
+                .changed
+                .wait_timeout(count, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        *count
+    }
+}
+
+/// Tracks clipboard ownership changes on Linux.
+///
+/// X11 and Wayland have no equivalent of macOS's `NSPasteboard.changeCount`,
+/// so a background thread listens for selection-owner changes and turns them
+/// into a counter:
+/// - Wayland: `selection` events from the `ext-data-control-v1` protocol,
+///   falling back to `wlr-data-control-unstable-v1`.
+/// - X11: `XFixesSelectionNotify` events for the `CLIPBOARD` selection.
+///
+/// On a Wayland session whose compositor lacks both data-control protocols
+/// (e.g. GNOME), X11 is tried next via XWayland.
+#[derive(Debug)]
+pub(crate) struct SelectionMonitor {
+    counter: Arc<ChangeCounter>,
+    running: Arc<AtomicBool>,
+}
+
+impl SelectionMonitor {
+    /// Connects to the display server and starts the listener thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the failure if no supported backend is available.
+    pub(crate) fn start() -> Result<Self, String> {
+        let counter = Arc::new(ChangeCounter::default());
+        let running = Arc::new(AtomicBool::new(true));
+
+        let mut errors = Vec::new();
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            match wayland::spawn(Arc::clone(&counter), Arc::clone(&running)) {
+                Ok(()) => return Ok(Self { counter, running }),
+                Err(e) => errors.push(format!("wayland: {e}")),
+            }
+        }
+        match x11::spawn(Arc::clone(&counter), Arc::clone(&running)) {
+            Ok(()) => Ok(Self { counter, running }),
+            Err(e) => {
+                errors.push(format!("x11: {e}"));
+                Err(errors.join("; "))
+            }
+        }
+    }
+
+    /// Returns the current change counter.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the listener thread has stopped (e.g. the display
+    /// connection was lost), since the counter would no longer advance.
+    pub(crate) fn change_count(&self) -> Result<u64, String> {
+        if !self.running.load(Ordering::Acquire) {
+            return Err("selection monitor stopped".to_string());
+        }
+        Ok(self.counter.get())
+    }
+
+    /// Waits until the counter moves past `seen`, up to `timeout`.
+    ///
+    /// Selection events arrive asynchronously, so after writing to the
+    /// clipboard this lets the caller observe its own change before reading
+    /// the counter again.
+    pub(crate) fn wait_for_change(&self, seen: u64, timeout: Duration) -> u64 {
+        self.counter.wait_past(seen, timeout)
+    }
+}
+
+/// Spawns a named listener thread that clears `running` when it exits.
+fn spawn_listener<F>(name: &str, running: Arc<AtomicBool>, listen: F) -> Result<(), String>
+where
+    F: FnOnce() -> Result<(), String> + Send + 'static,
+{
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            if let Err(e) = listen() {
+                warn!("clipboard selection monitor stopped: {e}");
+            }
+            running.store(false, Ordering::Release);
+        })
+        .map(|_| ())
+        .map_err(|e| format!("failed to spawn listener thread: {e}"))
+}
+
+mod x11 {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use log::debug;
+    use x11rb::connection::Connection;
+    use x11rb::protocol::Event;
+    use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+    use x11rb::protocol::xproto::ConnectionExt as _;
+
+    use super::{ChangeCounter, spawn_listener};
+
+    /// XFixes version that introduced selection tracking.
+    const XFIXES_MAJOR_VERSION: u32 = 1;
+    const XFIXES_MINOR_VERSION: u32 = 0;
+
+    /// Subscribes to `CLIPBOARD` owner changes and spawns the event loop.
+    pub(super) fn spawn(
+        counter: Arc<ChangeCounter>,
+        running: Arc<AtomicBool>,
+    ) -> Result<(), String> {
+        let (conn, screen_num) =
+            x11rb::connect(None).map_err(|e| format!("cannot connect to display: {e}"))?;
+
+        conn.xfixes_query_version(XFIXES_MAJOR_VERSION, XFIXES_MINOR_VERSION)
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| format!("XFixes extension unavailable: {e}"))?;
+
+        let clipboard = conn
+            .intern_atom(false, b"CLIPBOARD")
+            .map_err(|e| e.to_string())?
+            .reply()
+            .map_err(|e| e.to_string())?
+            .atom;
+        let root = conn.setup().roots[screen_num].root;
+
+        conn.xfixes_select_selection_input(
+            root,
+            clipboard,
+            SelectionEventMask::SET_SELECTION_OWNER
+                | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+        )
+        .map_err(|e| e.to_string())?;
+        conn.flush().map_err(|e| e.to_string())?;
+
+        debug!("tracking X11 CLIPBOARD owner changes via XFixes");
+
+        spawn_listener("bgclipper-x11-selection", running, move || {
+            loop {
+                match conn.wait_for_event().map_err(|e| e.to_string())? {
+                    Event::XfixesSelectionNotify(event) if event.selection == clipboard => {
+                        counter.bump();
+                    }
+                    _ => {}
+                }
+            }
+        })
+    }
+}
+
+mod wayland {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use log::debug;
+    use wayland_client::globals::{GlobalListContents, registry_queue_init};
+    use wayland_client::protocol::wl_registry::WlRegistry;
+    use wayland_client::protocol::wl_seat::WlSeat;
+    use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, event_created_child};
+    use wayland_protocols::ext::data_control::v1::client::{
+        ext_data_control_device_v1::{self, ExtDataControlDeviceV1},
+        ext_data_control_manager_v1::ExtDataControlManagerV1,
+        ext_data_control_offer_v1::ExtDataControlOfferV1,
+    };
+    use wayland_protocols_wlr::data_control::v1::client::{
+        zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+        zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+        zwlr_data_control_offer_v1::ZwlrDataControlOfferV1,
+    };
+
+    use super::{ChangeCounter, spawn_listener};
+
+    /// Dispatch state for the listener thread's event queue.
+    struct State {
+        counter: Arc<ChangeCounter>,
+        /// Whether the device has reported the selection it started with.
+        started: bool,
+    }
+
+    impl State {
+        /// Counts a `selection` event as a change, except the first: a new
+        /// device reports the current selection, which XFixes never does, so
+        /// both backends start at the same count.
+        fn selection(&mut self) {
+            if self.started {
+                self.counter.bump();
+            } else {
+                self.started = true;
+            }
+        }
+    }
+
+    /// Binds a data-control device for the first seat and spawns the event loop.
+    pub(super) fn spawn(
+        counter: Arc<ChangeCounter>,
+        running: Arc<AtomicBool>,
+    ) -> Result<(), String> {
+        let conn =
+            Connection::connect_to_env().map_err(|e| format!("cannot connect to display: {e}"))?;
+        let (globals, mut queue) =
+            registry_queue_init::<State>(&conn).map_err(|e| e.to_string())?;
+        let qh = queue.handle();
+
+        let seat: WlSeat = globals
+            .bind(&qh, 1..=1, ())
+            .map_err(|e| format!("no seat: {e}"))?;
+
+        if let Ok(manager) = globals.bind::<ExtDataControlManagerV1, _, _>(&qh, 1..=1, ()) {
+            manager.get_data_device(&seat, &qh, ());
+            debug!("tracking Wayland selection via ext-data-control-v1");
+        } else if let Ok(manager) = globals.bind::<ZwlrDataControlManagerV1, _, _>(&qh, 1..=2, ()) {
+            manager.get_data_device(&seat, &qh, ());
+            debug!("tracking Wayland selection via wlr-data-control-unstable-v1");
+        } else {
+            return Err("compositor supports neither ext- nor wlr-data-control".to_string());
+        }
+
+        let mut state = State {
+            counter,
+            started: false,
+        };
+        queue
+            .roundtrip(&mut state)
+            .map_err(|e| format!("initial roundtrip failed: {e}"))?;
+
+        spawn_listener("bgclipper-wayland-selection", running, move || {
+            loop {
+                queue
+                    .blocking_dispatch(&mut state)
+                    .map_err(|e| e.to_string())?;
+            }
+        })
+    }
+
+    impl Dispatch<WlRegistry, GlobalListContents> for State {
+        fn event(
+            _state: &mut Self,
+            _proxy: &WlRegistry,
+            _event: <WlRegistry as Proxy>::Event,
+            _data: &GlobalListContents,
+            _conn: &Connection,
+            _qh: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Dispatch<ExtDataControlDeviceV1, ()> for State {
+        fn event(
+            state: &mut Self,
+            _proxy: &ExtDataControlDeviceV1,
+            event: ext_data_control_device_v1::Event,
+            _data: &(),
+            _conn: &Connection,
+            _qh: &QueueHandle<Self>,
+        ) {
+            match event {
+                ext_data_control_device_v1::Event::Selection { id } => {
+                    state.selection();
+                    // Contents are read through arboard; the offer itself is not needed.
+                    if let Some(offer) = id {
+                        offer.destroy();
+                    }
+                }
+                ext_data_control_device_v1::Event::PrimarySelection { id: Some(offer) } => {
+                    offer.destroy();
+                }
+                _ => {}
+            }
+        }
+
+        event_created_child!(State, ExtDataControlDeviceV1, [
+            ext_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ExtDataControlOfferV1, ()),
+        ]);
+    }
+
+    impl Dispatch<ZwlrDataControlDeviceV1, ()> for State {
+        fn event(
+            state: &mut Self,
+            _proxy: &ZwlrDataControlDeviceV1,
+            event: zwlr_data_control_device_v1::Event,
+            _data: &(),
+            _conn: &Connection,
+            _qh: &QueueHandle<Self>,
+        ) {
+            match event {
+                zwlr_data_control_device_v1::Event::Selection { id } => {
+                    state.selection();
+                    if let Some(offer) = id {
+                        offer.destroy();
+                    }
+                }
+                zwlr_data_control_device_v1::Event::PrimarySelection { id: Some(offer) } => {
+                    offer.destroy();
+                }
+                _ => {}
+            }
+        }
+
+        event_created_child!(State, ZwlrDataControlDeviceV1, [
+            zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, ()),
+        ]);
+    }
+
+    wayland_client::delegate_noop!(State: ignore WlSeat);
+    wayland_client::delegate_noop!(State: ExtDataControlManagerV1);
+    wayland_client::delegate_noop!(State: ignore ExtDataControlOfferV1);
+    wayland_client::delegate_noop!(State: ZwlrDataControlManagerV1);
+    wayland_client::delegate_noop!(State: ignore ZwlrDataControlOfferV1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter_starts_at_zero() {
+        let counter = ChangeCounter::default();
+        assert_eq!(counter.get(), 0);
+    }
+
+    #[test]
+    fn bump_increments_counter() {
+        let counter = ChangeCounter::default();
+        counter.bump();
+        counter.bump();
+        assert_eq!(counter.get(), 2);
+    }
+
+    #[test]
+    fn wait_past_returns_immediately_when_already_changed() {
+        let counter = ChangeCounter::default();
+        counter.bump();
+        assert_eq!(counter.wait_past(0, Duration::from_secs(5)), 1);
+    }
+
+    #[test]
+    fn wait_past_times_out_when_unchanged() {
+        let counter = ChangeCounter::default();
+        assert_eq!(counter.wait_past(0, Duration::from_millis(10)), 0);
+    }
+
+    #[test]
+    fn wait_past_wakes_on_bump_from_other_thread() {
+        let counter = Arc::new(ChangeCounter::default());
+        let bumper = Arc::clone(&counter);
+        let handle = thread::spawn(move || bumper.bump());
+        assert_eq!(counter.wait_past(0, Duration::from_secs(5)), 1);
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+use std::thread;
+
+use log::{debug, warn};
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode,
+    SELECTION_NOTIFY_EVENT, SelectionNotifyEvent, SelectionRequestEvent, WindowClass,
+};
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::{COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME};
+
+use crate::domain::representation::Representation;
+
+/// Size of a `ChangeProperty` request without its data.
+const CHANGE_PROPERTY_HEADER_BYTES: usize = 24;
+
+/// Offers several representations of the clipboard content at once on X11.
+///
+/// `arboard` writes a single format per call, so this takes ownership of the
+/// `CLIPBOARD` selection with its own window and answers `TARGETS` and
+/// conversion requests for every representation, until another client takes
+/// the selection over.
+///
+/// Returns once the selection is owned; serving continues on a background
+/// thread.
+///
+/// Data is sent in a single request, without the `INCR` protocol, so
+/// representations larger than the server's request limit are not offered.
+///
+/// # Errors
+///
+/// Returns a description of the failure if the display cannot be reached,
+/// no representation fits in a request, or the selection cannot be taken.
+pub(crate) fn serve(representations: Vec<Representation>) -> Result<(), String> {
+    let (conn, screen_num) =
+        x11rb::connect(None).map_err(|e| format!("cannot connect to display: {e}"))?;
+
+    let limit = conn.maximum_request_bytes() - CHANGE_PROPERTY_HEADER_BYTES;
+    let (representations, oversized): (Vec<_>, Vec<_>) = representations
+        .into_iter()
+        .partition(|r| r.data.len() <= limit);
+    for r in &oversized {
+        debug!(
+            "not offering {} of {} bytes, over the X11 request limit",
+            r.mime_type,
+            r.data.len()
+        );
+    }
+    if representations.is_empty() {
+        return Err(format!(
+            "clipboard data exceeds the X11 request limit of {limit} bytes"
+        ));
+    }
+
+    let root = conn.setup().roots[screen_num].root;
+
+    let window = conn.generate_id().map_err(|e| e.to_string())?;
+    conn.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        window,
+        root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_ONLY,
+        COPY_FROM_PARENT,
+        &CreateWindowAux::new(),
+    )
+    .map_err(|e| e.to_string())?;
+
+    let clipboard = intern(&conn, b"CLIPBOARD")?;
+    let targets = intern(&conn, b"TARGETS")?;
+    let formats = representations
+        .into_iter()
+        .map(|r| Ok((intern(&conn, r.mime_type.as_bytes())?, r.data)))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    conn.set_selection_owner(window, clipboard, CURRENT_TIME)
+        .map_err(|e| e.to_string())?;
+    let owner = conn
+        .get_selection_owner(clipboard)
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map_err(|e| e.to_string())?
+        .owner;
+    if owner != window {
+        return Err("another client kept the clipboard selection".to_string());
+    }
+
+    debug!("serving {} clipboard format(s) on X11", formats.len());
+
+    let server = Server {
+        conn,
+        clipboard,
+        targets,
+        formats,
+    };
+    thread::Builder::new()
+        .name("bgclipper-x11-owner".to_string())
+        .spawn(move || {
+            if let Err(e) = server.run() {
+                warn!("clipboard selection owner stopped: {e}");
+            }
+        })
+        .map(|_| ())
+        .map_err(|e| format!("failed to spawn selection owner thread: {e}"))
+}
+
+fn intern(conn: &RustConnection, name: &[u8]) -> Result<Atom, String> {
+    Ok(conn
+        .intern_atom(false, name)
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map_err(|e| e.to_string())?
+        .atom)
+}
+
+/// Answers selection requests for the owned `CLIPBOARD` selection.
+struct Server {
+    conn: RustConnection,
+    clipboard: Atom,
+    targets: Atom,
+    /// Target atom and data of each representation.
+    formats: Vec<(Atom, Vec<u8>)>,
+}
+
+impl Server {
+    /// Serves requests until the selection is lost.
+    fn run(&self) -> Result<(), String> {
+        loop {
+            match self.conn.wait_for_event().map_err(|e| e.to_string())? {
+                Event::SelectionRequest(request) if request.selection == self.clipboard => {
+                    self.respond(&request)?;
+                }
+                Event::SelectionClear(event) if event.selection == self.clipboard => {
+                    debug!("clipboard selection taken over, no longer serving");
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Stores the requested target on the requestor's window and notifies it,
+    /// or refuses the request with a `None` property.
+    fn respond(&self, request: &SelectionRequestEvent) -> Result<(), String> {
+        // Obsolete clients leave the property unset and expect the target name
+        let property = if request.property == u32::from(AtomEnum::NONE) {
+            request.target
+        } else {
+            request.property
+        };
+
+        let stored = if request.target == self.targets {
+            let mut atoms = vec![self.targets];
+            atoms.extend(self.formats.iter().map(|(atom, _)| *atom));
+            self.conn
+                .change_property32(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    property,
+                    AtomEnum::ATOM,
+                    &atoms,
+                )
+                .map_err(|e| e.to_string())?;
+            true
+        } else if let Some((atom, data)) = self.formats.iter().find(|(a, _)| *a == request.target) {
+            self.conn
+                .change_property8(PropMode::REPLACE, request.requestor, property, *atom, data)
+                .map_err(|e| e.to_string())?;
+            true
+        } else {
+            false
+        };
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if stored {
+                property
+            } else {
+                AtomEnum::NONE.into()
+            },
+        };
+        self.conn
+            .send_event(false, request.requestor, EventMask::NO_EVENT, notify)
+            .map_err(|e| e.to_string())?;
+        self.conn.flush().map_err(|e| e.to_string())
+    }
+}
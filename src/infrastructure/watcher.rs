@@ -0,0 +1,433 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+#[cfg(target_os = "linux")]
+use std::sync::Arc;
+
+#[cfg(target_os = "linux")]
+use log::warn;
+
+use crate::domain::port::{ClipboardWatcher, ConfigWatcher};
+#[cfg(target_os = "linux")]
+use crate::infrastructure::selection_monitor::SelectionMonitor;
+
+/// Errors that can occur when starting a watcher.
+#[derive(Debug)]
+pub enum WatcherError {
+    /// The watcher was already started.
+    AlreadyWatching,
+    /// Failed to spawn the watcher thread.
+    Spawn(io::Error),
+}
+
+impl std::fmt::Display for WatcherError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatcherError::AlreadyWatching => write!(f, "watcher already started"),
+            WatcherError::Spawn(e) => write!(f, "failed to spawn watcher thread: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for WatcherError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WatcherError::AlreadyWatching => None,
+            WatcherError::Spawn(e) => Some(e),
+        }
+    }
+}
+
+/// Spawns a named background thread running `run`.
+fn spawn_watcher(name: &str, run: impl FnOnce() + Send + 'static) -> Result<(), WatcherError> {
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(run)
+        .map(|_| ())
+        .map_err(WatcherError::Spawn)
+}
+
+/// Fails with [`WatcherError::AlreadyWatching`] on every call after the first.
+fn start_once(started: &AtomicBool) -> Result<(), WatcherError> {
+    if started.swap(true, Ordering::AcqRel) {
+        return Err(WatcherError::AlreadyWatching);
+    }
+    Ok(())
+}
+
+/// Fallback watcher that notifies at a fixed interval.
+///
+/// Used where the platform offers no change events (macOS, Windows) or they
+/// are unavailable. Each tick only prompts a cheap change-counter check.
+#[derive(Debug)]
+pub struct PollingWatcher {
+    interval: Duration,
+    started: AtomicBool,
+}
+
+impl PollingWatcher {
+    /// Default polling interval.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Creates a watcher that notifies every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            started: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Default for PollingWatcher {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_INTERVAL)
+    }
+}
+
+impl ClipboardWatcher for PollingWatcher {
+    type Error = WatcherError;
+
+    fn watch<F>(&self, mut on_change: F) -> Result<(), Self::Error>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        start_once(&self.started)?;
+        let interval = self.interval;
+        spawn_watcher("clipboard-poll", move || {
+            loop {
+                thread::sleep(interval);
+                on_change();
+            }
+        })
+    }
+}
+
+/// Watcher for changes of files, such as the config file.
+///
+/// Polls the files' modification time and size at a fixed interval, and
+/// notifies only when either changes, or when a file appears or goes away.
+/// Editors that save by replacing the file are covered too.
+#[derive(Debug)]
+pub struct FileWatcher {
+    paths: Vec<PathBuf>,
+    interval: Duration,
+    started: AtomicBool,
+}
+
+impl FileWatcher {
+    /// Default polling interval.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Creates a watcher that checks `path` every `interval`.
+    pub fn new(path: PathBuf, interval: Duration) -> Self {
+        Self::for_paths(vec![path], interval)
+    }
+
+    /// Creates a watcher that checks each of `paths` every `interval`, and
+    /// notifies once per check in which any of them changed.
+    pub fn for_paths(paths: Vec<PathBuf>, interval: Duration) -> Self {
+        Self {
+            paths,
+            interval,
+            started: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Modification time and size of the file at `path`, or `None` if it is missing.
+fn file_version(path: &Path) -> Option<(Option<SystemTime>, u64)> {
+    fs::metadata(path)
+        .ok()
+        .map(|metadata| (metadata.modified().ok(), metadata.len()))
+}
+
+impl ConfigWatcher for FileWatcher {
+    type Error = WatcherError;
+
+    fn watch<F>(&self, mut on_change: F) -> Result<(), Self::Error>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        start_once(&self.started)?;
+        let paths = self.paths.clone();
+        let interval = self.interval;
+        let versions = move || {
+            paths
+                .iter()
+                .map(|path| file_version(path))
+                .collect::<Vec<_>>()
+        };
+        let mut version = versions();
+        spawn_watcher("file-poll", move || {
+            loop {
+                thread::sleep(interval);
+                let current = versions();
+                if current != version {
+                    version = current;
+                    on_change();
+                }
+            }
+        })
+    }
+}
+
+/// Watcher driven by messages on a channel.
+///
+/// Every `()` sent through the paired [`Sender`] becomes one notification;
+/// watching ends when all senders are dropped. Intended for tests and for
+/// triggering processing manually.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::mpsc;
+/// use bgclipper::domain::port::ClipboardWatcher;
+/// use bgclipper::infrastructure::watcher::ChannelWatcher;
+///
+/// let (watcher, trigger) = ChannelWatcher::new();
+/// let (notified_tx, notified_rx) = mpsc::channel();
+/// watcher.watch(move || notified_tx.send(()).unwrap()).unwrap();
+///
+/// trigger.send(()).unwrap();
+/// notified_rx.recv().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct ChannelWatcher {
+    receiver: Mutex<Option<Receiver<()>>>,
+}
+
+impl ChannelWatcher {
+    /// Creates a watcher and the sender that triggers its notifications.
+    pub fn new() -> (Self, Sender<()>) {
+        let (sender, receiver) = mpsc::channel();
+        let watcher = Self {
+            receiver: Mutex::new(Some(receiver)),
+        };
+        (watcher, sender)
+    }
+}
+
+impl ClipboardWatcher for ChannelWatcher {
+    type Error = WatcherError;
+
+    fn watch<F>(&self, mut on_change: F) -> Result<(), Self::Error>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let receiver = self
+            .receiver
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+            .ok_or(WatcherError::AlreadyWatching)?;
+        spawn_watcher("clipboard-channel", move || {
+            for () in receiver {
+                on_change();
+            }
+        })
+    }
+}
+
+/// Watcher for Linux clipboard selection-owner changes.
+///
+/// Notifies as soon as the [`SelectionMonitor`] sees an X11 XFixes or
+/// Wayland data-control selection event, so nothing runs while the
+/// clipboard is idle.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct SelectionWatcher {
+    monitor: Arc<SelectionMonitor>,
+    started: AtomicBool,
+}
+
+#[cfg(target_os = "linux")]
+impl SelectionWatcher {
+    /// How long to block per wait; bounds how late a stopped monitor is noticed.
+    const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub(crate) fn new(monitor: Arc<SelectionMonitor>) -> Self {
+        Self {
+            monitor,
+            started: AtomicBool::new(false),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl ClipboardWatcher for SelectionWatcher {
+    type Error = WatcherError;
+
+    fn watch<F>(&self, mut on_change: F) -> Result<(), Self::Error>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        start_once(&self.started)?;
+        let monitor = Arc::clone(&self.monitor);
+        spawn_watcher("clipboard-watch", move || {
+            // The first notification lets the caller check the current clipboard
+            let mut seen = None;
+            loop {
+                match monitor.change_count() {
+                    Ok(count) if seen != Some(count) => {
+                        seen = Some(count);
+                        on_change();
+                    }
+                    Ok(count) => {
+                        monitor.wait_for_change(count, Self::WAIT_TIMEOUT);
+                    }
+                    Err(e) => {
+                        // Let the caller run into the same error and report it
+                        warn!("clipboard watcher stopped: {e}");
+                        on_change();
+                        return;
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// The best watcher available on this platform.
+///
+/// Created by [`ArboardClipboardProvider::watcher`](crate::infrastructure::clipboard::ArboardClipboardProvider::watcher).
+#[derive(Debug)]
+pub enum PlatformWatcher {
+    /// Selection-owner change events (Linux).
+    #[cfg(target_os = "linux")]
+    Selection(SelectionWatcher),
+    /// Interval polling, where no change events are available.
+    Polling(PollingWatcher),
+}
+
+impl ClipboardWatcher for PlatformWatcher {
+    type Error = WatcherError;
+
+    fn watch<F>(&self, on_change: F) -> Result<(), Self::Error>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        match self {
+            #[cfg(target_os = "linux")]
+            PlatformWatcher::Selection(watcher) => watcher.watch(on_change),
+            PlatformWatcher::Polling(watcher) => watcher.watch(on_change),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn notifier() -> (impl FnMut() + Send + 'static, Receiver<()>) {
+        let (tx, rx) = mpsc::channel();
+        (move || tx.send(()).unwrap_or(()), rx)
+    }
+
+    #[test]
+    fn channel_watcher_forwards_each_message() {
+        let (watcher, trigger) = ChannelWatcher::new();
+        let (on_change, notified) = notifier();
+        watcher.watch(on_change).unwrap();
+
+        trigger.send(()).unwrap();
+        trigger.send(()).unwrap();
+
+        notified.recv_timeout(TIMEOUT).unwrap();
+        notified.recv_timeout(TIMEOUT).unwrap();
+    }
+
+    #[test]
+    fn channel_watcher_stops_when_sender_dropped() {
+        let (watcher, trigger) = ChannelWatcher::new();
+        let (on_change, notified) = notifier();
+        watcher.watch(on_change).unwrap();
+
+        drop(trigger);
+
+        // The watcher thread exits and drops `on_change`, closing the channel
+        assert_eq!(
+            notified.recv_timeout(TIMEOUT),
+            Err(mpsc::RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn channel_watcher_starts_only_once() {
+        let (watcher, _trigger) = ChannelWatcher::new();
+        watcher.watch(|| {}).unwrap();
+        assert!(matches!(
+            watcher.watch(|| {}),
+            Err(WatcherError::AlreadyWatching)
+        ));
+    }
+
+    #[test]
+    fn polling_watcher_notifies_repeatedly() {
+        let watcher = PollingWatcher::new(Duration::from_millis(5));
+        let (on_change, notified) = notifier();
+        let start = Instant::now();
+        watcher.watch(on_change).unwrap();
+
+        for _ in 0..3 {
+            notified.recv_timeout(TIMEOUT).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(15));
+        assert!(matches!(
+            watcher.watch(|| {}),
+            Err(WatcherError::AlreadyWatching)
+        ));
+    }
+
+    #[test]
+    fn file_watcher_notifies_only_on_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let watcher = FileWatcher::new(path.clone(), Duration::from_millis(5));
+        let (on_change, notified) = notifier();
+        watcher.watch(on_change).unwrap();
+
+        assert_eq!(
+            notified.recv_timeout(Duration::from_millis(50)),
+            Err(mpsc::RecvTimeoutError::Timeout)
+        );
+        fs::write(&path, "mode = \"flood\"\n").unwrap();
+        notified.recv_timeout(TIMEOUT).unwrap();
+
+        fs::remove_file(&path).unwrap();
+        notified.recv_timeout(TIMEOUT).unwrap();
+        assert!(matches!(
+            watcher.watch(|| {}),
+            Err(WatcherError::AlreadyWatching)
+        ));
+    }
+
+    #[test]
+    fn file_watcher_checks_every_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let (user, system) = (dir.path().join("user.toml"), dir.path().join("system.toml"));
+        let watcher = FileWatcher::for_paths(vec![user, system.clone()], Duration::from_millis(5));
+        let (on_change, notified) = notifier();
+        watcher.watch(on_change).unwrap();
+
+        fs::write(&system, "mode = \"flood\"\n").unwrap();
+        notified.recv_timeout(TIMEOUT).unwrap();
+    }
+
+    #[test]
+    fn platform_watcher_delegates() {
+        let watcher = PlatformWatcher::Polling(PollingWatcher::new(Duration::from_millis(5)));
+        let (on_change, notified) = notifier();
+        watcher.watch(on_change).unwrap();
+
+        notified.recv_timeout(TIMEOUT).unwrap();
+    }
+}
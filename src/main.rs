@@ -1,11 +1,17 @@
 use bgclipper::application::clipboard_service::ClipboardService;
 use bgclipper::domain::port::ConfigPort;
 use bgclipper::infrastructure::clipboard::ArboardClipboardProvider;
-use bgclipper::infrastructure::config::TomlConfigProvider;
+use bgclipper::infrastructure::history::{DEFAULT_HISTORY_LIMIT, HistoryStore};
+use bgclipper::infrastructure::layered_config::{ConfigOverrides, LayeredConfig};
+use bgclipper::infrastructure::png_codec::PngCodec;
+use bgclipper::presentation::cli::{self, Cli};
 use bgclipper::presentation::tray;
 use log::info;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args = Cli::parse_args();
 
-fn main() {
     // Initialize logger: debug level in debug builds, warn in release
     let default_level = if cfg!(debug_assertions) {
         "debug"
@@ -16,10 +22,25 @@ fn main() {
         .format_timestamp_secs()
         .init();
 
+    // Command-line options win over the environment, which wins over the file
+    let overrides = match ConfigOverrides::from_env() {
+        Ok(env) => args.config_overrides().over(env),
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let config = LayeredConfig::open(overrides).expect("failed to determine config directory");
+
+    // Headless commands run without touching the clipboard or the tray
+    if args.is_headless() {
+        return cli::run(&args, &config);
+    }
+
     info!("bgclipper starting");
 
     let clipboard = ArboardClipboardProvider::new();
-    let config = TomlConfigProvider::new().expect("failed to determine config directory");
+    let watcher = clipboard.watcher();
 
     // Create default config file if it doesn't exist
     if let Err(e) = config.ensure_config_exists() {
@@ -28,8 +49,24 @@ fn main() {
 
     info!("config initialized");
 
-    let service = ClipboardService::new(clipboard, config);
+    let history = match config.path().parent() {
+        Some(dir) if args.persist_history => {
+            HistoryStore::persistent(dir.join("history"), DEFAULT_HISTORY_LIMIT).unwrap_or_else(
+                |e| {
+                    log::error!("failed to load history, keeping it in memory: {e}");
+                    HistoryStore::in_memory(DEFAULT_HISTORY_LIMIT)
+                },
+            )
+        }
+        _ => HistoryStore::in_memory(DEFAULT_HISTORY_LIMIT),
+    };
+
+    let config_watcher = config.inner().watcher();
+    let service = ClipboardService::new(clipboard, config)
+        .with_codec(PngCodec)
+        .with_history(history);
 
     info!("starting system tray event loop");
-    tray::run(service);
+    tray::run(service, watcher, config_watcher);
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,1041 @@
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitCode};
+
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+
+use crate::domain::background::AutoDetect;
+use crate::domain::color::Rgba;
+use crate::domain::crop::Crop;
+use crate::domain::distance::{AlphaRamp, DistanceMetric, Tolerance};
+use crate::domain::flood_fill::{Connectivity, FloodFill, RemovalMode};
+use crate::domain::pipeline::{Outcome, remove_background};
+use crate::domain::port::{ConfigPort, ImageCodec};
+use crate::domain::profile::Profile;
+use crate::domain::target::AlphaMatching;
+use crate::infrastructure::config::TomlConfigProvider;
+use crate::infrastructure::image_file::{
+    ImageFileError, collect_png_files, read_encoded, read_encoded_from,
+};
+use crate::infrastructure::layered_config::{ConfigOverrides, LayeredConfig};
+use crate::infrastructure::png_codec::PngCodec;
+
+/// Command-line interface.
+///
+/// Without arguments, bgclipper runs as a system tray app. Processing flags
+/// without a subcommand select pipe mode, which filters one PNG from
+/// standard input to standard output:
+///
+/// ```sh
+/// grim - | bgclipper --color ffffff | wl-copy
+/// ```
+#[derive(Debug, Parser)]
+#[command(name = "bgclipper", version, about)]
+pub struct Cli {
+    /// Headless command to run instead of the tray app.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Read a PNG image from standard input (pipe mode).
+    #[arg(long)]
+    pub stdin: bool,
+
+    /// Write the processed PNG image to standard output (pipe mode).
+    #[arg(long)]
+    pub stdout: bool,
+
+    /// Keep the tray app's history of processed images across restarts, in
+    /// a `history` directory next to the config file.
+    #[arg(long)]
+    pub persist_history: bool,
+
+    /// Config file to use instead of the default one. Overrides
+    /// `BGCLIPPER_CONFIG`.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub settings: SettingsArgs,
+}
+
+impl Cli {
+    /// Parses the process arguments, exiting with a usage message if they
+    /// are invalid.
+    pub fn parse_args() -> Self {
+        Self::try_parse_args(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parses `args`, the first being the program name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the arguments are invalid, or if pipe mode
+    /// options are combined with a subcommand.
+    pub fn try_parse_args<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let cli = Self::try_parse_from(args)?;
+        // Checked here rather than by clap, which would also reject global
+        // options such as `--config` before a subcommand
+        if cli.command.is_some() && cli.is_pipe() {
+            return Err(Self::command().error(
+                ErrorKind::ArgumentConflict,
+                "pipe mode options cannot be used with a subcommand",
+            ));
+        }
+        Ok(cli)
+    }
+
+    /// Returns `true` if a headless command or pipe mode was requested,
+    /// rather than the tray app.
+    pub fn is_headless(&self) -> bool {
+        self.command.is_some() || self.is_pipe()
+    }
+
+    /// Returns `true` if any pipe mode option was given.
+    fn is_pipe(&self) -> bool {
+        self.stdin || self.stdout || self.settings != SettingsArgs::default()
+    }
+
+    /// Returns the config overrides given on the command line (`--config`
+    /// and `--color`), to layer over those from the environment.
+    pub fn config_overrides(&self) -> ConfigOverrides {
+        let settings = match &self.command {
+            Some(Command::Process(args)) => &args.settings,
+            _ => &self.settings,
+        };
+        ConfigOverrides {
+            path: self.config.clone(),
+            target_colors: settings.colors.clone(),
+        }
+    }
+}
+
+/// Headless subcommands.
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Remove the background from PNG files and write them to a directory.
+    Process(ProcessArgs),
+    /// Find, show or manage the config file.
+    Config(ConfigArgs),
+}
+
+/// Arguments of `bgclipper process`.
+#[derive(Debug, Args)]
+pub struct ProcessArgs {
+    /// PNG files, or directories whose PNG files are processed (not recursively).
+    #[arg(required = true, value_name = "INPUT")]
+    pub inputs: Vec<PathBuf>,
+
+    /// Directory to write the processed images to, under their original file
+    /// names. Nothing is processed if two inputs share a name or an input
+    /// would be overwritten.
+    #[arg(short, long, value_name = "DIR")]
+    pub output: PathBuf,
+
+    #[command(flatten)]
+    pub settings: SettingsArgs,
+}
+
+/// Arguments of `bgclipper config`.
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub action: ConfigAction,
+}
+
+/// Actions of `bgclipper config`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Subcommand)]
+pub enum ConfigAction {
+    /// Print the path of the config file in effect.
+    Path,
+    /// Print the config in effect, or the defaults if there is no file.
+    Show,
+    /// Open the config file in $VISUAL or $EDITOR, creating it first, and
+    /// check it afterwards.
+    Edit,
+    /// Move the config file aside to `config.toml.bak`, so the defaults
+    /// apply again.
+    Reset,
+}
+
+/// Processing settings that override the config file.
+#[derive(Debug, Default, PartialEq, Args)]
+pub struct SettingsArgs {
+    /// Start from this named profile instead of the active settings.
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Ignore the config file and start from the built-in defaults.
+    #[arg(long, conflicts_with = "profile")]
+    pub no_config: bool,
+
+    /// Background color to remove: hex (ffffff, #fff, #ffffff80), rgb(),
+    /// rgba(), hsl() or a CSS color name. Repeat for several colors.
+    /// Overrides `BGCLIPPER_TARGET_COLOR`.
+    #[arg(long = "color", value_name = "COLOR", value_parser = Rgba::parse_with_bare_hex)]
+    pub colors: Vec<Rgba>,
+
+    /// Detect the background from each image's border instead of using a fixed color.
+    #[arg(long, conflicts_with = "colors")]
+    pub auto: bool,
+
+    /// Confidence (0-1) auto-detection needs before an image is processed.
+    #[arg(long, requires = "auto", value_name = "RATIO")]
+    pub min_confidence: Option<f64>,
+
+    /// Color distance metric for the tolerance.
+    #[arg(long, value_enum)]
+    pub metric: Option<MetricArg>,
+
+    /// Distance from the target color up to which pixels become transparent.
+    #[arg(long, value_name = "DISTANCE")]
+    pub tolerance: Option<f64>,
+
+    /// Distance from which pixels stay opaque; pixels in between fade out.
+    #[arg(long, requires = "tolerance", value_name = "DISTANCE")]
+    pub outer_tolerance: Option<f64>,
+
+    /// How pixels that are already partially transparent are matched.
+    #[arg(long, value_enum)]
+    pub alpha_matching: Option<AlphaMatchingArg>,
+
+    /// Which pixels removal applies to.
+    #[arg(long, value_enum)]
+    pub mode: Option<ModeArg>,
+
+    /// Neighbors connected in flood mode: 4 or 8.
+    #[arg(long, value_parser = parse_connectivity, value_name = "4|8")]
+    pub connectivity: Option<Connectivity>,
+
+    /// Remove the background tint from partially transparent edge pixels.
+    #[arg(long)]
+    pub decontaminate: bool,
+
+    /// Crop the result to its non-transparent content.
+    #[arg(long)]
+    pub crop: bool,
+
+    /// Transparent margin kept around the content when cropping.
+    #[arg(long, requires = "crop", value_name = "PIXELS")]
+    pub crop_padding: Option<u32>,
+}
+
+/// Distance metric names accepted on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MetricArg {
+    Euclidean,
+    Cie76,
+    Ciede2000,
+    #[value(name = "channel_max")]
+    ChannelMax,
+}
+
+impl From<MetricArg> for DistanceMetric {
+    fn from(arg: MetricArg) -> Self {
+        match arg {
+            MetricArg::Euclidean => DistanceMetric::EuclideanRgb,
+            MetricArg::Cie76 => DistanceMetric::Cie76,
+            MetricArg::Ciede2000 => DistanceMetric::Ciede2000,
+            MetricArg::ChannelMax => DistanceMetric::ChannelMax,
+        }
+    }
+}
+
+/// Alpha matching rule names accepted on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AlphaMatchingArg {
+    /// Match on color alone, whatever the pixel's alpha.
+    Ignore,
+    /// Only fully opaque pixels can match.
+    OpaqueOnly,
+    /// Compare colors premultiplied by alpha.
+    Premultiplied,
+}
+
+impl From<AlphaMatchingArg> for AlphaMatching {
+    fn from(arg: AlphaMatchingArg) -> Self {
+        match arg {
+            AlphaMatchingArg::Ignore => AlphaMatching::Ignore,
+            AlphaMatchingArg::OpaqueOnly => AlphaMatching::OpaqueOnly,
+            AlphaMatchingArg::Premultiplied => AlphaMatching::Premultiplied,
+        }
+    }
+}
+
+/// Removal mode names accepted on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ModeArg {
+    /// Every matching pixel anywhere in the image.
+    Global,
+    /// Only matching pixels connected to the image border.
+    Flood,
+}
+
+impl SettingsArgs {
+    /// Loads the base settings from `config`, or with `--no-config` the
+    /// defaults with only its overrides, and applies the other flags.
+    ///
+    /// The target colors given by `--color` are among `config`'s overrides
+    /// (see [`Cli::config_overrides`]), above those of the environment.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error string if the config cannot be loaded, the profile
+    /// does not exist, or a flag value is out of range.
+    pub fn resolve<G: ConfigPort>(&self, config: &LayeredConfig<G>) -> Result<Profile, String> {
+        let base = if self.no_config {
+            config.overrides().apply(Profile::default())
+        } else if let Some(name) = &self.profile {
+            config
+                .load_profile(name)
+                .map_err(|e| format!("failed to load config: {e}"))?
+                .ok_or_else(|| format!("profile \"{name}\" is not defined"))?
+        } else {
+            config
+                .load_settings()
+                .map_err(|e| format!("failed to load config: {e}"))?
+        };
+        self.apply(base)
+    }
+
+    /// Applies the command-line flags other than `--color` to `profile`.
+    fn apply(&self, mut profile: Profile) -> Result<Profile, String> {
+        if self.auto {
+            let min_confidence = self
+                .min_confidence
+                .unwrap_or(AutoDetect::DEFAULT_MIN_CONFIDENCE);
+            profile.auto_detect = Some(AutoDetect::new(min_confidence).ok_or_else(|| {
+                format!("--min-confidence must be between 0 and 1, got {min_confidence}")
+            })?);
+        }
+
+        if self.metric.is_some() || self.tolerance.is_some() {
+            let metric = self
+                .metric
+                .map_or(profile.tolerance.metric(), DistanceMetric::from);
+            let threshold = self.tolerance.unwrap_or(profile.tolerance.threshold());
+            profile.tolerance = Tolerance::new(metric, threshold).ok_or_else(|| {
+                format!("--tolerance must be a non-negative number, got {threshold}")
+            })?;
+            profile.alpha_ramp = match self.outer_tolerance {
+                Some(outer) => Some(AlphaRamp::new(metric, threshold, outer).ok_or_else(|| {
+                    format!(
+                        "--outer-tolerance ({outer}) must be no smaller than --tolerance ({threshold})"
+                    )
+                })?),
+                None => None,
+            };
+        }
+
+        if let Some(matching) = self.alpha_matching {
+            profile.alpha_matching = matching.into();
+        }
+
+        match self.mode {
+            Some(ModeArg::Global) => profile.mode = RemovalMode::Global,
+            Some(ModeArg::Flood) if !matches!(profile.mode, RemovalMode::Flood(_)) => {
+                profile.mode = RemovalMode::Flood(FloodFill::default());
+            }
+            _ => {}
+        }
+        if let (RemovalMode::Flood(flood), Some(connectivity)) =
+            (&mut profile.mode, self.connectivity)
+        {
+            flood.connectivity = connectivity;
+        }
+
+        if self.decontaminate {
+            profile.post_processing.decontaminate_edges = true;
+        }
+        if self.crop {
+            let base = profile.post_processing.crop.unwrap_or_default();
+            profile.post_processing.crop = Some(Crop {
+                padding: self.crop_padding.unwrap_or(base.padding),
+            });
+        }
+        Ok(profile)
+    }
+}
+
+/// Parses a flood-fill connectivity of `4` or `8`.
+fn parse_connectivity(s: &str) -> Result<Connectivity, String> {
+    match s {
+        "4" => Ok(Connectivity::Four),
+        "8" => Ok(Connectivity::Eight),
+        _ => Err(format!("expected 4 or 8, got {s:?}")),
+    }
+}
+
+/// Runs a headless command, or pipe mode if no subcommand was given, and
+/// returns the process exit code.
+pub fn run(cli: &Cli, config: &LayeredConfig<TomlConfigProvider>) -> ExitCode {
+    match &cli.command {
+        Some(Command::Process(args)) => process(args, config),
+        Some(Command::Config(args)) => manage_config(args.action, config),
+        None => pipe(&cli.settings, config),
+    }
+}
+
+/// Runs a `bgclipper config` action, reporting errors on stderr.
+///
+/// `path` and `show` report the config in effect, overrides included; `edit`
+/// and `reset` act on the file itself.
+fn manage_config(action: ConfigAction, config: &LayeredConfig<TomlConfigProvider>) -> ExitCode {
+    let result = match action {
+        ConfigAction::Path => {
+            match config.effective_path() {
+                Some(path) => println!("{}", path.display()),
+                None => {
+                    println!("{}", config.path().display());
+                    eprintln!("(not created yet; the defaults are in use)");
+                }
+            }
+            Ok(())
+        }
+        ConfigAction::Show => {
+            if config.effective_path().is_none() {
+                eprintln!("no config file; showing the defaults");
+            }
+            if config.is_overridden() {
+                eprintln!("target colors overridden by the environment or command line");
+            }
+            config
+                .effective_content()
+                .map(|content| print!("{content}"))
+                .map_err(|e| e.to_string())
+        }
+        ConfigAction::Edit => edit_config(config.inner()),
+        ConfigAction::Reset => reset_config(config.inner()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Opens the config file in the user's editor, creating it first, and
+/// checks it once the editor exits.
+fn edit_config(config: &TomlConfigProvider) -> Result<(), String> {
+    let path = config.path();
+    config
+        .ensure_file()
+        .map_err(|e| format!("failed to create {}: {e}", path.display()))?;
+
+    let mut editor = editor_command(|name| std::env::var_os(name)).into_iter();
+    let program = editor.next().unwrap_or_default();
+    let status = process::Command::new(&program)
+        .args(editor)
+        .arg(path)
+        .status()
+        .map_err(|e| format!("failed to run {}: {e}", program.to_string_lossy()))?;
+    if !status.success() {
+        return Err(format!("editor exited with {status}"));
+    }
+
+    config
+        .reload()
+        .map(|_| ())
+        .map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Returns the editor to run and its arguments: `$VISUAL`, then `$EDITOR`,
+/// then the platform's text editor.
+fn editor_command(var: impl Fn(&str) -> Option<OsString>) -> Vec<OsString> {
+    ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(var)
+        .map(|value| {
+            value
+                .to_string_lossy()
+                .split_whitespace()
+                .map(OsString::from)
+                .collect::<Vec<_>>()
+        })
+        .find(|words| !words.is_empty())
+        .unwrap_or_else(|| {
+            let default: &[&str] = if cfg!(windows) {
+                &["notepad"]
+            } else if cfg!(target_os = "macos") {
+                &["open", "-W", "-t"]
+            } else {
+                &["vi"]
+            };
+            default.iter().map(OsString::from).collect()
+        })
+}
+
+/// Moves the config file aside and says which settings apply now.
+fn reset_config(config: &TomlConfigProvider) -> Result<(), String> {
+    match config.reset() {
+        Ok(Some(backup)) => {
+            eprintln!("previous config saved as {}", backup.display());
+            match config.effective_path() {
+                Some(path) => eprintln!("now using {}", path.display()),
+                None => eprintln!("now using the defaults"),
+            }
+            Ok(())
+        }
+        Ok(None) => {
+            eprintln!("no config file at {}", config.path().display());
+            Ok(())
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Filters one PNG image from standard input to standard output.
+///
+/// Diagnostics go to stderr so they never corrupt the image stream.
+fn pipe<G: ConfigPort>(settings: &SettingsArgs, config: &LayeredConfig<G>) -> ExitCode {
+    if io::stdin().is_terminal() {
+        eprintln!("error: expected a PNG image on standard input");
+        return ExitCode::FAILURE;
+    }
+    if io::stdout().is_terminal() {
+        eprintln!("error: refusing to write a PNG image to the terminal");
+        return ExitCode::FAILURE;
+    }
+
+    let profile = match settings.resolve(config) {
+        Ok(profile) => profile,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match filter(io::stdin().lock(), io::stdout().lock(), &profile) {
+        Ok(Outcome::Undetected(_)) => {
+            eprintln!(
+                "warning: background not detected confidently, image passed through unchanged"
+            );
+            ExitCode::SUCCESS
+        }
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Reads a PNG from `input`, removes its background and writes the result
+/// to `output` as PNG, keeping the input's metadata.
+fn filter(
+    input: impl Read,
+    mut output: impl Write,
+    profile: &Profile,
+) -> Result<Outcome, ImageFileError> {
+    let (png, outcome) = convert(&read_encoded_from(input)?, profile)?;
+    output.write_all(&png)?;
+    output.flush()?;
+    Ok(outcome)
+}
+
+/// Removes the background from PNG data, keeping its DPI, color profile and
+/// text metadata.
+fn convert(png: &[u8], profile: &Profile) -> Result<(Vec<u8>, Outcome), ImageFileError> {
+    let (mut image, metadata) = PngCodec.decode(png)?;
+    let outcome = remove_background(&mut image, profile);
+    Ok((PngCodec.encode(&image, &metadata)?, outcome))
+}
+
+/// Processes every input file, reporting progress and errors on stderr.
+///
+/// Fails if the settings are invalid, no input files are found, or any file
+/// cannot be processed; the remaining files are still processed.
+fn process<G: ConfigPort>(args: &ProcessArgs, config: &LayeredConfig<G>) -> ExitCode {
+    let profile = match args.settings.resolve(config) {
+        Ok(profile) => profile,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let files = match collect_png_files(&args.inputs) {
+        Ok(files) if files.is_empty() => {
+            eprintln!("error: no PNG files found");
+            return ExitCode::FAILURE;
+        }
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let outputs = match output_paths(&files, &args.output) {
+        Ok(outputs) => outputs,
+        Err(conflicts) => {
+            for conflict in &conflicts {
+                eprintln!("error: {conflict}");
+            }
+            eprintln!("nothing processed, rename the inputs or choose another output directory");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Err(e) = fs::create_dir_all(&args.output) {
+        eprintln!("error: failed to create {}: {e}", args.output.display());
+        return ExitCode::FAILURE;
+    }
+
+    let mut failed = 0;
+    for (file, output) in files.iter().zip(&outputs) {
+        match process_file(file, output, &profile) {
+            Ok(Outcome::Changed(changed)) => {
+                eprintln!("{}: {changed} pixel(s) made transparent", file.display());
+            }
+            Ok(Outcome::Unchanged) => {
+                eprintln!("{}: no background pixels found", file.display());
+            }
+            Ok(Outcome::Undetected(_)) => {
+                eprintln!(
+                    "{}: background not detected confidently, copied unchanged",
+                    file.display()
+                );
+            }
+            Err(e) => {
+                eprintln!("{}: error: {e}", file.display());
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        eprintln!("{failed} of {} file(s) failed", files.len());
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Returns the path in `output_dir` each input file is written to, under its
+/// own file name.
+///
+/// # Errors
+///
+/// Returns every conflict found, so none are processed: inputs without a
+/// file name, inputs sharing a file name, and outputs that would overwrite
+/// their own input.
+fn output_paths(files: &[PathBuf], output_dir: &Path) -> Result<Vec<PathBuf>, Vec<String>> {
+    let mut outputs = Vec::with_capacity(files.len());
+    let mut conflicts = Vec::new();
+    let mut sources: HashMap<&OsStr, &Path> = HashMap::new();
+    for file in files {
+        let Some(file_name) = file.file_name() else {
+            conflicts.push(format!("{}: not a file path", file.display()));
+            continue;
+        };
+        let output = output_dir.join(file_name);
+        if let Some(first) = sources.insert(file_name, file) {
+            conflicts.push(format!(
+                "{} and {} would both be written to {}",
+                first.display(),
+                file.display(),
+                output.display()
+            ));
+        } else if is_same_file(file, &output) {
+            conflicts.push(format!(
+                "{}: would be overwritten by its own output",
+                file.display()
+            ));
+        }
+        outputs.push(output);
+    }
+    if conflicts.is_empty() {
+        Ok(outputs)
+    } else {
+        Err(conflicts)
+    }
+}
+
+/// Returns `true` if `a` and `b` both exist and name the same file.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Processes one PNG file, writing the result to `output`.
+fn process_file(input: &Path, output: &Path, profile: &Profile) -> Result<Outcome, String> {
+    let png = read_encoded(input).map_err(|e| e.to_string())?;
+    let (png, outcome) = convert(&png, profile).map_err(|e| e.to_string())?;
+    fs::write(output, png).map_err(|e| e.to_string())?;
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::color::Color;
+    use crate::domain::port::ImageData;
+    use crate::domain::target::TargetColor;
+    use crate::infrastructure::image_file::{read_png, read_png_from, write_png, write_png_to};
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_args(std::iter::once("bgclipper").chain(args.iter().copied()))
+    }
+
+    fn settings(args: &[&str]) -> SettingsArgs {
+        let mut full = vec!["process", "in.png", "-o", "out"];
+        full.extend_from_slice(args);
+        match parse(&full).unwrap().command {
+            Some(Command::Process(process)) => process.settings,
+            other => panic!("unexpected command: {other:?}"),
+        }
+    }
+
+    fn temp_config() -> (LayeredConfig<TomlConfigProvider>, tempfile::TempDir) {
+        temp_config_with(ConfigOverrides::default())
+    }
+
+    fn temp_config_with(
+        overrides: ConfigOverrides,
+    ) -> (LayeredConfig<TomlConfigProvider>, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let provider = TomlConfigProvider::with_path(dir.path().join("config.toml"));
+        (LayeredConfig::new(provider, overrides), dir)
+    }
+
+    #[test]
+    fn no_arguments_runs_tray() {
+        assert!(parse(&[]).unwrap().command.is_none());
+    }
+
+    #[test]
+    fn processing_flags_select_pipe_mode() {
+        assert!(!parse(&[]).unwrap().is_headless());
+
+        let cli = parse(&["--color", "ffffff"]).unwrap();
+        assert!(cli.command.is_none());
+        assert!(cli.is_headless());
+        assert!(parse(&["--stdin", "--stdout"]).unwrap().is_headless());
+    }
+
+    #[test]
+    fn pipe_flags_conflict_with_subcommands() {
+        for args in [
+            &["--stdin", "process", "in.png", "-o", "out"][..],
+            &["--color", "ffffff", "config", "show"],
+        ] {
+            let err = parse(args).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn filter_processes_png_stream() {
+        let mut input = Vec::new();
+        write_png_to(
+            &mut input,
+            &ImageData {
+                pixels: vec![255, 255, 255, 255, 0, 0, 0, 255],
+                width: 2,
+                height: 1,
+            },
+        )
+        .unwrap();
+        let mut output = Vec::new();
+
+        let outcome = filter(input.as_slice(), &mut output, &Profile::default()).unwrap();
+
+        assert_eq!(outcome, Outcome::Changed(1));
+        let result = read_png_from(output.as_slice()).unwrap();
+        assert_eq!(result.pixels, vec![255, 255, 255, 0, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn filter_rejects_non_png_input() {
+        let mut output = Vec::new();
+        let result = filter(&b"GIF89a"[..], &mut output, &Profile::default());
+
+        assert!(matches!(result, Err(ImageFileError::UnsupportedFormat(_))));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn config_path_is_accepted_in_every_mode() {
+        let cli = parse(&["--config", "ci.toml"]).unwrap();
+        assert!(!cli.is_headless());
+        assert_eq!(cli.config_overrides().path, Some(PathBuf::from("ci.toml")));
+
+        let cli = parse(&["process", "in.png", "-o", "out", "--config", "ci.toml"]).unwrap();
+        assert_eq!(cli.config, Some(PathBuf::from("ci.toml")));
+        assert!(parse(&["--config", "ci.toml", "--stdin"]).is_ok());
+    }
+
+    #[test]
+    fn config_actions_parse() {
+        let action = |args: &[&str]| match parse(args).unwrap().command {
+            Some(Command::Config(config)) => config.action,
+            other => panic!("unexpected command: {other:?}"),
+        };
+
+        assert_eq!(action(&["config", "path"]), ConfigAction::Path);
+        assert_eq!(action(&["config", "show"]), ConfigAction::Show);
+        assert_eq!(
+            action(&["--config", "ci.toml", "config", "edit"]),
+            ConfigAction::Edit
+        );
+        assert_eq!(action(&["config", "reset"]), ConfigAction::Reset);
+        assert!(parse(&["config"]).is_err());
+    }
+
+    #[test]
+    fn editor_comes_from_visual_then_editor() {
+        let editor = |visual: Option<&str>, editor: Option<&str>| {
+            editor_command(|name| match name {
+                "VISUAL" => visual.map(OsString::from),
+                "EDITOR" => editor.map(OsString::from),
+                _ => None,
+            })
+        };
+
+        assert_eq!(
+            editor(Some("code --wait"), Some("nano")),
+            ["code", "--wait"]
+        );
+        assert_eq!(editor(Some(" "), Some("nano")), ["nano"]);
+        assert_eq!(
+            editor(None, None).len(),
+            if cfg!(target_os = "macos") { 3 } else { 1 }
+        );
+    }
+
+    #[test]
+    fn process_requires_output() {
+        assert!(parse(&["process", "in.png"]).is_err());
+    }
+
+    #[test]
+    fn flags_override_base_settings() {
+        let args = settings(&[
+            "--metric",
+            "channel_max",
+            "--tolerance",
+            "3",
+            "--outer-tolerance",
+            "9",
+            "--mode",
+            "flood",
+            "--connectivity",
+            "8",
+            "--decontaminate",
+            "--alpha-matching",
+            "opaque-only",
+            "--crop",
+            "--crop-padding",
+            "5",
+        ]);
+
+        let profile = args.apply(Profile::default()).unwrap();
+
+        assert_eq!(
+            profile.tolerance,
+            Tolerance::new(DistanceMetric::ChannelMax, 3.0).unwrap()
+        );
+        assert_eq!(
+            profile.alpha_ramp,
+            AlphaRamp::new(DistanceMetric::ChannelMax, 3.0, 9.0)
+        );
+        assert_eq!(
+            profile.mode,
+            RemovalMode::Flood(FloodFill {
+                connectivity: Connectivity::Eight,
+                ..FloodFill::default()
+            })
+        );
+        assert!(profile.post_processing.decontaminate_edges);
+        assert_eq!(profile.post_processing.crop, Some(Crop { padding: 5 }));
+        assert_eq!(profile.alpha_matching, AlphaMatching::OpaqueOnly);
+    }
+
+    #[test]
+    fn no_flags_keep_base_settings() {
+        let base = Profile {
+            targets: vec![TargetColor::new(Color::new(1, 2, 3))],
+            mode: RemovalMode::Flood(FloodFill::default()),
+            ..Profile::default()
+        };
+        assert_eq!(settings(&[]).apply(base.clone()).unwrap(), base);
+    }
+
+    #[test]
+    fn invalid_flag_values_are_rejected() {
+        assert!(
+            settings(&["--tolerance=-1"])
+                .apply(Profile::default())
+                .is_err()
+        );
+        assert!(
+            settings(&["--tolerance", "5", "--outer-tolerance", "2"])
+                .apply(Profile::default())
+                .is_err()
+        );
+        assert!(
+            settings(&["--auto", "--min-confidence", "2"])
+                .apply(Profile::default())
+                .is_err()
+        );
+        assert!(parse(&["process", "in.png", "-o", "out", "--connectivity", "6"]).is_err());
+    }
+
+    #[test]
+    fn color_flags_override_the_environment_and_file() {
+        let cli = parse(&["process", "in.png", "-o", "out"]).unwrap();
+        let cli_with_colors = parse(&[
+            "process", "in.png", "-o", "out", "--color", "ffffff", "--color", "#fafafa",
+        ])
+        .unwrap();
+        let env = ConfigOverrides {
+            target_colors: vec![Rgba::new(0, 0, 255, 255)],
+            ..ConfigOverrides::default()
+        };
+        let targets = |cli: &Cli| {
+            let (config, _dir) = temp_config_with(cli.config_overrides().over(env.clone()));
+            config.save_target_color(&Color::new(1, 2, 3)).unwrap();
+            let Some(Command::Process(args)) = &cli.command else {
+                panic!("unexpected command: {:?}", cli.command);
+            };
+            args.settings.resolve(&config).unwrap().targets
+        };
+
+        assert_eq!(
+            targets(&cli_with_colors),
+            vec![
+                TargetColor::new(Color::new(255, 255, 255)),
+                TargetColor::new(Color::new(250, 250, 250)),
+            ]
+        );
+        assert_eq!(targets(&cli), vec![TargetColor::new(Color::new(0, 0, 255))]);
+    }
+
+    #[test]
+    fn no_config_keeps_the_overrides() {
+        let (config, _dir) = temp_config_with(ConfigOverrides {
+            target_colors: vec![Rgba::new(0, 0, 255, 255)],
+            ..ConfigOverrides::default()
+        });
+        config
+            .save_tolerance(&Tolerance::new(DistanceMetric::ChannelMax, 30.0).unwrap())
+            .unwrap();
+
+        let profile = settings(&["--no-config"]).resolve(&config).unwrap();
+
+        assert_eq!(
+            profile.targets,
+            vec![TargetColor::new(Color::new(0, 0, 255))]
+        );
+        assert_eq!(profile.tolerance, Tolerance::default());
+    }
+
+    #[test]
+    fn resolve_reads_named_profile() {
+        let (config, _dir) = temp_config();
+        let green = Profile {
+            targets: vec![TargetColor::new(Color::new(0, 255, 0))],
+            ..Profile::default()
+        };
+        config.save_profile("green", &green).unwrap();
+
+        assert_eq!(
+            settings(&["--profile", "green"]).resolve(&config),
+            Ok(green)
+        );
+        assert!(
+            settings(&["--profile", "missing"])
+                .resolve(&config)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn process_writes_outputs_and_fails_on_bad_input() {
+        let (config, dir) = temp_config();
+        let input_dir = dir.path().join("in");
+        let output_dir = dir.path().join("out");
+        fs::create_dir(&input_dir).unwrap();
+        let image = ImageData {
+            pixels: vec![255, 255, 255, 255, 0, 0, 0, 255],
+            width: 2,
+            height: 1,
+        };
+        write_png(&input_dir.join("a.png"), &image).unwrap();
+
+        let args = ProcessArgs {
+            inputs: vec![input_dir.clone()],
+            output: output_dir.clone(),
+            settings: SettingsArgs::default(),
+        };
+        assert_eq!(process(&args, &config), ExitCode::SUCCESS);
+        assert_eq!(
+            read_png(&output_dir.join("a.png")).unwrap().pixels,
+            vec![255, 255, 255, 0, 0, 0, 0, 255]
+        );
+
+        fs::write(input_dir.join("broken.png"), b"not a png").unwrap();
+        assert_eq!(process(&args, &config), ExitCode::FAILURE);
+    }
+
+    #[test]
+    fn process_refuses_colliding_outputs() {
+        let (config, dir) = temp_config();
+        let (first, second) = (dir.path().join("first"), dir.path().join("second"));
+        fs::create_dir(&first).unwrap();
+        fs::create_dir(&second).unwrap();
+        let image = ImageData {
+            pixels: vec![255, 255, 255, 255],
+            width: 1,
+            height: 1,
+        };
+        write_png(&first.join("a.png"), &image).unwrap();
+        write_png(&second.join("a.png"), &image).unwrap();
+
+        let output_dir = dir.path().join("out");
+        let args = ProcessArgs {
+            inputs: vec![first.clone(), second.clone()],
+            output: output_dir.clone(),
+            settings: SettingsArgs::default(),
+        };
+        assert_eq!(process(&args, &config), ExitCode::FAILURE);
+        assert!(!output_dir.exists());
+
+        let conflicts =
+            output_paths(&[first.join("a.png"), second.join("a.png")], &output_dir).unwrap_err();
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0].contains("would both be written"));
+    }
+
+    #[test]
+    fn process_refuses_to_overwrite_inputs() {
+        let (config, dir) = temp_config();
+        let input = dir.path().join("a.png");
+        let image = ImageData {
+            pixels: vec![255, 255, 255, 255],
+            width: 1,
+            height: 1,
+        };
+        write_png(&input, &image).unwrap();
+
+        let args = ProcessArgs {
+            inputs: vec![input.clone()],
+            output: dir.path().to_path_buf(),
+            settings: SettingsArgs::default(),
+        };
+        assert_eq!(process(&args, &config), ExitCode::FAILURE);
+        assert_eq!(read_png(&input).unwrap(), image);
+    }
+}
@@ -1,49 +1,88 @@
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Duration;
+use std::time::SystemTime;
 
 use log::{error, info, warn};
-use tao::event::Event;
+use tao::event::{Event, StartCause};
 use tao::event_loop::{ControlFlow, EventLoopBuilder};
 use tray_icon::TrayIconBuilder;
-use tray_icon::menu::{Menu, MenuEvent, MenuItem};
+use tray_icon::menu::{CheckMenuItem, IconMenuItem, Menu, MenuEvent, MenuId, MenuItem, Submenu};
 
-use crate::application::clipboard_service::{ClipboardService, ProcessResult};
-use crate::domain::port::{ClipboardPort, ConfigPort};
-
-/// Clipboard polling interval when enabled.
-const POLL_INTERVAL: Duration = Duration::from_millis(500);
+use crate::application::clipboard_service::{ClipboardService, ProcessResult, ServiceError};
+use crate::domain::history::{HistoryEntry, HistoryImage};
+use crate::domain::port::{
+    ClipboardPort, ClipboardWatcher, ConfigPort, ConfigWatcher, HistoryPort, ImageCodec,
+};
 
 /// User event type for the event loop.
 enum UserEvent {
     MenuEvent(MenuEvent),
+    /// The watcher reported a possible clipboard change.
+    ClipboardChanged,
+    /// The config watcher reported a change of the config file.
+    ConfigChanged,
 }
 
 /// Runs the system tray application.
 ///
-/// Creates a tray icon with a context menu (Enable/Disable, Quit) and
-/// polls the clipboard at regular intervals when enabled.
+/// Creates a tray icon with a context menu (Enable/Disable, Restore original,
+/// History, Profile, Quit) and processes the clipboard whenever `watcher`
+/// reports a change while enabled. The event loop sleeps in between.
+///
+/// "Restore original" puts the last processed image back on the clipboard as
+/// it was copied, and is only enabled while there is one to restore. The
+/// History submenu lists the recently processed images with a thumbnail, and
+/// copies the result or the original of any of them again.
+///
+/// The Profile submenu lists the configured profiles as radio items and is
+/// only shown when at least one profile is defined. It is rebuilt whenever
+/// the config is reloaded, so added, removed or renamed profiles show up.
+///
+/// The config is reloaded whenever `config_watcher` reports a change. An
+/// invalid edit is reported with its location, both in an alert and in a
+/// disabled item at the top of the menu, and the previous settings stay in
+/// effect; if processing was disabled because the config could not be loaded
+/// at all, fixing the file enables it again. The menu item is removed once
+/// the config reloads successfully.
 ///
 /// # Panics
 ///
 /// Panics if the event loop or tray icon cannot be created.
-pub fn run<C, G>(service: ClipboardService<C, G>)
+pub fn run<C, G, K, H, W, V>(service: ClipboardService<C, G, K, H>, watcher: W, config_watcher: V)
 where
     C: ClipboardPort + 'static,
     G: ConfigPort + 'static,
+    K: ImageCodec + 'static,
+    H: HistoryPort + 'static,
+    W: ClipboardWatcher,
+    V: ConfigWatcher,
 {
     let enabled = Arc::new(AtomicBool::new(true));
+    // Whether processing was disabled because the config could not be loaded
+    let mut paused_by_config = false;
 
     let event_loop = EventLoopBuilder::<UserEvent>::with_user_event().build();
 
     // Build context menu
     let toggle_item = MenuItem::new("Disable", true, None);
+    let restore_item = MenuItem::new("Restore original", false, None);
     let quit_item = MenuItem::new("Quit", true, None);
 
     let menu = Menu::new();
     menu.append(&toggle_item).expect("failed to add menu item");
+    menu.append(&restore_item).expect("failed to add menu item");
+
+    let mut history_menu = HistoryMenu::new();
+    menu.append(&history_menu.submenu)
+        .expect("failed to add menu item");
+
     menu.append(&quit_item).expect("failed to add menu item");
 
+    let mut profile_menu = ProfileMenu::new(&menu, &quit_item);
+    profile_menu.refresh(&service);
+
+    let mut config_error = ConfigErrorItem::new(&menu);
+
     // Load tray icon from embedded PNG
     let icon_bytes = include_bytes!("../../logo/tray_icon.png");
     let icon_image = image::load_from_memory(icon_bytes).expect("failed to load tray icon");
@@ -66,15 +105,33 @@ where
         let _ = proxy.send_event(UserEvent::MenuEvent(event));
     }));
 
+    // Forward clipboard change notifications to the event loop
+    let proxy = event_loop.create_proxy();
+    if let Err(e) = watcher.watch(move || {
+        let _ = proxy.send_event(UserEvent::ClipboardChanged);
+    }) {
+        show_alert("bgclipper: Clipboard Error", &e.to_string());
+    }
+
+    // Forward config file changes to the event loop
+    let proxy = event_loop.create_proxy();
+    if let Err(e) = config_watcher.watch(move || {
+        let _ = proxy.send_event(UserEvent::ConfigChanged);
+    }) {
+        warn!("config changes will not be picked up: {e}");
+    }
+
     let toggle_id = toggle_item.id().clone();
+    let restore_id = restore_item.id().clone();
     let quit_id = quit_item.id().clone();
 
     event_loop.run(move |event, _event_loop, control_flow| {
-        *control_flow = ControlFlow::WaitUntil(std::time::Instant::now() + POLL_INTERVAL);
+        *control_flow = ControlFlow::Wait;
 
         match event {
             Event::UserEvent(UserEvent::MenuEvent(event)) => {
                 if event.id() == &toggle_id {
+                    paused_by_config = false;
                     let was_enabled = enabled.fetch_xor(true, Ordering::Relaxed);
                     if was_enabled {
                         toggle_item.set_text("Enable");
@@ -82,35 +139,407 @@ where
                     } else {
                         toggle_item.set_text("Disable");
                         info!("monitoring enabled");
+                        // Catch up on anything copied while disabled
+                        paused_by_config = process(
+                            &service,
+                            &enabled,
+                            &toggle_item,
+                            &restore_item,
+                            &mut config_error,
+                        );
+                        history_menu.refresh(&service);
+                    }
+                } else if event.id() == &restore_id {
+                    match service.restore_last_original() {
+                        Ok(true) => info!("original image restored"),
+                        Ok(false) => {}
+                        Err(e) => error!("{e}"),
+                    }
+                    restore_item.set_enabled(service.has_original());
+                } else if let Some((id, image)) = history_menu.find(event.id()) {
+                    match service.copy_from_history(id, image) {
+                        Ok(true) => info!("history entry {id} copied ({image:?})"),
+                        Ok(false) => warn!("history entry {id} is no longer available"),
+                        Err(e) => error!("{e}"),
                     }
+                    history_menu.refresh(&service);
                 } else if event.id() == &quit_id {
                     info!("quit requested");
                     *control_flow = ControlFlow::Exit;
+                } else if let Some(name) = profile_menu.find(event.id()) {
+                    match service.switch_profile(name.as_deref()) {
+                        Ok(()) => info!(
+                            "switched to profile: {}",
+                            name.as_deref().unwrap_or("Default")
+                        ),
+                        Err(e) => error!("{e}"),
+                    }
+                    // Clicking toggles the item itself; re-sync all items with the config
+                    profile_menu.sync(&service);
                 }
             }
-            Event::NewEvents(tao::event::StartCause::ResumeTimeReached { .. }) => {
+            Event::NewEvents(StartCause::Init) | Event::UserEvent(UserEvent::ClipboardChanged) => {
                 if enabled.load(Ordering::Relaxed) {
-                    match service.process_clipboard() {
-                        Ok(ProcessResult::Processed) => {
-                            info!("clipboard image processed successfully");
-                        }
-                        Ok(ProcessResult::NoImage | ProcessResult::Skipped) => {}
-                        Err(e) if e.contains("config parse error") => {
-                            warn!("config parse error: {e}");
-                            show_alert("bgclipper: Config Error", &e);
-                            // Disable processing until user fixes config
-                            enabled.store(false, Ordering::Relaxed);
-                            toggle_item.set_text("Enable");
-                        }
-                        Err(e) => error!("{e}"),
-                    }
+                    paused_by_config |= process(
+                        &service,
+                        &enabled,
+                        &toggle_item,
+                        &restore_item,
+                        &mut config_error,
+                    );
                 }
+                history_menu.refresh(&service);
             }
+            Event::UserEvent(UserEvent::ConfigChanged) => match service.reload_config() {
+                Ok(true) => {
+                    info!("config reloaded");
+                    config_error.clear();
+                    profile_menu.refresh(&service);
+                    if paused_by_config {
+                        enabled.store(true, Ordering::Relaxed);
+                        toggle_item.set_text("Disable");
+                        info!("monitoring enabled");
+                        paused_by_config = process(
+                            &service,
+                            &enabled,
+                            &toggle_item,
+                            &restore_item,
+                            &mut config_error,
+                        );
+                        history_menu.refresh(&service);
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    warn!("{e}");
+                    config_error.show(&e);
+                    let message = if paused_by_config {
+                        e.to_string()
+                    } else {
+                        format!("{e}\n\nThe previous settings stay in effect.")
+                    };
+                    show_alert("bgclipper: Config Error", &message);
+                }
+            },
             _ => {}
         }
     });
 }
 
+/// Processes the clipboard once, disabling processing on config errors and
+/// enabling the restore item once there is an original to restore.
+///
+/// Config errors are also shown in `config_error`.
+///
+/// Returns `true` if processing was disabled because the config cannot be
+/// loaded.
+fn process<C, G, K, H>(
+    service: &ClipboardService<C, G, K, H>,
+    enabled: &AtomicBool,
+    toggle_item: &MenuItem,
+    restore_item: &MenuItem,
+    config_error: &mut ConfigErrorItem,
+) -> bool
+where
+    C: ClipboardPort + 'static,
+    G: ConfigPort + 'static,
+    K: ImageCodec,
+    H: HistoryPort + 'static,
+{
+    let paused = match service.process_clipboard() {
+        Ok(ProcessResult::Processed) => {
+            info!("clipboard image processed successfully");
+            false
+        }
+        Ok(ProcessResult::Undetected) => {
+            info!("background not detected confidently, image left unchanged");
+            false
+        }
+        Ok(ProcessResult::NoImage | ProcessResult::Skipped) => false,
+        Err(e @ ServiceError::ConfigLoad(_)) => {
+            warn!("{e}");
+            config_error.show(&e);
+            show_alert("bgclipper: Config Error", &e.to_string());
+            // Disable processing until the config file is fixed
+            enabled.store(false, Ordering::Relaxed);
+            toggle_item.set_text("Enable");
+            true
+        }
+        Err(e) => {
+            error!("{e}");
+            false
+        }
+    };
+    restore_item.set_enabled(service.has_original());
+    paused
+}
+
+/// The Profile submenu: radio items for the top-level settings ("Default")
+/// and each profile, in the menu only while at least one profile is defined.
+struct ProfileMenu {
+    menu: Menu,
+    submenu: Submenu,
+    /// Id of the item the submenu is shown above.
+    before: MenuId,
+    /// Profile name of each item, `None` for the top-level settings.
+    items: Vec<(Option<String>, CheckMenuItem)>,
+    /// Whether the submenu is in the menu.
+    shown: bool,
+}
+
+impl ProfileMenu {
+    fn new(menu: &Menu, before: &MenuItem) -> Self {
+        Self {
+            menu: menu.clone(),
+            submenu: Submenu::new("Profile", true),
+            before: before.id().clone(),
+            items: Vec::new(),
+            shown: false,
+        }
+    }
+
+    /// Returns the profile a clicked item switches to, `Some(None)` for the
+    /// top-level settings.
+    fn find(&self, id: &MenuId) -> Option<Option<String>> {
+        self.items
+            .iter()
+            .find(|(_, item)| item.id() == id)
+            .map(|(name, _)| name.clone())
+    }
+
+    /// Rebuilds the items if the profiles changed, e.g. after the config was
+    /// edited, shows or hides the submenu accordingly and checks the active
+    /// profile.
+    fn refresh<C, G, K, H>(&mut self, service: &ClipboardService<C, G, K, H>)
+    where
+        C: ClipboardPort,
+        G: ConfigPort,
+        K: ImageCodec,
+        H: HistoryPort,
+    {
+        let names = match service.list_profiles() {
+            Ok(names) => names,
+            Err(e) => {
+                warn!("{e}");
+                return;
+            }
+        };
+        let shown_names: Vec<&str> = self
+            .items
+            .iter()
+            .filter_map(|(name, _)| name.as_deref())
+            .collect();
+        if self.items.is_empty() || names != shown_names {
+            while self.submenu.remove_at(0).is_some() {}
+            self.items = std::iter::once(None)
+                .chain(names.into_iter().map(Some))
+                .map(|name| {
+                    let label = name.as_deref().unwrap_or("Default");
+                    let item = CheckMenuItem::new(label, true, false, None);
+                    self.submenu.append(&item).expect("failed to add menu item");
+                    (name, item)
+                })
+                .collect();
+        }
+
+        let wanted = self.items.len() > 1;
+        if wanted != self.shown {
+            let result = if wanted {
+                let items = self.menu.items();
+                let position = items
+                    .iter()
+                    .position(|item| item.id() == &self.before)
+                    .unwrap_or(items.len());
+                self.menu.insert(&self.submenu, position)
+            } else {
+                self.menu.remove(&self.submenu)
+            };
+            match result {
+                Ok(()) => self.shown = wanted,
+                Err(e) => warn!("cannot update the Profile menu: {e}"),
+            }
+        }
+        self.sync(service);
+    }
+
+    /// Checks the item of the active profile and unchecks the others.
+    fn sync<C, G, K, H>(&self, service: &ClipboardService<C, G, K, H>)
+    where
+        C: ClipboardPort,
+        G: ConfigPort,
+        K: ImageCodec,
+        H: HistoryPort,
+    {
+        let active = service.active_profile().unwrap_or_else(|e| {
+            error!("{e}");
+            None
+        });
+        for (name, item) in &self.items {
+            item.set_checked(*name == active);
+        }
+    }
+}
+
+/// A disabled item at the top of the menu describing the last config error,
+/// since alerts are not shown on every platform.
+struct ConfigErrorItem {
+    menu: Menu,
+    item: MenuItem,
+    shown: bool,
+}
+
+impl ConfigErrorItem {
+    /// Longest label shown, in characters; longer errors are cut off.
+    const MAX_LABEL_CHARS: usize = 80;
+
+    fn new(menu: &Menu) -> Self {
+        Self {
+            menu: menu.clone(),
+            item: MenuItem::new("", false, None),
+            shown: false,
+        }
+    }
+
+    /// Shows `error` at the top of the menu, replacing any previous one.
+    fn show(&mut self, error: &(dyn std::error::Error + 'static)) {
+        self.item
+            .set_text(config_error_label(error, Self::MAX_LABEL_CHARS));
+        if !self.shown {
+            match self.menu.prepend(&self.item) {
+                Ok(()) => self.shown = true,
+                Err(e) => warn!("cannot show config error in the menu: {e}"),
+            }
+        }
+    }
+
+    /// Removes the error from the menu, if shown.
+    fn clear(&mut self) {
+        if self.shown {
+            if let Err(e) = self.menu.remove(&self.item) {
+                warn!("cannot remove config error from the menu: {e}");
+            }
+            self.shown = false;
+        }
+    }
+}
+
+/// Describes a config error in one menu label of at most `max_chars`
+/// characters, e.g. `Config error: line 3, column 13: invalid type`.
+fn config_error_label(error: &(dyn std::error::Error + 'static), max_chars: usize) -> String {
+    // Skip the "failed to load config" and "config parse error" wrappers;
+    // a parse error starts with its location
+    let inner = error.source().unwrap_or(error);
+    let cause = inner.source().unwrap_or(inner);
+    let mut label = format!("Config error: {cause}");
+    if let Some((end, _)) = label.char_indices().nth(max_chars) {
+        label.truncate(end);
+        label.push('…');
+    }
+    // `&` marks a mnemonic in menu labels
+    label.replace('&', "&&")
+}
+
+/// The History submenu: one submenu per processed image, newest first, with
+/// items to copy its result or original.
+struct HistoryMenu {
+    submenu: Submenu,
+    /// Menu item ids with the history entry and image they copy.
+    items: Vec<(MenuId, u64, HistoryImage)>,
+    /// Ids of the entries currently shown, to skip needless rebuilds.
+    shown: Vec<u64>,
+}
+
+impl HistoryMenu {
+    fn new() -> Self {
+        let submenu = Submenu::new("History", true);
+        let empty = MenuItem::new("No images yet", false, None);
+        submenu.append(&empty).expect("failed to add menu item");
+        Self {
+            submenu,
+            items: Vec::new(),
+            shown: Vec::new(),
+        }
+    }
+
+    /// Returns the history entry and image a clicked item copies.
+    fn find(&self, id: &MenuId) -> Option<(u64, HistoryImage)> {
+        self.items
+            .iter()
+            .find(|(item, _, _)| item == id)
+            .map(|(_, entry, image)| (*entry, *image))
+    }
+
+    /// Rebuilds the submenu if the history changed.
+    fn refresh<C, G, K, H>(&mut self, service: &ClipboardService<C, G, K, H>)
+    where
+        C: ClipboardPort,
+        G: ConfigPort,
+        K: ImageCodec,
+        H: HistoryPort,
+    {
+        let entries = match service.history() {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("{e}");
+                return;
+            }
+        };
+        let ids: Vec<u64> = entries.iter().map(|entry| entry.id).collect();
+        if ids == self.shown || entries.is_empty() {
+            return;
+        }
+
+        while self.submenu.remove_at(0).is_some() {}
+        self.items.clear();
+        for entry in &entries {
+            let icon = tray_icon::menu::Icon::from_rgba(
+                entry.thumbnail.pixels.clone(),
+                entry.thumbnail.width,
+                entry.thumbnail.height,
+            )
+            .ok();
+            let result_item = IconMenuItem::new("Copy result", true, icon, None);
+            let original_item = MenuItem::new("Copy original", true, None);
+            let entry_menu = Submenu::new(history_label(entry), true);
+            entry_menu
+                .append(&result_item)
+                .expect("failed to add menu item");
+            entry_menu
+                .append(&original_item)
+                .expect("failed to add menu item");
+            self.submenu
+                .append(&entry_menu)
+                .expect("failed to add menu item");
+            self.items
+                .push((result_item.id().clone(), entry.id, HistoryImage::Result));
+            self.items
+                .push((original_item.id().clone(), entry.id, HistoryImage::Original));
+        }
+        self.shown = ids;
+    }
+}
+
+/// Describes a history entry, e.g. `14:03 UTC · 1920×1080 · 52340 px · logos`.
+fn history_label(entry: &HistoryEntry) -> String {
+    let secs = entry
+        .timestamp
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let mut label = format!(
+        "{:02}:{:02} UTC · {}×{} · {} px",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        entry.width,
+        entry.height,
+        entry.changed_pixels
+    );
+    if let Some(profile) = &entry.profile {
+        label.push_str(" · ");
+        label.push_str(profile);
+    }
+    label
+}
+
 /// Shows a native alert dialog.
 ///
 /// Uses `osascript` on macOS and `msg` on Windows as a simple cross-platform approach.
@@ -4,6 +4,8 @@ use arboard::Clipboard;
 use bgclipper::application::clipboard_service::{ClipboardService, ProcessResult};
 use bgclipper::domain::color::Color;
 use bgclipper::domain::port::ConfigPort;
+use bgclipper::domain::profile::Profile;
+use bgclipper::domain::target::TargetColor;
 use bgclipper::infrastructure::clipboard::ArboardClipboardProvider;
 
 // -- Inline ConfigPort for testing (returns a fixed color) --
@@ -27,11 +29,34 @@ impl std::error::Error for FixedConfigError {}
 impl ConfigPort for FixedConfig {
     type Error = FixedConfigError;
 
-    fn load_target_color(&self) -> Result<Color, Self::Error> {
-        Ok(self.color)
+    fn load_settings(&self) -> Result<Profile, Self::Error> {
+        Ok(Profile {
+            targets: vec![TargetColor::new(self.color)],
+            ..Profile::default()
+        })
     }
 
-    fn save_target_color(&self, _color: &Color) -> Result<(), Self::Error> {
+    fn save_settings(&self, _settings: &Profile) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn list_profiles(&self) -> Result<Vec<String>, Self::Error> {
+        Ok(Vec::new())
+    }
+
+    fn load_profile(&self, _name: &str) -> Result<Option<Profile>, Self::Error> {
+        Ok(None)
+    }
+
+    fn save_profile(&self, _name: &str, _profile: &Profile) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn load_active_profile(&self) -> Result<Option<String>, Self::Error> {
+        Ok(None)
+    }
+
+    fn save_active_profile(&self, _name: Option<&str>) -> Result<(), Self::Error> {
         Ok(())
     }
 
@@ -0,0 +1,314 @@
+use bgclipper::application::clipboard_service::{ClipboardService, ProcessResult, ServiceError};
+use bgclipper::domain::history::HistoryImage;
+use bgclipper::domain::port::{ClipboardPort, ImageCodec, ImageData, PNG_MIME_TYPE};
+use bgclipper::domain::representation::HTML_MIME_TYPE;
+use bgclipper::infrastructure::config::TomlConfigProvider;
+use bgclipper::infrastructure::directory_clipboard::DirectoryClipboard;
+use bgclipper::infrastructure::history::HistoryStore;
+use bgclipper::infrastructure::image_file;
+use bgclipper::infrastructure::memory_clipboard::{ClipboardOperation, InMemoryClipboard};
+use bgclipper::infrastructure::png_codec::{PngCodec, PngMetadata};
+
+/// 2x1 image: white background pixel, black foreground pixel.
+fn sample() -> ImageData {
+    ImageData {
+        pixels: vec![255, 255, 255, 255, 0, 0, 0, 255],
+        width: 2,
+        height: 1,
+    }
+}
+
+/// The sample with the white (default target) pixel made transparent.
+fn expected() -> ImageData {
+    ImageData {
+        pixels: vec![255, 255, 255, 0, 0, 0, 0, 255],
+        width: 2,
+        height: 1,
+    }
+}
+
+/// Default settings from a config path that does not exist.
+fn default_config(dir: &tempfile::TempDir) -> TomlConfigProvider {
+    TomlConfigProvider::with_path(dir.path().join("config.toml"))
+}
+
+#[test]
+fn in_memory_clipboard_end_to_end() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let clipboard = InMemoryClipboard::new();
+    clipboard.copy(sample());
+    let service = ClipboardService::new(clipboard, default_config(&config_dir));
+
+    assert_eq!(
+        service.process_clipboard().unwrap(),
+        ProcessResult::Processed
+    );
+    // Our own write is not processed again
+    assert_eq!(service.process_clipboard().unwrap(), ProcessResult::Skipped);
+}
+
+#[test]
+fn in_memory_clipboard_records_written_image() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let clipboard = InMemoryClipboard::new();
+    clipboard.copy(sample());
+    let service = ClipboardService::new(&clipboard, default_config(&config_dir));
+
+    service.process_clipboard().unwrap();
+
+    assert_eq!(clipboard.writes(), vec![expected()]);
+}
+
+#[test]
+fn in_memory_clipboard_write_failure_is_retried() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let clipboard = InMemoryClipboard::new();
+    clipboard.copy(sample());
+    clipboard.fail_next(ClipboardOperation::SetImage, "clipboard locked");
+    let service = ClipboardService::new(&clipboard, default_config(&config_dir));
+
+    assert!(matches!(
+        service.process_clipboard(),
+        Err(ServiceError::Write(_))
+    ));
+    assert_eq!(
+        service.process_clipboard().unwrap(),
+        ProcessResult::Processed
+    );
+    assert_eq!(clipboard.image(), Some(expected()));
+}
+
+#[test]
+fn directory_clipboard_end_to_end() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let images = tempfile::tempdir().unwrap();
+    let path = images.path().join("screenshot.png");
+    image_file::write_png(&path, &sample()).unwrap();
+    let service = ClipboardService::new(
+        DirectoryClipboard::new(images.path()),
+        default_config(&config_dir),
+    );
+
+    assert_eq!(
+        service.process_clipboard().unwrap(),
+        ProcessResult::Processed
+    );
+    assert_eq!(service.process_clipboard().unwrap(), ProcessResult::Skipped);
+    assert_eq!(image_file::read_png(&path).unwrap(), expected());
+}
+
+#[test]
+fn invalid_config_edit_keeps_previous_settings() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let config_path = config_dir.path().join("config.toml");
+    std::fs::write(&config_path, "target_color = \"black\"\n").unwrap();
+    let clipboard = InMemoryClipboard::new();
+    let service = ClipboardService::new(
+        &clipboard,
+        TomlConfigProvider::with_path(config_path.clone()),
+    );
+    clipboard.copy(sample());
+    service.process_clipboard().unwrap();
+    assert_eq!(clipboard.image().unwrap().pixels[7], 0);
+
+    std::fs::write(&config_path, "target_color = \"white\n").unwrap();
+    let err = service.reload_config().unwrap_err();
+    assert!(matches!(err, ServiceError::ConfigLoad(_)));
+    assert!(err.to_string().contains("line 1, column"));
+
+    // Still removing black
+    clipboard.copy(sample());
+    service.process_clipboard().unwrap();
+    assert_eq!(clipboard.image().unwrap().pixels[7], 0);
+
+    std::fs::write(&config_path, "target_color = \"white\"\n").unwrap();
+    assert!(service.reload_config().unwrap());
+    clipboard.copy(sample());
+    service.process_clipboard().unwrap();
+    assert_eq!(clipboard.image(), Some(expected()));
+}
+
+/// The sample as PNG with a 144 DPI `pHYs` chunk, as a Retina screenshot has.
+fn retina_png() -> Vec<u8> {
+    let mut png = Vec::new();
+    image_file::write_png_to(&mut png, &sample()).unwrap();
+    // pHYs: 5669 pixels per meter on both axes, unit = meter
+    let mut chunk = 9u32.to_be_bytes().to_vec();
+    chunk.extend_from_slice(b"pHYs");
+    chunk.extend_from_slice(&5669u32.to_be_bytes());
+    chunk.extend_from_slice(&5669u32.to_be_bytes());
+    chunk.push(1);
+    chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+    // Insert after the signature (8 bytes) and IHDR chunk (25 bytes)
+    png.splice(33..33, chunk);
+    png
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn assert_retina(metadata: &PngMetadata) {
+    let (x, y) = metadata.dpi().expect("DPI should be preserved");
+    assert!((x - 144.0).abs() < 0.01 && (y - 144.0).abs() < 0.01);
+}
+
+#[test]
+fn encoded_png_keeps_dpi_through_processing() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let clipboard = InMemoryClipboard::new();
+    clipboard.copy_encoded(PNG_MIME_TYPE, retina_png());
+    assert_retina(&PngMetadata::read(&retina_png()));
+    let service =
+        ClipboardService::new(&clipboard, default_config(&config_dir)).with_codec(PngCodec);
+
+    assert_eq!(
+        service.process_clipboard().unwrap(),
+        ProcessResult::Processed
+    );
+    assert_eq!(service.process_clipboard().unwrap(), ProcessResult::Skipped);
+
+    let png = clipboard
+        .get_encoded(PNG_MIME_TYPE)
+        .unwrap()
+        .expect("written as encoded PNG");
+    let (image, metadata) = PngCodec.decode(&png).unwrap();
+    assert_eq!(image, expected());
+    assert_retina(&metadata);
+    assert_eq!(clipboard.writes(), vec![expected()]);
+}
+
+#[test]
+fn codec_reads_pixels_without_encoded_content() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let clipboard = InMemoryClipboard::new();
+    clipboard.copy(sample());
+    let service =
+        ClipboardService::new(&clipboard, default_config(&config_dir)).with_codec(PngCodec);
+
+    assert_eq!(
+        service.process_clipboard().unwrap(),
+        ProcessResult::Processed
+    );
+
+    assert_eq!(clipboard.writes(), vec![expected()]);
+    let png = clipboard.get_encoded(PNG_MIME_TYPE).unwrap().unwrap();
+    assert_eq!(PngCodec.decode(&png).unwrap().0, expected());
+}
+
+#[test]
+fn codec_offers_png_and_html_together() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let clipboard = InMemoryClipboard::new();
+    clipboard.copy(sample());
+    let service =
+        ClipboardService::new(&clipboard, default_config(&config_dir)).with_codec(PngCodec);
+
+    service.process_clipboard().unwrap();
+
+    let representations = clipboard.representations();
+    let mime_types: Vec<&str> = representations
+        .iter()
+        .map(|r| r.mime_type.as_str())
+        .collect();
+    assert_eq!(mime_types, [PNG_MIME_TYPE, HTML_MIME_TYPE]);
+    assert_eq!(representations[1], representations[0].to_html_image());
+}
+
+#[test]
+fn directory_clipboard_keeps_dpi() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let images = tempfile::tempdir().unwrap();
+    let path = images.path().join("screenshot.png");
+    std::fs::write(&path, retina_png()).unwrap();
+    let service = ClipboardService::new(
+        DirectoryClipboard::new(images.path()),
+        default_config(&config_dir),
+    )
+    .with_codec(PngCodec);
+
+    assert_eq!(
+        service.process_clipboard().unwrap(),
+        ProcessResult::Processed
+    );
+
+    let (image, metadata) = PngCodec.decode(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(image, expected());
+    assert_retina(&metadata);
+}
+
+#[test]
+fn restored_original_keeps_dpi_and_is_not_reprocessed() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let clipboard = InMemoryClipboard::new();
+    clipboard.copy_encoded(PNG_MIME_TYPE, retina_png());
+    let service =
+        ClipboardService::new(&clipboard, default_config(&config_dir)).with_codec(PngCodec);
+    service.process_clipboard().unwrap();
+
+    assert!(service.restore_last_original().unwrap());
+    assert_eq!(service.process_clipboard().unwrap(), ProcessResult::Skipped);
+
+    assert_eq!(clipboard.writes(), vec![expected(), sample()]);
+    let png = clipboard.get_encoded(PNG_MIME_TYPE).unwrap().unwrap();
+    let (image, metadata) = PngCodec.decode(&png).unwrap();
+    assert_eq!(image, sample());
+    assert_retina(&metadata);
+}
+
+#[test]
+fn history_entries_keep_dpi_when_copied_again() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let history_dir = tempfile::tempdir().unwrap();
+    for history in [
+        HistoryStore::in_memory(5),
+        HistoryStore::persistent(history_dir.path(), 5).unwrap(),
+    ] {
+        let clipboard = InMemoryClipboard::new();
+        clipboard.copy_encoded(PNG_MIME_TYPE, retina_png());
+        let service = ClipboardService::new(&clipboard, default_config(&config_dir))
+            .with_codec(PngCodec)
+            .with_history(history);
+        service.process_clipboard().unwrap();
+        let id = service.history().unwrap()[0].id;
+
+        for (which, image) in [
+            (HistoryImage::Original, sample()),
+            (HistoryImage::Result, expected()),
+        ] {
+            assert!(service.copy_from_history(id, which).unwrap());
+            let png = clipboard.get_encoded(PNG_MIME_TYPE).unwrap().unwrap();
+            let (decoded, metadata) = PngCodec.decode(&png).unwrap();
+            assert_eq!(decoded, image);
+            assert_retina(&metadata);
+        }
+    }
+}
+
+#[test]
+fn failed_restore_can_be_retried() {
+    let config_dir = tempfile::tempdir().unwrap();
+    let clipboard = InMemoryClipboard::new();
+    clipboard.copy(sample());
+    let service = ClipboardService::new(&clipboard, default_config(&config_dir));
+    service.process_clipboard().unwrap();
+    clipboard.fail_next(ClipboardOperation::SetImage, "clipboard locked");
+
+    assert!(matches!(
+        service.restore_last_original(),
+        Err(ServiceError::Write(_))
+    ));
+    assert!(service.restore_last_original().unwrap());
+    assert_eq!(clipboard.image(), Some(sample()));
+}